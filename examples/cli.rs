@@ -190,7 +190,7 @@ fn start_capture<T>(
     draw_border: DrawBorderSettings,
     settings: CaptureSettings,
 ) where
-    T: TryInto<GraphicsCaptureItem>,
+    T: TryInto<GraphicsCaptureItem> + 'static,
 {
     let capture_settings = Settings::new(
         capture_item,
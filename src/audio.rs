@@ -0,0 +1,994 @@
+use std::{
+    collections::VecDeque,
+    ptr, slice,
+    sync::{
+        atomic::{self, AtomicBool},
+        mpsc, Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use windows::{
+    core::GUID,
+    Win32::{
+        Devices::FunctionDiscovery::PKEY_Device_FriendlyName,
+        Media::Audio::{
+            eCapture, eConsole, eRender, IAudioCaptureClient, IAudioClient, IMMDevice,
+            IMMDeviceEnumerator, MMDeviceEnumerator, AUDCLNT_BUFFERFLAGS_SILENT,
+            AUDCLNT_SHAREMODE_SHARED, AUDCLNT_STREAMFLAGS_LOOPBACK, DEVICE_STATE_ACTIVE,
+            WAVEFORMATEX, WAVEFORMATEXTENSIBLE, WAVE_FORMAT_PCM,
+        },
+        System::Com::{
+            CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, CLSCTX_ALL,
+            COINIT_MULTITHREADED, STGM_READ,
+        },
+    },
+};
+
+use crate::encoder::{VideoEncoder, VideoEncoderError};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("The sample format of the audio device is not supported")]
+    UnsupportedFormat,
+    #[error("Sample rate and channel count must be at least one")]
+    InvalidOutputFormat,
+    #[error("No active capture device named {0}")]
+    DeviceNotFound(String),
+    #[error("Audio capture thread panicked")]
+    ThreadPanicked,
+    #[error("Video encoder error: {0}")]
+    VideoEncoderError(#[from] VideoEncoderError),
+    #[error("Windows API error: {0}")]
+    WindowsError(#[from] windows::core::Error),
+}
+
+/// The format tag of a `WAVEFORMATEX` holding IEEE float samples.
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+/// The format tag of a `WAVEFORMATEX` that is followed by the rest of a `WAVEFORMATEXTENSIBLE`.
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+/// The sub format of a `WAVEFORMATEXTENSIBLE` holding integer PCM samples.
+const KSDATAFORMAT_SUBTYPE_PCM: GUID = GUID::from_u128(0x00000001_0000_0010_8000_00aa00389b71);
+/// The sub format of a `WAVEFORMATEXTENSIBLE` holding IEEE float samples.
+const KSDATAFORMAT_SUBTYPE_IEEE_FLOAT: GUID =
+    GUID::from_u128(0x00000003_0000_0010_8000_00aa00389b71);
+
+/// The length of the capture buffer in 100 nanosecond units.
+const CAPTURE_BUFFER_DURATION: i64 = 10_000_000;
+/// The gap in milliseconds between two packets of a mixer source that is filled with silence.
+const MIXER_GAP_TOLERANCE_MS: u64 = 20;
+/// The time in milliseconds a mixer source can stay behind the other before it is treated as silent.
+const MIXER_LATENCY_MS: u64 = 100;
+/// The time the pump thread waits between reads of the loopback buffer.
+const PUMP_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The sample formats the audio engine mixes in.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+enum SampleFormat {
+    Float32,
+    Int16,
+    Int32,
+}
+
+/// Captures the audio played on the default render device, or recorded by a capture device.
+///
+/// The samples are converted from the mix format of the device to interleaved 16-bit PCM with the
+/// requested sample rate and channel count, which is what `AudioSettingsBuilder` configures for
+/// `VideoEncoder`. Channels are mapped by position, a mono output averages the input channels.
+/// Resampling is linear, which is good enough for screen recordings.
+///
+/// # Example
+/// ```no_run
+/// use windows_capture::audio::LoopbackCapture;
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut capture = LoopbackCapture::new(48000, 2)?;
+///     capture.start()?;
+///
+///     let mut buffer = Vec::new();
+///     if let Some(timespan) = capture.read(&mut buffer)? {
+///         println!("Captured {} bytes at {timespan}", buffer.len());
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+pub struct LoopbackCapture {
+    audio_client: IAudioClient,
+    capture_client: IAudioCaptureClient,
+    sample_format: SampleFormat,
+    resampler: Resampler,
+    // Dropped Last So The Audio Client Is Released Before COM Is Uninitialized
+    _com: ComGuard,
+}
+
+impl LoopbackCapture {
+    /// Open the default render device in loopback mode.
+    ///
+    /// COM is initialized on the current thread as multithreaded if it isn't initialized yet, and
+    /// uninitialized again when the capture is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The sample rate of the delivered samples.
+    /// * `channel_count` - The number of channels of the delivered samples.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::UnsupportedFormat` if the device mixes in a sample format that can't be
+    /// converted, or an `Error::WindowsError` if the device can't be opened.
+    #[inline]
+    pub fn new(sample_rate: u32, channel_count: u32) -> Result<Self, Error> {
+        let com = ComGuard::new();
+        let device =
+            unsafe { Self::device_enumerator()?.GetDefaultAudioEndpoint(eRender, eConsole)? };
+
+        Self::open(
+            com,
+            device,
+            AUDCLNT_STREAMFLAGS_LOOPBACK,
+            sample_rate,
+            channel_count,
+        )
+    }
+
+    /// Open an active capture device, like a microphone, by its friendly name or endpoint ID.
+    ///
+    /// Samples are delivered the same way as the loopback samples of `new`.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The friendly name, for example `Microphone (USB Audio)`, or the endpoint ID
+    ///   of the capture device.
+    /// * `sample_rate` - The sample rate of the delivered samples.
+    /// * `channel_count` - The number of channels of the delivered samples.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::DeviceNotFound` if no active capture device matches `device`, otherwise
+    /// the same errors as `new`.
+    #[inline]
+    pub fn from_capture_device(
+        device: &str,
+        sample_rate: u32,
+        channel_count: u32,
+    ) -> Result<Self, Error> {
+        let com = ComGuard::new();
+        let capture_device = Self::capture_device(device)?;
+
+        Self::open(com, capture_device, 0, sample_rate, channel_count)
+    }
+
+    // Find An Active Capture Device By Its Friendly Name Or Endpoint ID
+    fn capture_device(device: &str) -> Result<IMMDevice, Error> {
+        let enumerator = Self::device_enumerator()?;
+        let devices = unsafe { enumerator.EnumAudioEndpoints(eCapture, DEVICE_STATE_ACTIVE)? };
+
+        for i in 0..unsafe { devices.GetCount()? } {
+            let capture_device = unsafe { devices.Item(i)? };
+
+            let id = unsafe { capture_device.GetId()? };
+            let id_matches = unsafe { id.to_string() }.is_ok_and(|id| id == device);
+            unsafe { CoTaskMemFree(Some(id.0.cast_const().cast())) };
+
+            let name_matches = unsafe { capture_device.OpenPropertyStore(STGM_READ) }
+                .and_then(|store| unsafe { store.GetValue(&PKEY_Device_FriendlyName) })
+                .is_ok_and(|name| name.to_string() == device);
+
+            if id_matches || name_matches {
+                return Ok(capture_device);
+            }
+        }
+
+        Err(Error::DeviceNotFound(device.to_string()))
+    }
+
+    // Create The Device Enumerator, COM Must Be Initialized
+    fn device_enumerator() -> Result<IMMDeviceEnumerator, Error> {
+        Ok(unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)? })
+    }
+
+    // Open A Shared Mode Stream On A Device, The Device Is Released Before COM On Failure
+    fn open(
+        com: ComGuard,
+        device: IMMDevice,
+        stream_flags: u32,
+        sample_rate: u32,
+        channel_count: u32,
+    ) -> Result<Self, Error> {
+        if sample_rate == 0 || channel_count == 0 {
+            return Err(Error::InvalidOutputFormat);
+        }
+
+        let audio_client: IAudioClient = unsafe { device.Activate(CLSCTX_ALL, None)? };
+
+        let mix_format = unsafe { audio_client.GetMixFormat()? };
+        let format = unsafe { Self::sample_format(mix_format) };
+        let result = format.and_then(|(sample_format, format)| {
+            unsafe {
+                audio_client.Initialize(
+                    AUDCLNT_SHAREMODE_SHARED,
+                    stream_flags,
+                    CAPTURE_BUFFER_DURATION,
+                    0,
+                    mix_format,
+                    None,
+                )?;
+            };
+
+            Ok((sample_format, format))
+        });
+        unsafe { CoTaskMemFree(Some(mix_format.cast_const().cast())) };
+        let (sample_format, format) = result?;
+
+        let capture_client = unsafe { audio_client.GetService::<IAudioCaptureClient>()? };
+
+        Ok(Self {
+            audio_client,
+            capture_client,
+            sample_format,
+            resampler: Resampler::new(
+                format.nSamplesPerSec,
+                usize::from(format.nChannels),
+                sample_rate,
+                channel_count as usize,
+            ),
+            _com: com,
+        })
+    }
+
+    /// Start capturing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::WindowsError` if the audio client fails to start.
+    #[inline]
+    pub fn start(&self) -> Result<(), Error> {
+        unsafe { self.audio_client.Start()? };
+
+        Ok(())
+    }
+
+    /// Stop capturing, samples that were not read yet are kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::WindowsError` if the audio client fails to stop.
+    #[inline]
+    pub fn stop(&self) -> Result<(), Error> {
+        unsafe { self.audio_client.Stop()? };
+
+        Ok(())
+    }
+
+    /// Read the samples captured since the previous read.
+    ///
+    /// The loopback stream only delivers samples while something is playing, silence in between
+    /// is not filled in.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - The buffer the interleaved 16-bit PCM samples are written to, it is cleared first.
+    ///
+    /// # Returns
+    ///
+    /// Returns the QPC time of the first sample in 100 nanosecond units, the same timebase as
+    /// `Frame::system_relative_time`, or `None` if no samples were captured.
+    #[inline]
+    pub fn read(&mut self, out: &mut Vec<u8>) -> Result<Option<i64>, Error> {
+        out.clear();
+
+        let mut samples = Vec::new();
+        let mut timespan = None;
+        loop {
+            let packet_size = unsafe { self.capture_client.GetNextPacketSize()? };
+            if packet_size == 0 {
+                break;
+            }
+
+            let mut data = ptr::null_mut();
+            let mut frame_count = 0;
+            let mut flags = 0;
+            let mut qpc_position = 0;
+            unsafe {
+                self.capture_client.GetBuffer(
+                    &mut data,
+                    &mut frame_count,
+                    &mut flags,
+                    None,
+                    Some(&mut qpc_position),
+                )?;
+            };
+
+            if timespan.is_none() {
+                timespan = i64::try_from(qpc_position).ok();
+            }
+
+            let sample_count = frame_count as usize * self.resampler.source_channel_count;
+            if flags & AUDCLNT_BUFFERFLAGS_SILENT.0 as u32 != 0 {
+                samples.resize(samples.len() + sample_count, 0.0);
+            } else {
+                unsafe { self.push_samples(data, sample_count, &mut samples) };
+            }
+
+            unsafe { self.capture_client.ReleaseBuffer(frame_count)? };
+        }
+
+        if samples.is_empty() {
+            return Ok(None);
+        }
+
+        self.resampler.resample(&samples, out);
+
+        Ok(timespan)
+    }
+
+    // Convert The Samples Of A Packet To Floats
+    unsafe fn push_samples(&self, data: *const u8, sample_count: usize, samples: &mut Vec<f32>) {
+        match self.sample_format {
+            SampleFormat::Float32 => {
+                let data = slice::from_raw_parts(data.cast::<f32>(), sample_count);
+                samples.extend_from_slice(data);
+            }
+            SampleFormat::Int16 => {
+                let data = slice::from_raw_parts(data.cast::<i16>(), sample_count);
+                samples.extend(data.iter().map(|sample| f32::from(*sample) / 32768.0));
+            }
+            SampleFormat::Int32 => {
+                let data = slice::from_raw_parts(data.cast::<i32>(), sample_count);
+                samples.extend(data.iter().map(|sample| *sample as f32 / 2_147_483_648.0));
+            }
+        }
+    }
+
+    // Get The Sample Format Of A Mix Format
+    unsafe fn sample_format(
+        mix_format: *const WAVEFORMATEX,
+    ) -> Result<(SampleFormat, WAVEFORMATEX), Error> {
+        let format = ptr::read_unaligned(mix_format);
+
+        let tag = format.wFormatTag;
+        let is_float = match tag {
+            WAVE_FORMAT_IEEE_FLOAT => true,
+            tag if u32::from(tag) == WAVE_FORMAT_PCM => false,
+            WAVE_FORMAT_EXTENSIBLE => {
+                let extensible = ptr::read_unaligned(mix_format.cast::<WAVEFORMATEXTENSIBLE>());
+                let sub_format = extensible.SubFormat;
+
+                if sub_format == KSDATAFORMAT_SUBTYPE_IEEE_FLOAT {
+                    true
+                } else if sub_format == KSDATAFORMAT_SUBTYPE_PCM {
+                    false
+                } else {
+                    return Err(Error::UnsupportedFormat);
+                }
+            }
+            _ => return Err(Error::UnsupportedFormat),
+        };
+
+        let sample_format = match (is_float, format.wBitsPerSample) {
+            (true, 32) => SampleFormat::Float32,
+            (false, 16) => SampleFormat::Int16,
+            (false, 32) => SampleFormat::Int32,
+            _ => return Err(Error::UnsupportedFormat),
+        };
+
+        if format.nChannels == 0 || format.nSamplesPerSec == 0 {
+            return Err(Error::UnsupportedFormat);
+        }
+
+        Ok((sample_format, format))
+    }
+}
+
+impl Drop for LoopbackCapture {
+    #[inline]
+    fn drop(&mut self) {
+        let _ = unsafe { self.audio_client.Stop() };
+    }
+}
+
+/// Initializes COM on the current thread and balances it with `CoUninitialize` when dropped.
+struct ComGuard {
+    initialized: bool,
+}
+
+impl ComGuard {
+    /// Initializes COM as multithreaded if it isn't initialized yet.
+    fn new() -> Self {
+        // Fails if COM is already initialized in another mode, which works as well but must not
+        // be uninitialized, S_OK and S_FALSE both take a reference that has to be released
+        let result = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+
+        Self {
+            initialized: result.is_ok(),
+        }
+    }
+}
+
+impl Drop for ComGuard {
+    #[inline]
+    fn drop(&mut self) {
+        if self.initialized {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
+
+/// Converts the samples of a capture device to the sample rate and channel count of the output.
+struct Resampler {
+    source_sample_rate: u32,
+    source_channel_count: usize,
+    sample_rate: u32,
+    channel_count: usize,
+    // Position of the next output frame relative to `previous_frame`, in source frames
+    position: f64,
+    previous_frame: Option<Vec<f32>>,
+}
+
+impl Resampler {
+    const fn new(
+        source_sample_rate: u32,
+        source_channel_count: usize,
+        sample_rate: u32,
+        channel_count: usize,
+    ) -> Self {
+        Self {
+            source_sample_rate,
+            source_channel_count,
+            sample_rate,
+            channel_count,
+            position: 0.0,
+            previous_frame: None,
+        }
+    }
+
+    // Map The Channels, Resample Linearly And Write 16-Bit PCM
+    fn resample(&mut self, samples: &[f32], out: &mut Vec<u8>) {
+        let mut frames = samples
+            .chunks_exact(self.source_channel_count)
+            .map(|frame| self.map_channels(frame))
+            .collect::<Vec<_>>();
+
+        // Interpolate across reads by starting from the last frame of the previous read
+        if let Some(previous_frame) = self.previous_frame.take() {
+            frames.insert(0, previous_frame);
+        }
+
+        let step = f64::from(self.source_sample_rate) / f64::from(self.sample_rate);
+        let last = (frames.len() - 1) as f64;
+
+        // Count The Output Frames That Fall Within This Read
+        let count = if self.position <= last {
+            ((last - self.position) / step) as usize + 1
+        } else {
+            0
+        };
+
+        for output_frame in 0..count {
+            let position = (output_frame as f64).mul_add(step, self.position);
+            let index = (position as usize).min(frames.len() - 1);
+            let fraction = (position - index as f64) as f32;
+            let next = &frames[(index + 1).min(frames.len() - 1)];
+
+            for (channel, sample) in frames[index].iter().enumerate() {
+                let sample = sample + (next[channel] - sample) * fraction;
+                let sample = (sample.clamp(-1.0, 1.0) * 32767.0) as i16;
+                out.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+
+        self.position = (count as f64).mul_add(step, self.position) - last;
+        self.previous_frame = frames.pop();
+    }
+
+    // Map A Frame Of The Device Channels To The Output Channels
+    fn map_channels(&self, frame: &[f32]) -> Vec<f32> {
+        if self.channel_count == 1 {
+            return vec![frame.iter().sum::<f32>() / frame.len() as f32];
+        }
+
+        (0..self.channel_count)
+            .map(|channel| frame[channel % frame.len()])
+            .collect()
+    }
+}
+
+/// A capture source of a `Mixer` with the samples that were not mixed yet.
+struct MixerSource {
+    capture: LoopbackCapture,
+    gain: f32,
+    is_muted: bool,
+    pending: VecDeque<i16>,
+}
+
+impl MixerSource {
+    const fn new(capture: LoopbackCapture) -> Self {
+        Self {
+            capture,
+            gain: 1.0,
+            is_muted: false,
+            pending: VecDeque::new(),
+        }
+    }
+
+    // Read The Source And Place The Samples At Their Position On The Mixer Clock
+    fn read(
+        &mut self,
+        buffer: &mut Vec<u8>,
+        start_timespan: &mut Option<i64>,
+        position: u64,
+        sample_rate: u32,
+        channel_count: usize,
+    ) -> Result<(), Error> {
+        let Some(timespan) = self.capture.read(buffer)? else {
+            return Ok(());
+        };
+
+        let start_timespan = *start_timespan.get_or_insert(timespan);
+        let index = (timespan - start_timespan).max(0) as u64 * u64::from(sample_rate) / 10_000_000;
+
+        // Fill the time the source delivered nothing with silence, small gaps are timestamp
+        // jitter and are ignored so the samples stay contiguous
+        let end = position + (self.pending.len() / channel_count) as u64;
+        let tolerance = u64::from(sample_rate) * MIXER_GAP_TOLERANCE_MS / 1000;
+        if index > end + tolerance {
+            let gap = (index - end) as usize * channel_count;
+            self.pending.resize(self.pending.len() + gap, 0);
+        }
+
+        self.pending.extend(
+            buffer
+                .chunks_exact(2)
+                .map(|sample| i16::from_le_bytes([sample[0], sample[1]])),
+        );
+
+        Ok(())
+    }
+
+    // Get The Number Of Pending Frames
+    fn frame_count(&self, channel_count: usize) -> usize {
+        self.pending.len() / channel_count
+    }
+}
+
+/// Mixes the system audio with a capture device, like a microphone, into one track.
+///
+/// Both sources are placed on a sample clock that starts at the first captured sample, using the
+/// QPC time of every packet, so the microphone and the desktop audio stay aligned even though the
+/// loopback stream delivers nothing while the system is silent. The output is interleaved 16-bit
+/// PCM for `VideoEncoder::send_audio_buffer`, summed with the gain of each source and saturated
+/// to the `i16` range.
+///
+/// # Example
+/// ```no_run
+/// use windows_capture::audio::Mixer;
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut mixer = Mixer::new("Microphone (USB Audio)", 48000, 2)?;
+///     mixer.set_microphone_gain(0.8);
+///     mixer.start()?;
+///
+///     let mut buffer = Vec::new();
+///     if let Some(timespan) = mixer.read(&mut buffer)? {
+///         println!("Mixed {} bytes at {timespan}", buffer.len());
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+pub struct Mixer {
+    loopback: MixerSource,
+    microphone: MixerSource,
+    sample_rate: u32,
+    channel_count: usize,
+    start_timespan: Option<i64>,
+    position: u64,
+    buffer: Vec<u8>,
+}
+
+impl Mixer {
+    /// Open the default render device in loopback mode and a capture device.
+    ///
+    /// # Arguments
+    ///
+    /// * `microphone` - The friendly name or endpoint ID of the capture device, see
+    ///   `LoopbackCapture::from_capture_device`.
+    /// * `sample_rate` - The sample rate of the mixed samples.
+    /// * `channel_count` - The number of channels of the mixed samples.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `LoopbackCapture::new` or `LoopbackCapture::from_capture_device` if
+    /// a source can't be opened.
+    #[inline]
+    pub fn new(microphone: &str, sample_rate: u32, channel_count: u32) -> Result<Self, Error> {
+        let loopback = LoopbackCapture::new(sample_rate, channel_count)?;
+        let microphone =
+            LoopbackCapture::from_capture_device(microphone, sample_rate, channel_count)?;
+
+        Ok(Self {
+            loopback: MixerSource::new(loopback),
+            microphone: MixerSource::new(microphone),
+            sample_rate,
+            channel_count: channel_count as usize,
+            start_timespan: None,
+            position: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Start capturing both sources.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::WindowsError` if a source fails to start.
+    #[inline]
+    pub fn start(&self) -> Result<(), Error> {
+        self.loopback.capture.start()?;
+        self.microphone.capture.start()?;
+
+        Ok(())
+    }
+
+    /// Stop capturing both sources.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::WindowsError` if a source fails to stop.
+    #[inline]
+    pub fn stop(&self) -> Result<(), Error> {
+        self.loopback.capture.stop()?;
+        self.microphone.capture.stop()?;
+
+        Ok(())
+    }
+
+    /// Set the gain of the system audio, by default `1.0`.
+    #[inline]
+    pub const fn set_loopback_gain(&mut self, gain: f32) {
+        self.loopback.gain = gain;
+    }
+
+    /// Set the gain of the capture device, by default `1.0`.
+    #[inline]
+    pub const fn set_microphone_gain(&mut self, gain: f32) {
+        self.microphone.gain = gain;
+    }
+
+    /// Mute or unmute the system audio, a muted source is still captured to keep it aligned.
+    #[inline]
+    pub const fn set_loopback_muted(&mut self, is_muted: bool) {
+        self.loopback.is_muted = is_muted;
+    }
+
+    /// Mute or unmute the capture device, a muted source is still captured to keep it aligned.
+    #[inline]
+    pub const fn set_microphone_muted(&mut self, is_muted: bool) {
+        self.microphone.is_muted = is_muted;
+    }
+
+    /// Get whether the system audio is muted.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the system audio is muted.
+    #[must_use]
+    #[inline]
+    pub const fn is_loopback_muted(&self) -> bool {
+        self.loopback.is_muted
+    }
+
+    /// Get whether the capture device is muted.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the capture device is muted.
+    #[must_use]
+    #[inline]
+    pub const fn is_microphone_muted(&self) -> bool {
+        self.microphone.is_muted
+    }
+
+    /// Read both sources and mix the samples that are available.
+    ///
+    /// Samples are held back until both sources delivered them, a source that stays behind the
+    /// other for longer than the mixer latency is treated as silent.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - The buffer the interleaved 16-bit PCM samples are written to, it is cleared first.
+    ///
+    /// # Returns
+    ///
+    /// Returns the QPC time of the first mixed sample in 100 nanosecond units, the same timebase
+    /// as `Frame::system_relative_time`, or `None` if nothing could be mixed yet.
+    #[inline]
+    pub fn read(&mut self, out: &mut Vec<u8>) -> Result<Option<i64>, Error> {
+        out.clear();
+
+        for source in [&mut self.loopback, &mut self.microphone] {
+            source.read(
+                &mut self.buffer,
+                &mut self.start_timespan,
+                self.position,
+                self.sample_rate,
+                self.channel_count,
+            )?;
+        }
+
+        let loopback_frames = self.loopback.frame_count(self.channel_count);
+        let microphone_frames = self.microphone.frame_count(self.channel_count);
+        let latency = self.sample_rate as usize * MIXER_LATENCY_MS as usize / 1000;
+        let frame_count = loopback_frames.min(microphone_frames).max(
+            loopback_frames
+                .max(microphone_frames)
+                .saturating_sub(latency),
+        );
+
+        let Some(start_timespan) = self.start_timespan else {
+            return Ok(None);
+        };
+        if frame_count == 0 {
+            return Ok(None);
+        }
+
+        let sample_count = frame_count * self.channel_count;
+        for source in [&mut self.loopback, &mut self.microphone] {
+            if source.pending.len() < sample_count {
+                source.pending.resize(sample_count, 0);
+            }
+        }
+
+        out.reserve(sample_count * 2);
+        for _ in 0..sample_count {
+            let mut sample = 0.0;
+            for source in [&mut self.loopback, &mut self.microphone] {
+                let source_sample = source.pending.pop_front().unwrap_or(0);
+                if !source.is_muted {
+                    sample = f32::from(source_sample).mul_add(source.gain, sample);
+                }
+            }
+
+            let sample = sample.clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16;
+            out.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        let timespan =
+            start_timespan + (self.position * 10_000_000 / u64::from(self.sample_rate)) as i64;
+        self.position += frame_count as u64;
+
+        Ok(Some(timespan))
+    }
+}
+
+// Audio Buffers And Their Timestamps Waiting To Be Sent
+type AudioQueue = Arc<Mutex<VecDeque<(Vec<u8>, i64)>>>;
+
+/// Captures system audio on a background thread and queues it for a `VideoEncoder`.
+///
+/// `VideoEncoder` has to be driven from a single thread because every send waits for the
+/// encoder to request the matching sample, so the pump only captures and converts the audio.
+/// Call `send_pending` next to `VideoEncoder::send_frame` in the frame handler to write the
+/// queued audio.
+///
+/// # Example
+/// ```ignore
+/// // In the frame handler
+/// self.encoder.send_frame(frame)?;
+/// self.audio_pump.send_pending(&mut self.encoder)?;
+/// ```
+pub struct AudioPump {
+    queue: AudioQueue,
+    halt: Arc<AtomicBool>,
+    thread_handle: Option<JoinHandle<Result<(), Error>>>,
+}
+
+impl AudioPump {
+    /// Start capturing system audio on a background thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The sample rate configured in `AudioSettingsBuilder`.
+    /// * `channel_count` - The channel count configured in `AudioSettingsBuilder`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `LoopbackCapture::new` or `LoopbackCapture::start` if the loopback
+    /// capture can't be started.
+    #[inline]
+    pub fn start(sample_rate: u32, channel_count: u32) -> Result<Self, Error> {
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let halt = Arc::new(AtomicBool::new(false));
+
+        let (started_sender, started_receiver) = mpsc::channel();
+        let thread_handle = thread::spawn({
+            let queue = queue.clone();
+            let halt = halt.clone();
+
+            move || -> Result<(), Error> {
+                let capture = LoopbackCapture::new(sample_rate, channel_count)
+                    .and_then(|capture| capture.start().map(|()| capture));
+                let mut capture = match capture {
+                    Ok(capture) => {
+                        let _ = started_sender.send(Ok(()));
+                        capture
+                    }
+                    Err(e) => {
+                        let _ = started_sender.send(Err(e));
+                        return Ok(());
+                    }
+                };
+
+                // About one pump interval of 16-bit samples
+                let buffer_size = sample_rate as usize
+                    * channel_count as usize
+                    * 2
+                    * PUMP_INTERVAL.as_millis() as usize
+                    / 1000;
+
+                let result = (|| {
+                    while !halt.load(atomic::Ordering::Relaxed) {
+                        let mut buffer = vec![0; buffer_size];
+                        if let Some(timespan) = capture.read(&mut buffer)? {
+                            queue.lock().push_back((buffer, timespan));
+                        }
+
+                        thread::sleep(PUMP_INTERVAL);
+                    }
+
+                    capture.stop()
+                })();
+
+                result
+            }
+        });
+
+        match started_receiver.recv() {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => {
+                let _ = thread_handle.join();
+                return Err(e);
+            }
+            Err(_) => return Err(Error::ThreadPanicked),
+        }
+
+        Ok(Self {
+            queue,
+            halt,
+            thread_handle: Some(thread_handle),
+        })
+    }
+
+    /// Write the audio captured so far to the encoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder` - The encoder the audio is sent to with `VideoEncoder::send_audio_buffer`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::VideoEncoderError` if the encoder fails to encode the audio.
+    #[inline]
+    pub fn send_pending(&self, encoder: &mut VideoEncoder) -> Result<(), Error> {
+        let pending = self.queue.lock().drain(..).collect::<Vec<_>>();
+
+        for (buffer, timespan) in pending {
+            encoder.send_audio_buffer(&buffer, timespan)?;
+        }
+
+        Ok(())
+    }
+
+    /// Stop capturing and wait for the background thread to exit, queued audio is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error the background thread stopped with.
+    #[inline]
+    pub fn stop(mut self) -> Result<(), Error> {
+        self.halt.store(true, atomic::Ordering::Relaxed);
+
+        match self.thread_handle.take() {
+            Some(thread_handle) => thread_handle.join().map_err(|_| Error::ThreadPanicked)?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for AudioPump {
+    #[inline]
+    fn drop(&mut self) {
+        self.halt.store(true, atomic::Ordering::Relaxed);
+
+        if let Some(thread_handle) = self.thread_handle.take() {
+            let _ = thread_handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use windows::Win32::{Foundation::S_OK, System::Com::COINIT_APARTMENTTHREADED};
+
+    use super::*;
+
+    #[test]
+    fn com_guard_uninitializes_on_drop() {
+        thread::spawn(|| {
+            let com = ComGuard::new();
+            let nested = ComGuard::new();
+            assert!(com.initialized && nested.initialized);
+            drop(nested);
+            drop(com);
+
+            // The multithreaded apartment is released, so the thread can enter another one
+            let result = unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) };
+            assert_eq!(result, S_OK);
+            unsafe { CoUninitialize() };
+        })
+        .join()
+        .unwrap();
+    }
+
+    fn pcm(out: &[u8]) -> Vec<i16> {
+        out.chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn map_channels_downmix_and_upmix() {
+        let to_mono = Resampler::new(48000, 2, 48000, 1);
+        assert_eq!(to_mono.map_channels(&[0.25, 0.75]), vec![0.5]);
+
+        let to_stereo = Resampler::new(48000, 1, 48000, 2);
+        assert_eq!(to_stereo.map_channels(&[0.5]), vec![0.5, 0.5]);
+
+        // Extra device channels are dropped
+        let surround_to_stereo = Resampler::new(48000, 6, 48000, 2);
+        assert_eq!(
+            surround_to_stereo.map_channels(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]),
+            vec![0.1, 0.2]
+        );
+    }
+
+    #[test]
+    fn resample_same_rate_keeps_every_frame() {
+        let mut resampler = Resampler::new(48000, 2, 48000, 2);
+        let mut out = Vec::new();
+        resampler.resample(&[0.0, 1.0, -1.0, 0.5], &mut out);
+        resampler.resample(&[2.0, -2.0], &mut out);
+
+        assert_eq!(pcm(&out), vec![0, 32767, -32767, 16383, 32767, -32767]);
+    }
+
+    #[test]
+    fn resample_downsample_across_reads() {
+        let mut resampler = Resampler::new(48000, 1, 24000, 1);
+        let samples = (0..100).map(|i| i as f32 / 100.0).collect::<Vec<_>>();
+
+        let mut out = Vec::new();
+        resampler.resample(&samples[..51], &mut out);
+        resampler.resample(&samples[51..], &mut out);
+
+        // Every second source frame, without a gap or a repeat at the read boundary
+        let expected = (0..50)
+            .map(|i| (samples[i * 2] * 32767.0) as i16)
+            .collect::<Vec<_>>();
+        assert_eq!(pcm(&out), expected);
+    }
+
+    #[test]
+    fn resample_upsample_interpolates_across_reads() {
+        let mut resampler = Resampler::new(24000, 1, 48000, 1);
+        let mut out = Vec::new();
+        resampler.resample(&[0.0, 0.5], &mut out);
+        resampler.resample(&[1.0], &mut out);
+
+        let expected = [0.0, 0.25, 0.5, 0.75, 1.0].map(|sample: f32| (sample * 32767.0) as i16);
+        assert_eq!(pcm(&out), expected);
+    }
+}
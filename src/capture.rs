@@ -1,17 +1,26 @@
 use std::{
-    mem,
+    collections::VecDeque,
+    future, mem,
     os::windows::prelude::AsRawHandle,
+    pin::Pin,
     sync::{
         atomic::{self, AtomicBool},
         mpsc, Arc,
     },
+    task::{self, Poll, Waker},
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
+use futures_core::Stream;
 use parking_lot::Mutex;
 use windows::{
-    Foundation::AsyncActionCompletedHandler,
-    Graphics::Capture::GraphicsCaptureItem,
+    Foundation::{AsyncActionCompletedHandler, TimeSpan},
+    Graphics::{
+        Capture::{Direct3D11CaptureFramePool, GraphicsCaptureItem},
+        DirectX::DirectXPixelFormat,
+    },
+    System::{DispatcherQueue, DispatcherQueueController},
     Win32::{
         Foundation::{HANDLE, LPARAM, WPARAM},
         Graphics::Direct3D11::{ID3D11Device, ID3D11DeviceContext},
@@ -30,10 +39,16 @@ use windows::{
 };
 
 use crate::{
-    d3d11::{self, create_d3d_device},
-    frame::Frame,
-    graphics_capture_api::{self, GraphicsCaptureApi, InternalCaptureControl},
-    settings::Settings,
+    d3d11::{self, create_d3d_device, create_direct3d_device},
+    frame::{self, Frame, OwnedFrame, OwnedGpuFrame},
+    graphics_capture_api::{
+        self, CaptureCounters, CaptureStats, GraphicsCaptureApi, InternalCaptureControl,
+    },
+    settings::{
+        ColorFormat, CursorCaptureSettings, DrawBorderSettings, Settings,
+        TryIntoCaptureItemWithType,
+    },
+    window::{self, Window},
 };
 
 #[derive(thiserror::Error, Debug)]
@@ -54,6 +69,7 @@ pub enum CaptureControlError<E> {
 pub struct CaptureControl<T: GraphicsCaptureApiHandler + Send + 'static, E> {
     thread_handle: Option<JoinHandle<Result<(), GraphicsCaptureApiError<E>>>>,
     halt_handle: Arc<AtomicBool>,
+    counters: Option<Arc<CaptureCounters>>,
     callback: Arc<Mutex<T>>,
 }
 
@@ -68,7 +84,8 @@ impl<T: GraphicsCaptureApiHandler + Send + 'static, E> CaptureControl<T, E> {
     ///
     /// # Returns
     ///
-    /// The newly created CaptureControl struct.
+    /// The newly created CaptureControl struct, its `stats` stay empty because it has no access
+    /// to the frame counters of the capture thread.
     #[must_use]
     #[inline]
     pub const fn new(
@@ -79,6 +96,35 @@ impl<T: GraphicsCaptureApiHandler + Send + 'static, E> CaptureControl<T, E> {
         Self {
             thread_handle: Some(thread_handle),
             halt_handle,
+            counters: None,
+            callback,
+        }
+    }
+
+    /// Creates a new Capture Control struct that reports the frame counters of the capture thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `thread_handle` - The join handle for the capture thread.
+    /// * `halt_handle` - The atomic boolean used to pause the capture thread.
+    /// * `counters` - The frame counters of the capture thread.
+    /// * `callback` - The mutex-protected callback struct used to call struct methods directly.
+    ///
+    /// # Returns
+    ///
+    /// The newly created CaptureControl struct.
+    #[must_use]
+    #[inline]
+    pub(crate) const fn with_counters(
+        thread_handle: JoinHandle<Result<(), GraphicsCaptureApiError<E>>>,
+        halt_handle: Arc<AtomicBool>,
+        counters: Arc<CaptureCounters>,
+        callback: Arc<Mutex<T>>,
+    ) -> Self {
+        Self {
+            thread_handle: Some(thread_handle),
+            halt_handle,
+            counters: Some(counters),
             callback,
         }
     }
@@ -118,6 +164,36 @@ impl<T: GraphicsCaptureApiHandler + Send + 'static, E> CaptureControl<T, E> {
         self.halt_handle.clone()
     }
 
+    /// Gets the number of delivered and dropped frames and the timestamp of the last delivered
+    /// frame.
+    ///
+    /// # Returns
+    ///
+    /// The frame statistics of the capture thread, or empty statistics if the capture control
+    /// was created with `new`.
+    #[must_use]
+    #[inline]
+    pub fn stats(&self) -> CaptureStats {
+        self.counters
+            .as_ref()
+            .map_or_else(CaptureStats::default, |counters| counters.stats())
+    }
+
+    /// Gets the epoch `Frame::timespan` is measured from, in 100 nanosecond units of the QPC
+    /// timebase.
+    ///
+    /// Pass it to `VideoEncoder::set_epoch_qpc` to place samples of several encoders, or audio
+    /// sent before the first frame, on the same timeline as the frames.
+    ///
+    /// # Returns
+    ///
+    /// The epoch, or `None` if it is not known yet or the capture control was created with `new`.
+    #[must_use]
+    #[inline]
+    pub fn epoch_qpc(&self) -> Option<TimeSpan> {
+        self.counters.as_ref().and_then(|counters| counters.epoch())
+    }
+
     /// Gets the callback struct used to call struct methods directly.
     ///
     /// # Returns
@@ -195,6 +271,150 @@ impl<T: GraphicsCaptureApiHandler + Send + 'static, E> CaptureControl<T, E> {
     }
 }
 
+impl<T: GraphicsCaptureApiHandler<Error = E> + Send + 'static, E> CaptureControl<T, E> {
+    /// Gracefully stops the capture thread and lets the handler flush its consumers.
+    ///
+    /// The capture thread is joined first, so no frame handler call is still running, then
+    /// `GraphicsCaptureApiHandler::on_flush` is called once. A handler that forwards frames to a
+    /// channel can send a final marker from it, and a consumer that receives the marker has
+    /// received every frame delivered before the stop. The handler is flushed even if stopping
+    /// the capture thread failed.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the capture thread stops and the handler is flushed successfully, an error
+    /// otherwise.
+    #[inline]
+    pub fn stop_and_flush(self) -> Result<(), CaptureControlError<E>> {
+        let callback = self.callback.clone();
+
+        let result = self.stop();
+        let flush_result = callback.lock().on_flush();
+
+        result?;
+        flush_result.map_err(CaptureControlError::StoppedHandlerError)
+    }
+}
+
+/// Used to control a capture session running on a caller-provided dispatcher queue
+pub struct DispatcherCaptureControl<T: GraphicsCaptureApiHandler + Send + 'static, E> {
+    capture: GraphicsCaptureApi,
+    callback: Arc<Mutex<T>>,
+    result: Arc<Mutex<Option<E>>>,
+}
+
+impl<T: GraphicsCaptureApiHandler + Send + 'static, E> DispatcherCaptureControl<T, E> {
+    /// Creates a new Dispatcher Capture Control struct.
+    ///
+    /// # Arguments
+    ///
+    /// * `capture` - The started capture.
+    /// * `callback` - The mutex-protected callback struct used to call struct methods directly.
+    /// * `result` - The error returned by the frame handler, if any.
+    ///
+    /// # Returns
+    ///
+    /// The newly created DispatcherCaptureControl struct.
+    #[must_use]
+    #[inline]
+    pub const fn new(
+        capture: GraphicsCaptureApi,
+        callback: Arc<Mutex<T>>,
+        result: Arc<Mutex<Option<E>>>,
+    ) -> Self {
+        Self {
+            capture,
+            callback,
+            result,
+        }
+    }
+
+    /// Checks to see if the capture has stopped delivering frames, because the handler stopped
+    /// it, returned an error or the capture item was closed.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the capture is finished, `false` otherwise.
+    #[must_use]
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.capture.halt_handle().load(atomic::Ordering::Relaxed)
+    }
+
+    /// Gets the halt handle used to pause the capture.
+    ///
+    /// # Returns
+    ///
+    /// The halt handle used to pause the capture.
+    #[must_use]
+    #[inline]
+    pub fn halt_handle(&self) -> Arc<AtomicBool> {
+        self.capture.halt_handle()
+    }
+
+    /// Gets the number of delivered and dropped frames and the timestamp of the last delivered
+    /// frame.
+    ///
+    /// # Returns
+    ///
+    /// The frame statistics of the capture.
+    #[must_use]
+    #[inline]
+    pub fn stats(&self) -> CaptureStats {
+        self.capture.stats()
+    }
+
+    /// Gets the epoch `Frame::timespan` is measured from, see `CaptureControl::epoch_qpc`.
+    ///
+    /// # Returns
+    ///
+    /// The epoch, or `None` if it is not known yet.
+    #[must_use]
+    #[inline]
+    pub fn epoch_qpc(&self) -> Option<TimeSpan> {
+        self.capture.counters_handle().epoch()
+    }
+
+    /// Gets the callback struct used to call struct methods directly.
+    ///
+    /// # Returns
+    ///
+    /// The callback struct used to call struct methods directly.
+    #[must_use]
+    #[inline]
+    pub fn callback(&self) -> Arc<Mutex<T>> {
+        self.callback.clone()
+    }
+
+    /// Stops the capture, this must be called on the dispatcher queue thread.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the capture stops successfully, the handler error otherwise.
+    #[inline]
+    pub fn stop(self) -> Result<(), GraphicsCaptureApiError<E>> {
+        let handler_timed_out = self.capture.is_handler_timed_out();
+        let capture_error = self.capture.take_capture_error();
+        self.capture.stop_capture();
+
+        // Check handler result
+        if handler_timed_out {
+            return Err(GraphicsCaptureApiError::HandlerTimedOut);
+        }
+
+        if let Some(e) = capture_error {
+            return Err(GraphicsCaptureApiError::GraphicsCaptureApiError(e));
+        }
+
+        let result = self.result.lock().take();
+        if let Some(e) = result {
+            return Err(GraphicsCaptureApiError::FrameHandlerError(e));
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(thiserror::Error, Eq, PartialEq, Clone, Debug)]
 pub enum GraphicsCaptureApiError<E> {
     #[error("Failed to join thread")]
@@ -207,6 +427,10 @@ pub enum GraphicsCaptureApiError<E> {
     FailedToShutdownDispatcherQueue,
     #[error("Failed to set dispatcher queue completed handler")]
     FailedToSetDispatcherQueueCompletedHandler,
+    #[error("Failed to get dispatcher queue")]
+    FailedToGetDispatcherQueue,
+    #[error("Dispatcher queue does not belong to the current thread")]
+    DispatcherQueueNotOnCurrentThread,
     #[error("Failed to convert item to GraphicsCaptureItem")]
     ItemConvertFailed,
     #[error("DirectX error: {0}")]
@@ -217,6 +441,24 @@ pub enum GraphicsCaptureApiError<E> {
     NewHandlerError(E),
     #[error("Frame handler error: {0}")]
     FrameHandlerError(E),
+    #[error("Frame handler exceeded the handler timeout")]
+    HandlerTimedOut,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ScreenshotError {
+    #[error("Window error: {0}")]
+    WindowError(#[from] window::Error),
+    #[error("Frame error: {0}")]
+    FrameError(#[from] frame::Error),
+    #[error("Windows capture error: {0}")]
+    GraphicsCaptureApiError(#[from] GraphicsCaptureApiError<frame::Error>),
+    #[error("Capture control error: {0}")]
+    CaptureControlError(#[from] CaptureControlError<frame::Error>),
+    #[error("Capture stopped before a frame arrived")]
+    NoFrame,
+    #[error("No frame arrived before the timeout")]
+    Timeout,
 }
 
 /// A struct representing the context of the capture handler.
@@ -247,7 +489,7 @@ pub trait GraphicsCaptureApiHandler: Sized {
     ///
     /// Returns `Ok(())` if the capture was successful, otherwise returns an error of type `GraphicsCaptureApiError`.
     #[inline]
-    fn start<T: TryInto<GraphicsCaptureItem>>(
+    fn start<T: TryInto<GraphicsCaptureItem> + 'static>(
         settings: Settings<Self::Flags, T>,
     ) -> Result<(), GraphicsCaptureApiError<Self::Error>>
     where
@@ -290,20 +532,30 @@ pub trait GraphicsCaptureApiHandler: Sized {
             Self::new(ctx).map_err(GraphicsCaptureApiError::NewHandlerError)?,
         ));
 
-        let item = settings
+        let (item, item_type) = settings
             .item
-            .try_into()
+            .try_into_capture_item_with_type()
             .map_err(|_| GraphicsCaptureApiError::ItemConvertFailed)?;
 
         let mut capture = GraphicsCaptureApi::new(
             d3d_device,
             d3d_device_context,
             item,
+            item_type,
             callback,
             settings.cursor_capture,
             settings.draw_border,
+            settings.dirty_region,
+            settings.deliver_on_change_only,
+            settings.warmup_frames,
+            settings.timestamp_epoch,
+            settings.handler_timeout,
+            settings.capture_area,
+            settings.capture_region,
+            settings.target_frame_rate,
+            settings.force_opaque,
             settings.color_format,
-            thread_id,
+            Some(thread_id),
             result.clone(),
         )
         .map_err(GraphicsCaptureApiError::GraphicsCaptureApiError)?;
@@ -343,12 +595,22 @@ pub trait GraphicsCaptureApiHandler: Sized {
         }
 
         // Stop capture
+        let handler_timed_out = capture.is_handler_timed_out();
+        let capture_error = capture.take_capture_error();
         capture.stop_capture();
 
         // Uninitialize WinRT
         unsafe { RoUninitialize() };
 
         // Check handler result
+        if handler_timed_out {
+            return Err(GraphicsCaptureApiError::HandlerTimedOut);
+        }
+
+        if let Some(e) = capture_error {
+            return Err(GraphicsCaptureApiError::GraphicsCaptureApiError(e));
+        }
+
         let result = result.lock().take();
         if let Some(e) = result {
             return Err(GraphicsCaptureApiError::FrameHandlerError(e));
@@ -374,145 +636,90 @@ pub trait GraphicsCaptureApiHandler: Sized {
         Self: Send + 'static,
         <Self as GraphicsCaptureApiHandler>::Flags: Send,
     {
-        let (halt_sender, halt_receiver) = mpsc::channel::<Arc<AtomicBool>>();
-        let (callback_sender, callback_receiver) = mpsc::channel::<Arc<Mutex<Self>>>();
-
-        let thread_handle = thread::spawn(
-            move || -> Result<(), GraphicsCaptureApiError<Self::Error>> {
-                // Initialize WinRT
-                unsafe {
-                    RoInitialize(RO_INIT_MULTITHREADED)
-                        .map_err(|_| GraphicsCaptureApiError::FailedToInitWinRT)?;
-                };
-
-                // Create a dispatcher queue for the current thread
-                let options = DispatcherQueueOptions {
-                    dwSize: u32::try_from(mem::size_of::<DispatcherQueueOptions>()).unwrap(),
-                    threadType: DQTYPE_THREAD_CURRENT,
-                    apartmentType: DQTAT_COM_NONE,
-                };
-                let controller = unsafe {
-                    CreateDispatcherQueueController(options).map_err(|_| {
-                        GraphicsCaptureApiError::FailedToCreateDispatcherQueueController
-                    })?
-                };
-
-                // Get current thread ID
-                let thread_id = unsafe { GetCurrentThreadId() };
-
-                // Create direct3d device and context
-                let (d3d_device, d3d_device_context) = create_d3d_device()?;
-
-                // Start capture
-                let result = Arc::new(Mutex::new(None));
-
-                let ctx = Context {
-                    flags: settings.flags,
-                    device: d3d_device.clone(),
-                    device_context: d3d_device_context.clone(),
-                };
-
-                let callback = Arc::new(Mutex::new(
-                    Self::new(ctx).map_err(GraphicsCaptureApiError::NewHandlerError)?,
-                ));
-
-                let item = settings
-                    .item
-                    .try_into()
-                    .map_err(|_| GraphicsCaptureApiError::ItemConvertFailed)?;
-
-                let mut capture = GraphicsCaptureApi::new(
-                    d3d_device,
-                    d3d_device_context,
-                    item,
-                    callback.clone(),
-                    settings.cursor_capture,
-                    settings.draw_border,
-                    settings.color_format,
-                    thread_id,
-                    result.clone(),
-                )
-                .map_err(GraphicsCaptureApiError::GraphicsCaptureApiError)?;
-                capture
-                    .start_capture()
-                    .map_err(GraphicsCaptureApiError::GraphicsCaptureApiError)?;
-
-                // Send halt handle
-                let halt_handle = capture.halt_handle();
-                halt_sender.send(halt_handle).unwrap();
-
-                // Send callback
-                callback_sender.send(callback).unwrap();
-
-                // Message loop
-                let mut message = MSG::default();
-                unsafe {
-                    while GetMessageW(&mut message, None, 0, 0).as_bool() {
-                        let _ = TranslateMessage(&message);
-                        DispatchMessageW(&message);
-                    }
-                }
+        spawn_capture_thread(settings, || ())
+    }
 
-                // Shutdown dispatcher queue
-                let async_action = controller
-                    .ShutdownQueueAsync()
-                    .map_err(|_| GraphicsCaptureApiError::FailedToShutdownDispatcherQueue)?;
-
-                async_action
-                    .SetCompleted(&AsyncActionCompletedHandler::new(
-                        move |_, _| -> Result<(), windows::core::Error> {
-                            unsafe { PostQuitMessage(0) };
-                            Ok(())
-                        },
-                    ))
-                    .map_err(|_| {
-                        GraphicsCaptureApiError::FailedToSetDispatcherQueueCompletedHandler
-                    })?;
-
-                // Final message loop
-                let mut message = MSG::default();
-                unsafe {
-                    while GetMessageW(&mut message, None, 0, 0).as_bool() {
-                        let _ = TranslateMessage(&message);
-                        DispatchMessageW(&message);
-                    }
-                }
+    /// Starts the capture on a dispatcher queue that is already running on the current thread.
+    ///
+    /// This is meant for threads that already pump messages for a UI framework, the capture
+    /// doesn't initialize WinRT, create a dispatcher queue or run a message loop. Frames are
+    /// delivered by the existing message loop of the thread, and stopping the capture from the
+    /// handler or closing the capture item only stops delivering frames, it never posts
+    /// `WM_QUIT` to the thread. The returned control must be used and dropped on the same thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - The capture settings.
+    /// * `controller` - The dispatcher queue controller of the current thread.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(DispatcherCaptureControl)` if the capture was started successfully, otherwise returns an error of type `GraphicsCaptureApiError`.
+    #[inline]
+    fn start_on_dispatcher<T: TryInto<GraphicsCaptureItem> + 'static>(
+        settings: Settings<Self::Flags, T>,
+        controller: &DispatcherQueueController,
+    ) -> Result<DispatcherCaptureControl<Self, Self::Error>, GraphicsCaptureApiError<Self::Error>>
+    where
+        Self: Send + 'static,
+        <Self as GraphicsCaptureApiHandler>::Flags: Send,
+    {
+        // Make sure the dispatcher queue runs on the current thread
+        let dispatcher_queue = controller
+            .DispatcherQueue()
+            .map_err(|_| GraphicsCaptureApiError::FailedToGetDispatcherQueue)?;
+        if DispatcherQueue::GetForCurrentThread().ok() != Some(dispatcher_queue) {
+            return Err(GraphicsCaptureApiError::DispatcherQueueNotOnCurrentThread);
+        }
 
-                // Stop capture
-                capture.stop_capture();
+        // Create direct3d device and context
+        let (d3d_device, d3d_device_context) = create_d3d_device()?;
 
-                // Uninitialize WinRT
-                unsafe { RoUninitialize() };
+        // Start capture
+        let result = Arc::new(Mutex::new(None));
 
-                // Check handler result
-                let result = result.lock().take();
-                if let Some(e) = result {
-                    return Err(GraphicsCaptureApiError::FrameHandlerError(e));
-                }
+        let ctx = Context {
+            flags: settings.flags,
+            device: d3d_device.clone(),
+            device_context: d3d_device_context.clone(),
+        };
 
-                Ok(())
-            },
-        );
+        let callback = Arc::new(Mutex::new(
+            Self::new(ctx).map_err(GraphicsCaptureApiError::NewHandlerError)?,
+        ));
 
-        let Ok(halt_handle) = halt_receiver.recv() else {
-            match thread_handle.join() {
-                Ok(result) => return Err(result.err().unwrap()),
-                Err(_) => {
-                    return Err(GraphicsCaptureApiError::FailedToJoinThread);
-                }
-            }
-        };
+        let (item, item_type) = settings
+            .item
+            .try_into_capture_item_with_type()
+            .map_err(|_| GraphicsCaptureApiError::ItemConvertFailed)?;
 
-        let Ok(callback) = callback_receiver.recv() else {
-            match thread_handle.join() {
-                Ok(result) => return Err(result.err().unwrap()),
-                Err(_) => {
-                    return Err(GraphicsCaptureApiError::FailedToJoinThread);
-                }
-            }
-        };
+        let mut capture = GraphicsCaptureApi::new(
+            d3d_device,
+            d3d_device_context,
+            item,
+            item_type,
+            callback.clone(),
+            settings.cursor_capture,
+            settings.draw_border,
+            settings.dirty_region,
+            settings.deliver_on_change_only,
+            settings.warmup_frames,
+            settings.timestamp_epoch,
+            settings.handler_timeout,
+            settings.capture_area,
+            settings.capture_region,
+            settings.target_frame_rate,
+            settings.force_opaque,
+            settings.color_format,
+            None,
+            result.clone(),
+        )
+        .map_err(GraphicsCaptureApiError::GraphicsCaptureApiError)?;
+        capture
+            .start_capture()
+            .map_err(GraphicsCaptureApiError::GraphicsCaptureApiError)?;
 
-        Ok(CaptureControl::new(thread_handle, halt_handle, callback))
+        Ok(DispatcherCaptureControl::new(capture, callback, result))
     }
 
     /// Function that will be called to create the struct. The flags can be passed from settings.
@@ -551,4 +758,962 @@ pub trait GraphicsCaptureApiHandler: Sized {
     fn on_closed(&mut self) -> Result<(), Self::Error> {
         Ok(())
     }
+
+    /// Optional handler called by `CaptureControl::stop_and_flush` after the capture thread
+    /// stopped, no frame arrives after it.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the handler execution was successful, otherwise returns an error of type `Self::Error`.
+    #[inline]
+    fn on_flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Optional handler called when the size of the capture item changes, before the first
+    /// frame with the new size is delivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The new width of the capture item, before any capture area or region is applied.
+    /// * `height` - The new height of the capture item, before any capture area or region is applied.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the handler execution was successful, otherwise returns an error of type `Self::Error`.
+    #[inline]
+    fn on_size_changed(&mut self, width: u32, height: u32) -> Result<(), Self::Error> {
+        let _ = (width, height);
+        Ok(())
+    }
+}
+
+/// Spawns the capture thread of `GraphicsCaptureApiHandler::start_free_threaded`.
+///
+/// # Arguments
+///
+/// * `settings` - The capture settings.
+/// * `on_exit` - Called when the capture thread exits, including when it fails or panics.
+///
+/// # Returns
+///
+/// Returns `Ok(CaptureControl)` if the capture was successful, otherwise returns an error of type `GraphicsCaptureApiError`.
+fn spawn_capture_thread<
+    H: GraphicsCaptureApiHandler + Send + 'static,
+    T: TryInto<GraphicsCaptureItem> + Send + 'static,
+>(
+    settings: Settings<H::Flags, T>,
+    on_exit: impl FnOnce() + Send + 'static,
+) -> Result<CaptureControl<H, H::Error>, GraphicsCaptureApiError<H::Error>>
+where
+    H::Flags: Send,
+{
+    let (halt_sender, halt_receiver) = mpsc::channel::<Arc<AtomicBool>>();
+    let (counters_sender, counters_receiver) = mpsc::channel::<Arc<CaptureCounters>>();
+    let (callback_sender, callback_receiver) = mpsc::channel::<Arc<Mutex<H>>>();
+
+    let thread_handle = thread::spawn(move || -> Result<(), GraphicsCaptureApiError<H::Error>> {
+        let _exit_guard = ExitGuard(Some(on_exit));
+
+        // Initialize WinRT
+        unsafe {
+            RoInitialize(RO_INIT_MULTITHREADED)
+                .map_err(|_| GraphicsCaptureApiError::FailedToInitWinRT)?;
+        };
+
+        // Create a dispatcher queue for the current thread
+        let options = DispatcherQueueOptions {
+            dwSize: u32::try_from(mem::size_of::<DispatcherQueueOptions>()).unwrap(),
+            threadType: DQTYPE_THREAD_CURRENT,
+            apartmentType: DQTAT_COM_NONE,
+        };
+        let controller = unsafe {
+            CreateDispatcherQueueController(options)
+                .map_err(|_| GraphicsCaptureApiError::FailedToCreateDispatcherQueueController)?
+        };
+
+        // Get current thread ID
+        let thread_id = unsafe { GetCurrentThreadId() };
+
+        // Create direct3d device and context
+        let (d3d_device, d3d_device_context) = create_d3d_device()?;
+
+        // Start capture
+        let result = Arc::new(Mutex::new(None));
+
+        let ctx = Context {
+            flags: settings.flags,
+            device: d3d_device.clone(),
+            device_context: d3d_device_context.clone(),
+        };
+
+        let callback = Arc::new(Mutex::new(
+            H::new(ctx).map_err(GraphicsCaptureApiError::NewHandlerError)?,
+        ));
+
+        let (item, item_type) = settings
+            .item
+            .try_into_capture_item_with_type()
+            .map_err(|_| GraphicsCaptureApiError::ItemConvertFailed)?;
+
+        let mut capture = GraphicsCaptureApi::new(
+            d3d_device,
+            d3d_device_context,
+            item,
+            item_type,
+            callback.clone(),
+            settings.cursor_capture,
+            settings.draw_border,
+            settings.dirty_region,
+            settings.deliver_on_change_only,
+            settings.warmup_frames,
+            settings.timestamp_epoch,
+            settings.handler_timeout,
+            settings.capture_area,
+            settings.capture_region,
+            settings.target_frame_rate,
+            settings.force_opaque,
+            settings.color_format,
+            Some(thread_id),
+            result.clone(),
+        )
+        .map_err(GraphicsCaptureApiError::GraphicsCaptureApiError)?;
+        capture
+            .start_capture()
+            .map_err(GraphicsCaptureApiError::GraphicsCaptureApiError)?;
+
+        // Send halt handle
+        let halt_handle = capture.halt_handle();
+        halt_sender.send(halt_handle).unwrap();
+
+        // Send frame counters
+        counters_sender.send(capture.counters_handle()).unwrap();
+
+        // Send callback
+        callback_sender.send(callback).unwrap();
+
+        // Message loop
+        let mut message = MSG::default();
+        unsafe {
+            while GetMessageW(&mut message, None, 0, 0).as_bool() {
+                let _ = TranslateMessage(&message);
+                DispatchMessageW(&message);
+            }
+        }
+
+        // Shutdown dispatcher queue
+        let async_action = controller
+            .ShutdownQueueAsync()
+            .map_err(|_| GraphicsCaptureApiError::FailedToShutdownDispatcherQueue)?;
+
+        async_action
+            .SetCompleted(&AsyncActionCompletedHandler::new(
+                move |_, _| -> Result<(), windows::core::Error> {
+                    unsafe { PostQuitMessage(0) };
+                    Ok(())
+                },
+            ))
+            .map_err(|_| GraphicsCaptureApiError::FailedToSetDispatcherQueueCompletedHandler)?;
+
+        // Final message loop
+        let mut message = MSG::default();
+        unsafe {
+            while GetMessageW(&mut message, None, 0, 0).as_bool() {
+                let _ = TranslateMessage(&message);
+                DispatchMessageW(&message);
+            }
+        }
+
+        // Stop capture
+        let handler_timed_out = capture.is_handler_timed_out();
+        let capture_error = capture.take_capture_error();
+        capture.stop_capture();
+
+        // Uninitialize WinRT
+        unsafe { RoUninitialize() };
+
+        // Check handler result
+        if handler_timed_out {
+            return Err(GraphicsCaptureApiError::HandlerTimedOut);
+        }
+
+        if let Some(e) = capture_error {
+            return Err(GraphicsCaptureApiError::GraphicsCaptureApiError(e));
+        }
+
+        let result = result.lock().take();
+        if let Some(e) = result {
+            return Err(GraphicsCaptureApiError::FrameHandlerError(e));
+        }
+
+        Ok(())
+    });
+
+    let Ok(halt_handle) = halt_receiver.recv() else {
+        match thread_handle.join() {
+            Ok(result) => return Err(result.err().unwrap()),
+            Err(_) => {
+                return Err(GraphicsCaptureApiError::FailedToJoinThread);
+            }
+        }
+    };
+
+    let Ok(counters) = counters_receiver.recv() else {
+        match thread_handle.join() {
+            Ok(result) => return Err(result.err().unwrap()),
+            Err(_) => {
+                return Err(GraphicsCaptureApiError::FailedToJoinThread);
+            }
+        }
+    };
+
+    let Ok(callback) = callback_receiver.recv() else {
+        match thread_handle.join() {
+            Ok(result) => return Err(result.err().unwrap()),
+            Err(_) => {
+                return Err(GraphicsCaptureApiError::FailedToJoinThread);
+            }
+        }
+    };
+
+    Ok(CaptureControl::with_counters(
+        thread_handle,
+        halt_handle,
+        counters,
+        callback,
+    ))
+}
+
+/// Calls a function when dropped, used to notify that the capture thread exited.
+struct ExitGuard<F: FnOnce()>(Option<F>);
+
+impl<F: FnOnce()> Drop for ExitGuard<F> {
+    fn drop(&mut self) {
+        if let Some(on_exit) = self.0.take() {
+            on_exit();
+        }
+    }
+}
+
+/// Returns the color formats the frame pool accepts for the capture item.
+///
+/// Each color format is probed by creating a frame pool and a capture session for the item, the
+/// probes are closed right away and no frame is captured.
+///
+/// # Arguments
+///
+/// * `item` - The graphics capture item to probe.
+///
+/// # Returns
+///
+/// Returns the supported color formats, or an `Error` if the Direct3D device can't be created.
+#[inline]
+pub fn supported_color_formats(
+    item: &GraphicsCaptureItem,
+) -> Result<Vec<ColorFormat>, graphics_capture_api::Error> {
+    let (d3d_device, _) = create_d3d_device()?;
+    let direct3d_device = create_direct3d_device(&d3d_device)?;
+    let size = item.Size()?;
+
+    let mut color_formats = Vec::new();
+    for color_format in [ColorFormat::Rgba16F, ColorFormat::Rgba8, ColorFormat::Bgra8] {
+        let Ok(frame_pool) = Direct3D11CaptureFramePool::Create(
+            &direct3d_device,
+            DirectXPixelFormat(color_format as i32),
+            1,
+            size,
+        ) else {
+            continue;
+        };
+
+        let is_supported = frame_pool
+            .CreateCaptureSession(item)
+            .is_ok_and(|session| session.Close().is_ok());
+        frame_pool.Close()?;
+
+        if is_supported {
+            color_formats.push(color_format);
+        }
+    }
+
+    Ok(color_formats)
+}
+
+/// Captures a single frame of the current foreground window.
+///
+/// The frame has the size of the window. The function waits until the window is capturable,
+/// then captures on a separate thread that is stopped as soon as the first frame arrives.
+///
+/// # Arguments
+///
+/// * `color_format` - The color format of the captured frame.
+/// * `timeout` - The maximum time to wait for the window to become capturable and present a
+///   frame, a minimized window or a window that never presents would otherwise block forever.
+///
+/// # Returns
+///
+/// Returns the captured frame, `ScreenshotError::WindowError(window::Error::NoActiveWindow)` if
+/// there is no foreground window, or `ScreenshotError::Timeout` if no frame arrived in time.
+#[inline]
+pub fn screenshot_foreground(
+    color_format: ColorFormat,
+    timeout: Duration,
+) -> Result<OwnedFrame, ScreenshotError> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+    let start = Instant::now();
+    let window = Window::foreground()?;
+
+    window
+        .wait_until_capturable(timeout)
+        .map_err(|error| match error {
+            window::Error::CapturableTimeout => ScreenshotError::Timeout,
+            error => ScreenshotError::WindowError(error),
+        })?;
+
+    let frame = Arc::new(Mutex::new(None));
+    let settings = Settings::new(
+        window,
+        CursorCaptureSettings::Default,
+        DrawBorderSettings::Default,
+        color_format,
+        frame.clone(),
+    );
+    let capture_control = ScreenshotHandler::start_free_threaded(settings)?;
+
+    while !capture_control.is_finished() {
+        if start.elapsed() >= timeout {
+            capture_control.stop()?;
+
+            // The frame may have arrived while the capture was stopped
+            let frame = frame.lock().take();
+            return frame.ok_or(ScreenshotError::Timeout);
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+    capture_control.wait()?;
+
+    let frame = frame.lock().take();
+    frame.ok_or(ScreenshotError::NoFrame)
+}
+
+/// Capture handler that copies the first frame and stops the capture.
+struct ScreenshotHandler {
+    frame: Arc<Mutex<Option<OwnedFrame>>>,
+}
+
+impl GraphicsCaptureApiHandler for ScreenshotHandler {
+    type Flags = Arc<Mutex<Option<OwnedFrame>>>;
+    type Error = frame::Error;
+
+    fn new(ctx: Context<Self::Flags>) -> Result<Self, Self::Error> {
+        Ok(Self { frame: ctx.flags })
+    }
+
+    fn on_frame_arrived(
+        &mut self,
+        frame: &mut Frame,
+        capture_control: InternalCaptureControl,
+    ) -> Result<(), Self::Error> {
+        *self.frame.lock() = Some(frame.to_owned()?);
+
+        capture_control.stop();
+
+        Ok(())
+    }
+}
+
+/// The maximum number of frames a `FrameStream` keeps before dropping the oldest one.
+const FRAME_STREAM_CAPACITY: usize = 3;
+
+/// An async stream of captured frames, it implements `futures_core::Stream`.
+///
+/// The capture runs on its own thread like `GraphicsCaptureApiHandler::start_free_threaded`.
+/// Because the frame texture can't outlive the frame handler, every frame is copied to an owned
+/// CPU buffer before it's queued, this costs a GPU to CPU copy of the whole frame per frame. If
+/// the consumer falls behind, only the latest frames are kept and the oldest ones are dropped,
+/// `dropped_frames` reports how many.
+///
+/// A stream created with `FrameStream::new_gpu` copies every frame into an `OwnedGpuFrame`
+/// instead, which keeps the pixels on the GPU.
+///
+/// # Example
+/// ```ignore
+/// let mut stream = FrameStream::new(settings)?;
+///
+/// while let Some(frame) = stream.next().await {
+///     let frame = frame?;
+///     println!("New frame: {}x{}", frame.width(), frame.height());
+/// }
+/// ```
+pub struct FrameStream<F: Send + 'static = OwnedFrame> {
+    capture_control: Option<CaptureControl<FrameStreamHandler<F>, frame::Error>>,
+    state: Arc<Mutex<FrameStreamState<F>>>,
+}
+
+impl FrameStream {
+    /// Starts the capture and creates a new frame stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - The capture settings, the flags are not used.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(FrameStream)` if the capture was started successfully, otherwise returns an error of type `GraphicsCaptureApiError`.
+    #[inline]
+    pub fn new<Flags, T: TryInto<GraphicsCaptureItem> + Send + 'static>(
+        settings: Settings<Flags, T>,
+    ) -> Result<Self, GraphicsCaptureApiError<frame::Error>> {
+        Self::start(settings, |frame| frame.to_owned())
+    }
+}
+
+impl FrameStream<OwnedGpuFrame> {
+    /// Starts the capture and creates a new frame stream that keeps the frames on the GPU.
+    ///
+    /// Every frame is copied into an `OwnedGpuFrame` with `Frame::to_owned_gpu`, without a CPU
+    /// round-trip. The textures belong to the capture's Direct3D device, which can be retrieved
+    /// with `ID3D11DeviceChild::GetDevice` on the texture, see `OwnedGpuFrame` for the device
+    /// affinity requirements.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - The capture settings, the flags are not used.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(FrameStream)` if the capture was started successfully, otherwise returns an error of type `GraphicsCaptureApiError`.
+    #[inline]
+    pub fn new_gpu<Flags, T: TryInto<GraphicsCaptureItem> + Send + 'static>(
+        settings: Settings<Flags, T>,
+    ) -> Result<Self, GraphicsCaptureApiError<frame::Error>> {
+        Self::start(settings, |frame| frame.to_owned_gpu())
+    }
+}
+
+impl<F: Send + 'static> FrameStream<F> {
+    /// Starts the capture of a frame stream that copies every frame with `copy`.
+    fn start<Flags, T: TryInto<GraphicsCaptureItem> + Send + 'static>(
+        settings: Settings<Flags, T>,
+        copy: fn(&mut Frame) -> Result<F, frame::Error>,
+    ) -> Result<Self, GraphicsCaptureApiError<frame::Error>> {
+        let state = Arc::new(Mutex::new(FrameStreamState {
+            copy,
+            frames: VecDeque::with_capacity(FRAME_STREAM_CAPACITY),
+            dropped: 0,
+            #[cfg(test)]
+            produced: 0,
+            closed: false,
+            waker: None,
+        }));
+
+        // Close the stream when the capture thread exits, even if it failed without calling
+        // `on_closed`, so a pending `next` doesn't wait forever
+        let exit_state = state.clone();
+        let capture_control = spawn_capture_thread::<FrameStreamHandler<F>, T>(
+            settings.with_flags(state.clone()),
+            move || {
+                let mut state = exit_state.lock();
+                state.closed = true;
+                state.wake();
+            },
+        )?;
+
+        Ok(Self {
+            capture_control: Some(capture_control),
+            state,
+        })
+    }
+
+    /// Polls the next frame of the stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `cx` - The task context used to wake the task when a new frame arrives.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Poll::Ready(Some(frame))` if a frame is available, `Poll::Ready(None)` if the capture has ended, otherwise `Poll::Pending`.
+    #[inline]
+    pub fn poll_next(
+        &mut self,
+        cx: &mut task::Context<'_>,
+    ) -> Poll<Option<Result<F, frame::Error>>> {
+        let mut state = self.state.lock();
+
+        if let Some(frame) = state.frames.pop_front() {
+            return Poll::Ready(Some(frame));
+        }
+
+        if state.closed
+            || self
+                .capture_control
+                .as_ref()
+                .is_none_or(CaptureControl::is_finished)
+        {
+            return Poll::Ready(None);
+        }
+
+        state.waker = Some(cx.waker().clone());
+
+        Poll::Pending
+    }
+
+    /// Waits for the next frame of the stream.
+    ///
+    /// # Returns
+    ///
+    /// Returns the next frame, or `None` if the capture has ended.
+    #[inline]
+    pub async fn next(&mut self) -> Option<Result<F, frame::Error>> {
+        future::poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    /// Gets the number of frames dropped because the consumer fell behind.
+    ///
+    /// # Returns
+    ///
+    /// The number of frames that were removed from the stream before they were received.
+    #[must_use]
+    #[inline]
+    pub fn dropped_frames(&self) -> u64 {
+        self.state.lock().dropped
+    }
+
+    /// Gracefully stops the capture thread.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the capture thread stops successfully, an error otherwise.
+    #[inline]
+    pub fn stop(mut self) -> Result<(), CaptureControlError<frame::Error>> {
+        self.capture_control
+            .take()
+            .map_or(Ok(()), CaptureControl::stop)
+    }
+
+    /// Stops the capture thread and keeps the frames that were already captured.
+    ///
+    /// The capture thread is joined before the stream is closed, so no frame handler call is
+    /// still running and every frame captured up to the stop is in the stream. The stream then
+    /// yields the remaining frames followed by `None`, which marks that the consumer has received
+    /// every frame. Frames dropped earlier because the consumer fell behind are not recovered,
+    /// they are counted by `dropped_frames`.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the capture thread stops successfully, an error otherwise.
+    #[inline]
+    pub fn stop_and_flush(&mut self) -> Result<(), CaptureControlError<frame::Error>> {
+        let result = self
+            .capture_control
+            .take()
+            .map_or(Ok(()), CaptureControl::stop_and_flush);
+
+        let mut state = self.state.lock();
+        state.closed = true;
+        state.wake();
+
+        result
+    }
+}
+
+impl<F: Send + 'static> Stream for FrameStream<F> {
+    type Item = Result<F, frame::Error>;
+
+    #[inline]
+    fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        Self::poll_next(self.get_mut(), cx)
+    }
+}
+
+/// The state shared between a `FrameStream` and its capture handler.
+struct FrameStreamState<F> {
+    // Copies A Frame Out Of The Frame Handler, Read By The Handler When It Is Created
+    copy: fn(&mut Frame) -> Result<F, frame::Error>,
+    frames: VecDeque<Result<F, frame::Error>>,
+    dropped: u64,
+    // Number Of Frames Queued By The Handler, Checked By The Tests
+    #[cfg(test)]
+    produced: u64,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl<F> FrameStreamState<F> {
+    /// Wakes the task waiting for the next frame.
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Capture handler that copies every frame into a `FrameStream`.
+struct FrameStreamHandler<F> {
+    copy: fn(&mut Frame) -> Result<F, frame::Error>,
+    state: Arc<Mutex<FrameStreamState<F>>>,
+}
+
+impl<F: Send + 'static> GraphicsCaptureApiHandler for FrameStreamHandler<F> {
+    type Flags = Arc<Mutex<FrameStreamState<F>>>;
+    type Error = frame::Error;
+
+    fn new(ctx: Context<Self::Flags>) -> Result<Self, Self::Error> {
+        let copy = ctx.flags.lock().copy;
+
+        Ok(Self {
+            copy,
+            state: ctx.flags,
+        })
+    }
+
+    fn on_frame_arrived(
+        &mut self,
+        frame: &mut Frame,
+        _capture_control: InternalCaptureControl,
+    ) -> Result<(), Self::Error> {
+        let owned_frame = (self.copy)(frame);
+
+        let mut state = self.state.lock();
+        if state.frames.len() == FRAME_STREAM_CAPACITY {
+            state.frames.pop_front();
+            state.dropped += 1;
+        }
+        state.frames.push_back(owned_frame);
+        #[cfg(test)]
+        {
+            state.produced += 1;
+        }
+        state.wake();
+        drop(state);
+
+        Ok(())
+    }
+
+    fn on_closed(&mut self) -> Result<(), Self::Error> {
+        let mut state = self.state.lock();
+        state.closed = true;
+        state.wake();
+        drop(state);
+
+        Ok(())
+    }
+
+    fn on_flush(&mut self) -> Result<(), Self::Error> {
+        self.on_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use windows::Win32::Graphics::Direct3D11::D3D11_TEXTURE2D_DESC;
+
+    use super::*;
+    use crate::monitor::Monitor;
+
+    #[test]
+    fn screenshot_foreground_returns_frame_or_error() {
+        // Depending on the environment there may be no foreground window, or it never presents
+        match screenshot_foreground(ColorFormat::Rgba8, Duration::from_secs(5)) {
+            Ok(frame) => {
+                assert!(frame.width() > 0);
+                assert!(frame.height() > 0);
+                assert_eq!(frame.color_format(), ColorFormat::Rgba8);
+            }
+            Err(
+                ScreenshotError::WindowError(window::Error::NoActiveWindow)
+                | ScreenshotError::Timeout,
+            ) => (),
+            Err(error) => panic!("Unexpected screenshot error: {error}"),
+        }
+    }
+
+    #[test]
+    fn capture_control_without_counters_has_empty_stats() {
+        let capture_control = CaptureControl::<ScreenshotHandler, frame::Error>::new(
+            thread::spawn(|| Ok(())),
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(Mutex::new(ScreenshotHandler {
+                frame: Arc::new(Mutex::new(None)),
+            })),
+        );
+
+        assert_eq!(capture_control.stats(), CaptureStats::default());
+        assert_eq!(capture_control.epoch_qpc(), None);
+        capture_control.wait().unwrap();
+    }
+
+    #[test]
+    fn start_on_dispatcher_rejects_queue_of_other_thread() {
+        let controller = DispatcherQueueController::CreateOnDedicatedThread().unwrap();
+        let settings = Settings::new(
+            Monitor::primary().unwrap(),
+            CursorCaptureSettings::Default,
+            DrawBorderSettings::Default,
+            ColorFormat::Rgba8,
+            Arc::new(Mutex::new(None)),
+        );
+
+        assert!(matches!(
+            ScreenshotHandler::start_on_dispatcher(settings, &controller),
+            Err(GraphicsCaptureApiError::DispatcherQueueNotOnCurrentThread)
+        ));
+
+        controller.ShutdownQueueAsync().unwrap().get().unwrap();
+    }
+
+    // Handler That Forwards Every Frame To A Channel And Ends It With A Flush Marker
+    struct ChannelHandler {
+        sender: mpsc::Sender<Option<u64>>,
+        produced: u64,
+    }
+
+    impl GraphicsCaptureApiHandler for ChannelHandler {
+        type Flags = mpsc::Sender<Option<u64>>;
+        type Error = frame::Error;
+
+        fn new(ctx: Context<Self::Flags>) -> Result<Self, Self::Error> {
+            Ok(Self {
+                sender: ctx.flags,
+                produced: 0,
+            })
+        }
+
+        fn on_frame_arrived(
+            &mut self,
+            _frame: &mut Frame,
+            _capture_control: InternalCaptureControl,
+        ) -> Result<(), Self::Error> {
+            self.produced += 1;
+            let _ = self.sender.send(Some(self.produced));
+
+            Ok(())
+        }
+
+        fn on_flush(&mut self) -> Result<(), Self::Error> {
+            let _ = self.sender.send(None);
+
+            Ok(())
+        }
+    }
+
+    // Waker That Does Nothing, The Tests Poll In A Loop
+    struct NoopWaker;
+
+    impl task::Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[test]
+    fn stop_and_flush_delivers_every_frame() {
+        let (sender, receiver) = mpsc::channel();
+        let settings = Settings::new(
+            Monitor::primary().unwrap(),
+            CursorCaptureSettings::Default,
+            DrawBorderSettings::Default,
+            ColorFormat::Rgba8,
+            sender,
+        );
+
+        // The consumer counts frames until the flush marker arrives
+        let consumer = thread::spawn(move || {
+            let mut received = 0;
+            while let Some(index) = receiver.recv().unwrap() {
+                received += 1;
+                assert_eq!(index, received);
+            }
+            received
+        });
+
+        let capture_control = ChannelHandler::start_free_threaded(settings).unwrap();
+        let callback = capture_control.callback();
+        thread::sleep(Duration::from_millis(500));
+        capture_control.stop_and_flush().unwrap();
+
+        let received = consumer.join().unwrap();
+        assert_eq!(received, callback.lock().produced);
+    }
+
+    #[test]
+    fn frame_stream_stop_and_flush_accounts_for_every_frame() {
+        let settings = Settings::new(
+            Monitor::primary().unwrap(),
+            CursorCaptureSettings::Default,
+            DrawBorderSettings::Default,
+            ColorFormat::Rgba8,
+            (),
+        );
+
+        let mut stream = FrameStream::new(settings).unwrap();
+        thread::sleep(Duration::from_millis(500));
+        stream.stop_and_flush().unwrap();
+
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = task::Context::from_waker(&waker);
+        let mut received = 0;
+        loop {
+            match stream.poll_next(&mut cx) {
+                Poll::Ready(Some(frame)) => {
+                    frame.unwrap();
+                    received += 1;
+                }
+                Poll::Ready(None) => break,
+                Poll::Pending => panic!("The stream is pending after stop_and_flush"),
+            }
+        }
+
+        assert!(received <= FRAME_STREAM_CAPACITY as u64);
+        assert_eq!(
+            received + stream.dropped_frames(),
+            stream.state.lock().produced
+        );
+    }
+
+    // Handler That Sleeps In Every Call And Counts The Calls
+    struct SleepHandler {
+        sleep: Duration,
+        calls: u32,
+    }
+
+    impl GraphicsCaptureApiHandler for SleepHandler {
+        type Flags = Duration;
+        type Error = frame::Error;
+
+        fn new(ctx: Context<Self::Flags>) -> Result<Self, Self::Error> {
+            Ok(Self {
+                sleep: ctx.flags,
+                calls: 0,
+            })
+        }
+
+        fn on_frame_arrived(
+            &mut self,
+            _frame: &mut Frame,
+            capture_control: InternalCaptureControl,
+        ) -> Result<(), Self::Error> {
+            self.calls += 1;
+            thread::sleep(self.sleep);
+
+            if self.calls == 3 {
+                capture_control.stop();
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn handler_timeout_stops_slow_handler() {
+        let settings = Settings::new(
+            Monitor::primary().unwrap(),
+            CursorCaptureSettings::Default,
+            DrawBorderSettings::Default,
+            ColorFormat::Rgba8,
+            Duration::from_millis(500),
+        )
+        .with_handler_timeout(Some(Duration::from_millis(50)));
+
+        let capture_control = SleepHandler::start_free_threaded(settings).unwrap();
+        let callback = capture_control.callback();
+
+        // The watchdog stops the capture once the hung call returns, and is joined at teardown
+        assert!(matches!(
+            capture_control.wait(),
+            Err(CaptureControlError::GraphicsCaptureApiError(
+                GraphicsCaptureApiError::HandlerTimedOut
+            ))
+        ));
+        assert_eq!(callback.lock().calls, 1);
+    }
+
+    #[test]
+    fn handler_timeout_lets_fast_handler_run() {
+        let settings = Settings::new(
+            Monitor::primary().unwrap(),
+            CursorCaptureSettings::Default,
+            DrawBorderSettings::Default,
+            ColorFormat::Rgba8,
+            Duration::ZERO,
+        )
+        .with_handler_timeout(Some(Duration::from_secs(5)));
+
+        let capture_control = SleepHandler::start_free_threaded(settings).unwrap();
+        let callback = capture_control.callback();
+
+        capture_control.wait().unwrap();
+        assert_eq!(callback.lock().calls, 3);
+    }
+
+    #[test]
+    fn frame_stream_keeps_gpu_frames() {
+        let monitor = Monitor::primary().unwrap();
+        let settings = Settings::new(
+            monitor,
+            CursorCaptureSettings::Default,
+            DrawBorderSettings::Default,
+            ColorFormat::Rgba8,
+            (),
+        );
+
+        let mut stream = FrameStream::new_gpu(settings).unwrap();
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = task::Context::from_waker(&waker);
+        let frame = loop {
+            match stream.poll_next(&mut cx) {
+                Poll::Ready(Some(frame)) => break frame.unwrap(),
+                Poll::Ready(None) => panic!("The stream ended before the first frame"),
+                Poll::Pending => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        stream.stop().unwrap();
+
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { frame.texture().GetDesc(&mut desc) };
+        assert_eq!((desc.Width, desc.Height), (frame.width(), frame.height()));
+        assert_eq!(frame.color_format(), ColorFormat::Rgba8);
+    }
+
+    #[test]
+    fn supported_color_formats_of_monitor() {
+        let item = GraphicsCaptureItem::try_from(Monitor::primary().unwrap()).unwrap();
+        let color_formats = supported_color_formats(&item).unwrap();
+
+        // Every capture item accepts the 8-bit formats
+        assert!(color_formats.contains(&ColorFormat::Rgba8));
+        assert!(color_formats.contains(&ColorFormat::Bgra8));
+
+        // Formats are reported once each, in probing order
+        let probe_order = [ColorFormat::Rgba16F, ColorFormat::Rgba8, ColorFormat::Bgra8];
+        let mut probed = probe_order
+            .iter()
+            .filter(|color_format| color_formats.contains(color_format));
+        assert!(color_formats
+            .iter()
+            .all(|color_format| probed.next() == Some(color_format)));
+    }
+
+    #[test]
+    fn supported_color_formats_leaves_item_capturable() {
+        let monitor = Monitor::primary().unwrap();
+        let item = GraphicsCaptureItem::try_from(monitor).unwrap();
+        supported_color_formats(&item).unwrap();
+
+        // The probe sessions are closed, so a real capture of the same item still delivers frames
+        let frame = Arc::new(Mutex::new(None));
+        let settings = Settings::new(
+            monitor,
+            CursorCaptureSettings::Default,
+            DrawBorderSettings::Default,
+            ColorFormat::Rgba8,
+            frame.clone(),
+        );
+        ScreenshotHandler::start_free_threaded(settings)
+            .unwrap()
+            .wait()
+            .unwrap();
+        assert!(frame.lock().is_some());
+    }
 }
@@ -1,7 +1,13 @@
+use std::{
+    mem::{self, ManuallyDrop},
+    ptr,
+};
+
 use windows::{
     core::Interface,
     Graphics::DirectX::Direct3D11::IDirect3DDevice,
     Win32::{
+        Foundation::{FALSE, POINT, RECT, TRUE},
         Graphics::{
             Direct3D::{
                 D3D_DRIVER_TYPE_HARDWARE, D3D_FEATURE_LEVEL, D3D_FEATURE_LEVEL_10_0,
@@ -9,21 +15,47 @@ use windows::{
                 D3D_FEATURE_LEVEL_9_1, D3D_FEATURE_LEVEL_9_2, D3D_FEATURE_LEVEL_9_3,
             },
             Direct3D11::{
-                D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext,
-                D3D11_CREATE_DEVICE_BGRA_SUPPORT, D3D11_SDK_VERSION,
+                D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, ID3D11RenderTargetView,
+                ID3D11Texture2D, ID3D11VideoContext, ID3D11VideoDevice, ID3D11VideoProcessor,
+                ID3D11VideoProcessorEnumerator, ID3D11VideoProcessorOutputView,
+                D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE, D3D11_BOX,
+                D3D11_CPU_ACCESS_READ, D3D11_CPU_ACCESS_WRITE, D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+                D3D11_RENDER_TARGET_VIEW_DESC, D3D11_RENDER_TARGET_VIEW_DESC_0,
+                D3D11_RESOURCE_MISC_GDI_COMPATIBLE, D3D11_RTV_DIMENSION_TEXTURE2D,
+                D3D11_SDK_VERSION, D3D11_TEX2D_RTV, D3D11_TEX2D_VPIV, D3D11_TEX2D_VPOV,
+                D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT, D3D11_USAGE_STAGING,
+                D3D11_VIDEO_FRAME_FORMAT_PROGRESSIVE, D3D11_VIDEO_PROCESSOR_ALPHA_FILL_MODE_OPAQUE,
+                D3D11_VIDEO_PROCESSOR_COLOR_SPACE, D3D11_VIDEO_PROCESSOR_CONTENT_DESC,
+                D3D11_VIDEO_PROCESSOR_INPUT_VIEW_DESC, D3D11_VIDEO_PROCESSOR_INPUT_VIEW_DESC_0,
+                D3D11_VIDEO_PROCESSOR_OUTPUT_VIEW_DESC, D3D11_VIDEO_PROCESSOR_OUTPUT_VIEW_DESC_0,
+                D3D11_VIDEO_PROCESSOR_STREAM, D3D11_VIDEO_USAGE_PLAYBACK_NORMAL,
+                D3D11_VPIV_DIMENSION_TEXTURE2D, D3D11_VPOV_DIMENSION_TEXTURE2D,
             },
-            Dxgi::IDXGIDevice,
+            Dxgi::{
+                Common::{
+                    DXGI_FORMAT, DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_NV12,
+                    DXGI_FORMAT_R8G8_UNORM, DXGI_RATIONAL, DXGI_SAMPLE_DESC,
+                },
+                CreateDXGIFactory1, IDXGIAdapter3, IDXGIDevice, IDXGIFactory1, IDXGISurface1,
+                DXGI_MEMORY_SEGMENT_GROUP_LOCAL, DXGI_QUERY_VIDEO_MEMORY_INFO,
+            },
+            Gdi::{DeleteObject, GetObjectW, BITMAP},
         },
         System::WinRT::Direct3D11::CreateDirect3D11DeviceFromDXGIDevice,
+        UI::WindowsAndMessaging::{DrawIconEx, GetIconInfo, DI_NORMAL, HCURSOR, ICONINFO},
     },
 };
 
+use crate::settings::{CaptureRegion, ColorFormat};
+
 #[derive(thiserror::Error, Eq, PartialEq, Clone, Debug)]
 pub enum Error {
     #[error("Failed to create DirectX device with the recommended feature levels")]
     FeatureLevelNotSatisfied,
     #[error("Windows API Error: {0}")]
     WindowsError(#[from] windows::core::Error),
+    #[error("The crop region lies outside of the frame")]
+    CropOutsideFrame,
 }
 
 /// Used To Send DirectX Device Across Threads
@@ -49,6 +81,799 @@ impl<T> SendDirectX<T> {
 #[allow(clippy::non_send_fields_in_send_ty)]
 unsafe impl<T> Send for SendDirectX<T> {}
 
+/// Video Memory Information Of An Adapter
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub struct VideoMemoryInfo {
+    /// The amount of local video memory in bytes the application can use without being throttled.
+    pub budget: u64,
+    /// The amount of local video memory in bytes the application is currently using.
+    pub current_usage: u64,
+}
+
+/// A CPU Readable Texture That Can Be Reused To Read Back Frames Of The Same Size And Format
+///
+/// Pass it to `Frame::buffer_with` to avoid creating a staging texture for every frame. The
+/// texture stays mapped after a frame buffer is returned and is unmapped when it is reused.
+pub struct StagingTexture {
+    texture: ID3D11Texture2D,
+    width: u32,
+    height: u32,
+    color_format: ColorFormat,
+    is_mapped: bool,
+}
+
+impl StagingTexture {
+    /// Create A New `StagingTexture`
+    ///
+    /// # Arguments
+    ///
+    /// * `d3d_device` - The DirectX device the frames are captured with
+    /// * `width` - The width of the frames
+    /// * `height` - The height of the frames
+    /// * `color_format` - The color format of the frames
+    ///
+    /// # Returns
+    ///
+    /// Returns A New `StagingTexture` Instance
+    #[inline]
+    pub fn new(
+        d3d_device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        color_format: ColorFormat,
+    ) -> Result<Self, Error> {
+        let texture_desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT(color_format as i32),
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_STAGING,
+            BindFlags: 0,
+            CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as u32 | D3D11_CPU_ACCESS_WRITE.0 as u32,
+            MiscFlags: 0,
+        };
+
+        let mut texture = None;
+        unsafe { d3d_device.CreateTexture2D(&texture_desc, None, Some(&mut texture))? };
+
+        Ok(Self {
+            texture: texture.unwrap(),
+            width,
+            height,
+            color_format,
+            is_mapped: false,
+        })
+    }
+
+    /// Get The Width Of The Texture
+    #[must_use]
+    #[inline]
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Get The Height Of The Texture
+    #[must_use]
+    #[inline]
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Get The Color Format Of The Texture
+    #[must_use]
+    #[inline]
+    pub const fn color_format(&self) -> ColorFormat {
+        self.color_format
+    }
+
+    /// Get The Underlying Texture
+    #[must_use]
+    #[inline]
+    pub const fn texture(&self) -> &ID3D11Texture2D {
+        &self.texture
+    }
+
+    /// Unmap The Texture If It Is Mapped
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The device context the texture was mapped with
+    #[inline]
+    pub fn unmap(&mut self, context: &ID3D11DeviceContext) {
+        if self.is_mapped {
+            unsafe { context.Unmap(&self.texture, 0) };
+            self.is_mapped = false;
+        }
+    }
+
+    // Mark The Texture As Mapped
+    #[inline]
+    pub(crate) const fn set_mapped(&mut self) {
+        self.is_mapped = true;
+    }
+
+    // Get The Mapped State, For Frame Buffers That Unmap And Remap The Texture
+    #[inline]
+    pub(crate) const fn is_mapped_mut(&mut self) -> &mut bool {
+        &mut self.is_mapped
+    }
+}
+
+/// The Hot Spot And Size Of A Cursor
+#[derive(Clone, Copy)]
+pub struct CursorShape {
+    pub cursor: HCURSOR,
+    pub hotspot: POINT,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Draws The Cursor Onto Bgra8 Textures Through A GDI Compatible Copy Of The Area Under It
+pub struct CursorOverlay {
+    texture: Option<SendDirectX<ID3D11Texture2D>>,
+    shape: Option<SendDirectX<CursorShape>>,
+}
+
+impl CursorOverlay {
+    // Create An Overlay, The GDI Compatible Texture Is Created On First Use
+    pub const fn new() -> Self {
+        Self {
+            texture: None,
+            shape: None,
+        }
+    }
+
+    // Get The Shape Of A Cursor, It Is Only Read Again When The Cursor Changes
+    pub fn shape(&mut self, cursor: HCURSOR) -> Result<CursorShape, Error> {
+        if let Some(shape) = &self.shape {
+            if shape.0.cursor == cursor {
+                return Ok(shape.0);
+            }
+        }
+
+        let mut icon_info = ICONINFO::default();
+        unsafe { GetIconInfo(cursor, &mut icon_info)? };
+
+        // Monochrome Cursors Stack The AND And XOR Masks In A Bitmap Of Twice The Height
+        let mut bitmap = BITMAP::default();
+        let is_monochrome = icon_info.hbmColor.is_invalid();
+        let size = unsafe {
+            GetObjectW(
+                if is_monochrome {
+                    icon_info.hbmMask
+                } else {
+                    icon_info.hbmColor
+                },
+                i32::try_from(mem::size_of::<BITMAP>()).unwrap(),
+                Some(ptr::addr_of_mut!(bitmap).cast()),
+            )
+        };
+        unsafe {
+            let _ = DeleteObject(icon_info.hbmMask);
+            let _ = DeleteObject(icon_info.hbmColor);
+        };
+
+        if size == 0 {
+            return Err(Error::WindowsError(windows::core::Error::from_win32()));
+        }
+
+        let height = if is_monochrome {
+            bitmap.bmHeight / 2
+        } else {
+            bitmap.bmHeight
+        };
+
+        let shape = CursorShape {
+            cursor,
+            hotspot: POINT {
+                x: i32::try_from(icon_info.xHotspot).unwrap(),
+                y: i32::try_from(icon_info.yHotspot).unwrap(),
+            },
+            width: u32::try_from(bitmap.bmWidth).unwrap(),
+            height: u32::try_from(height).unwrap(),
+        };
+        self.shape = Some(SendDirectX::new(shape));
+
+        Ok(shape)
+    }
+
+    // Draw A Cursor With Its Top Left Corner At The Given Position Of A Bgra8 Texture, Only The
+    // Part Of The Texture Under The Cursor Is Copied Through GDI
+    pub fn draw(
+        &mut self,
+        d3d_device: &ID3D11Device,
+        context: &ID3D11DeviceContext,
+        texture: &ID3D11Texture2D,
+        shape: CursorShape,
+        x: i32,
+        y: i32,
+    ) -> Result<(), Error> {
+        let mut texture_desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { texture.GetDesc(&mut texture_desc) };
+
+        // Part Of The Texture Covered By The Cursor
+        let texture_width = i32::try_from(texture_desc.Width).unwrap();
+        let texture_height = i32::try_from(texture_desc.Height).unwrap();
+        let left = x.clamp(0, texture_width);
+        let top = y.clamp(0, texture_height);
+        let right = x
+            .saturating_add(i32::try_from(shape.width).unwrap())
+            .clamp(0, texture_width);
+        let bottom = y
+            .saturating_add(i32::try_from(shape.height).unwrap())
+            .clamp(0, texture_height);
+
+        if left >= right || top >= bottom {
+            return Ok(());
+        }
+
+        // Recreate The GDI Compatible Texture When The Cursor Size Changes
+        let mut gdi_desc = D3D11_TEXTURE2D_DESC::default();
+        if let Some(gdi_texture) = &self.texture {
+            unsafe { gdi_texture.0.GetDesc(&mut gdi_desc) };
+        }
+
+        if self.texture.is_none()
+            || gdi_desc.Width != shape.width
+            || gdi_desc.Height != shape.height
+        {
+            let gdi_desc = D3D11_TEXTURE2D_DESC {
+                Width: shape.width,
+                Height: shape.height,
+                MipLevels: 1,
+                ArraySize: 1,
+                Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Usage: D3D11_USAGE_DEFAULT,
+                BindFlags: D3D11_BIND_RENDER_TARGET.0 as u32,
+                CPUAccessFlags: 0,
+                MiscFlags: D3D11_RESOURCE_MISC_GDI_COMPATIBLE.0 as u32,
+            };
+
+            let mut gdi_texture = None;
+            unsafe { d3d_device.CreateTexture2D(&gdi_desc, None, Some(&mut gdi_texture))? };
+            self.texture = Some(SendDirectX::new(gdi_texture.unwrap()));
+        }
+
+        let gdi_texture = &self.texture.as_ref().unwrap().0;
+        let surface = gdi_texture.cast::<IDXGISurface1>()?;
+
+        let texture_box = D3D11_BOX {
+            left: u32::try_from(left).unwrap(),
+            top: u32::try_from(top).unwrap(),
+            front: 0,
+            right: u32::try_from(right).unwrap(),
+            bottom: u32::try_from(bottom).unwrap(),
+            back: 1,
+        };
+        let gdi_box = D3D11_BOX {
+            left: u32::try_from(left - x).unwrap(),
+            top: u32::try_from(top - y).unwrap(),
+            front: 0,
+            right: u32::try_from(right - x).unwrap(),
+            bottom: u32::try_from(bottom - y).unwrap(),
+            back: 1,
+        };
+
+        unsafe {
+            context.CopySubresourceRegion(
+                gdi_texture,
+                0,
+                gdi_box.left,
+                gdi_box.top,
+                0,
+                texture,
+                0,
+                Some(&texture_box),
+            );
+
+            let hdc = surface.GetDC(FALSE)?;
+            let result = DrawIconEx(hdc, 0, 0, shape.cursor, 0, 0, 0, None, DI_NORMAL);
+            surface.ReleaseDC(None)?;
+            result?;
+
+            context.CopySubresourceRegion(
+                texture,
+                0,
+                texture_box.left,
+                texture_box.top,
+                0,
+                gdi_texture,
+                0,
+                Some(&gdi_box),
+            );
+        };
+
+        Ok(())
+    }
+}
+
+/// Converts BGRA Textures To NV12 Textures On The GPU With The Video Processor
+///
+/// The converted frame is written to a single output texture that is reused for every frame,
+/// the input is scaled to the output size if they differ. In grayscale mode the chroma plane is
+/// cleared to the neutral value after every conversion.
+pub struct Nv12Converter {
+    d3d_device: ID3D11Device,
+    context: ID3D11DeviceContext,
+    chroma_view: Option<ID3D11RenderTargetView>,
+    video_device: ID3D11VideoDevice,
+    video_context: ID3D11VideoContext,
+    enumerator: ID3D11VideoProcessorEnumerator,
+    processor: ID3D11VideoProcessor,
+    output_texture: ID3D11Texture2D,
+    output_view: ID3D11VideoProcessorOutputView,
+    input_width: u32,
+    input_height: u32,
+    crop: Option<CaptureRegion>,
+    // Number Of Video Processor Passes, Checked By The Tests
+    #[cfg(test)]
+    passes: u32,
+}
+
+impl Nv12Converter {
+    // Create A Converter For Inputs Of The Given Size
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        d3d_device: &ID3D11Device,
+        context: &ID3D11DeviceContext,
+        input_width: u32,
+        input_height: u32,
+        output_width: u32,
+        output_height: u32,
+        grayscale: bool,
+        crop: Option<CaptureRegion>,
+    ) -> Result<Self, Error> {
+        let video_device: ID3D11VideoDevice = d3d_device.cast()?;
+        let video_context: ID3D11VideoContext = context.cast()?;
+
+        let (enumerator, processor) = Self::create_processor(
+            &video_device,
+            &video_context,
+            input_width,
+            input_height,
+            output_width,
+            output_height,
+        )?;
+
+        // NV12 Output Texture
+        let texture_desc = D3D11_TEXTURE2D_DESC {
+            Width: output_width,
+            Height: output_height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT_NV12,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_RENDER_TARGET.0 as u32 | D3D11_BIND_SHADER_RESOURCE.0 as u32,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
+        };
+
+        let mut output_texture = None;
+        unsafe { d3d_device.CreateTexture2D(&texture_desc, None, Some(&mut output_texture))? };
+        let output_texture = output_texture.unwrap();
+
+        let output_view = create_output_view(&video_device, &enumerator, &output_texture)?;
+
+        // Render Target View Of The Interleaved UV Plane
+        let chroma_view = if grayscale {
+            let chroma_view_desc = D3D11_RENDER_TARGET_VIEW_DESC {
+                Format: DXGI_FORMAT_R8G8_UNORM,
+                ViewDimension: D3D11_RTV_DIMENSION_TEXTURE2D,
+                Anonymous: D3D11_RENDER_TARGET_VIEW_DESC_0 {
+                    Texture2D: D3D11_TEX2D_RTV { MipSlice: 0 },
+                },
+            };
+
+            let mut chroma_view = None;
+            unsafe {
+                d3d_device.CreateRenderTargetView(
+                    &output_texture,
+                    Some(&chroma_view_desc),
+                    Some(&mut chroma_view),
+                )?;
+            };
+
+            chroma_view
+        } else {
+            None
+        };
+
+        Ok(Self {
+            d3d_device: d3d_device.clone(),
+            context: context.clone(),
+            chroma_view,
+            video_device,
+            video_context,
+            enumerator,
+            processor,
+            output_texture,
+            output_view,
+            input_width,
+            input_height,
+            crop,
+            #[cfg(test)]
+            passes: 0,
+        })
+    }
+
+    // Convert A BGRA Texture Into The Output Texture
+    pub fn convert(&mut self, texture: &ID3D11Texture2D) -> Result<&ID3D11Texture2D, Error> {
+        let mut texture_desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { texture.GetDesc(&mut texture_desc) };
+
+        // Recreate The Processor When The Input Size Changes
+        if texture_desc.Width != self.input_width || texture_desc.Height != self.input_height {
+            let mut output_desc = D3D11_TEXTURE2D_DESC::default();
+            unsafe { self.output_texture.GetDesc(&mut output_desc) };
+
+            let (enumerator, processor) = Self::create_processor(
+                &self.video_device,
+                &self.video_context,
+                texture_desc.Width,
+                texture_desc.Height,
+                output_desc.Width,
+                output_desc.Height,
+            )?;
+            self.output_view =
+                create_output_view(&self.video_device, &enumerator, &self.output_texture)?;
+            self.enumerator = enumerator;
+            self.processor = processor;
+            self.input_width = texture_desc.Width;
+            self.input_height = texture_desc.Height;
+        }
+
+        // Crop In The Same Pass By Reading Only The Source Rectangle
+        if let Some(crop) = self.crop {
+            let source_rect = crop_source_rect(crop, self.input_width, self.input_height)
+                .ok_or(Error::CropOutsideFrame)?;
+
+            unsafe {
+                self.video_context.VideoProcessorSetStreamSourceRect(
+                    &self.processor,
+                    0,
+                    TRUE,
+                    Some(&source_rect),
+                );
+            };
+        }
+
+        video_processor_blt(
+            &self.video_device,
+            &self.video_context,
+            &self.enumerator,
+            &self.processor,
+            &self.output_view,
+            texture,
+        )?;
+
+        #[cfg(test)]
+        {
+            self.passes += 1;
+        }
+
+        // Neutral Chroma Leaves Only The Luma
+        if let Some(chroma_view) = &self.chroma_view {
+            unsafe {
+                self.context
+                    .ClearRenderTargetView(chroma_view, &[128.0 / 255.0, 128.0 / 255.0, 0.0, 0.0]);
+            };
+        }
+
+        Ok(&self.output_texture)
+    }
+
+    // Copy The Output Texture Into A New Texture
+    pub fn copy_output(&self, context: &ID3D11DeviceContext) -> Result<ID3D11Texture2D, Error> {
+        let mut texture_desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { self.output_texture.GetDesc(&mut texture_desc) };
+
+        let mut texture = None;
+        unsafe {
+            self.d3d_device
+                .CreateTexture2D(&texture_desc, None, Some(&mut texture))?;
+        };
+        let texture = texture.unwrap();
+
+        unsafe { context.CopyResource(&texture, &self.output_texture) };
+
+        Ok(texture)
+    }
+
+    // Create The Video Processor For The Given Sizes
+    fn create_processor(
+        video_device: &ID3D11VideoDevice,
+        video_context: &ID3D11VideoContext,
+        input_width: u32,
+        input_height: u32,
+        output_width: u32,
+        output_height: u32,
+    ) -> Result<(ID3D11VideoProcessorEnumerator, ID3D11VideoProcessor), Error> {
+        let (enumerator, processor) = create_video_processor(
+            video_device,
+            input_width,
+            input_height,
+            output_width,
+            output_height,
+        )?;
+
+        // Full Range RGB Input To Studio Range BT.709 Output
+        let input_color_space = D3D11_VIDEO_PROCESSOR_COLOR_SPACE { _bitfield: 0 };
+        let output_color_space = D3D11_VIDEO_PROCESSOR_COLOR_SPACE { _bitfield: 0x14 };
+        unsafe {
+            video_context.VideoProcessorSetStreamFrameFormat(
+                &processor,
+                0,
+                D3D11_VIDEO_FRAME_FORMAT_PROGRESSIVE,
+            );
+            video_context.VideoProcessorSetStreamColorSpace(&processor, 0, &input_color_space);
+            video_context.VideoProcessorSetOutputColorSpace(&processor, &output_color_space);
+        };
+
+        Ok((enumerator, processor))
+    }
+}
+
+/// Fills The Alpha Channel Of Textures With Opaque On The GPU With The Video Processor
+///
+/// The video processor writes the texture into an output texture of the same size and format
+/// with the alpha fill mode set to opaque, the output is then copied back over the texture. The
+/// output texture and the processor are reused until the size or format changes.
+pub struct OpaqueFiller {
+    video_device: ID3D11VideoDevice,
+    video_context: ID3D11VideoContext,
+    output: Option<OpaqueFillerOutput>,
+}
+
+// The Video Processor And Output Texture Of An Input Size And Format
+struct OpaqueFillerOutput {
+    enumerator: ID3D11VideoProcessorEnumerator,
+    processor: ID3D11VideoProcessor,
+    output_texture: ID3D11Texture2D,
+    output_view: ID3D11VideoProcessorOutputView,
+}
+
+impl OpaqueFiller {
+    // Create A Filler, The Processor Is Created On First Use
+    pub fn new(d3d_device: &ID3D11Device, context: &ID3D11DeviceContext) -> Result<Self, Error> {
+        Ok(Self {
+            video_device: d3d_device.cast()?,
+            video_context: context.cast()?,
+            output: None,
+        })
+    }
+
+    // Set The Alpha Channel Of Every Pixel Of A Texture To Opaque
+    pub fn fill(
+        &mut self,
+        d3d_device: &ID3D11Device,
+        context: &ID3D11DeviceContext,
+        texture: &ID3D11Texture2D,
+    ) -> Result<(), Error> {
+        let mut texture_desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { texture.GetDesc(&mut texture_desc) };
+
+        // Recreate The Processor When The Size Or Format Changes
+        let mut output_desc = D3D11_TEXTURE2D_DESC::default();
+        if let Some(output) = &self.output {
+            unsafe { output.output_texture.GetDesc(&mut output_desc) };
+        }
+
+        if self.output.is_none()
+            || output_desc.Width != texture_desc.Width
+            || output_desc.Height != texture_desc.Height
+            || output_desc.Format != texture_desc.Format
+        {
+            let (enumerator, processor) = create_video_processor(
+                &self.video_device,
+                texture_desc.Width,
+                texture_desc.Height,
+                texture_desc.Width,
+                texture_desc.Height,
+            )?;
+
+            // Full Range RGB In And Out Without Any Driver Enhancements
+            let color_space = D3D11_VIDEO_PROCESSOR_COLOR_SPACE { _bitfield: 0 };
+            unsafe {
+                self.video_context.VideoProcessorSetStreamFrameFormat(
+                    &processor,
+                    0,
+                    D3D11_VIDEO_FRAME_FORMAT_PROGRESSIVE,
+                );
+                self.video_context
+                    .VideoProcessorSetStreamColorSpace(&processor, 0, &color_space);
+                self.video_context
+                    .VideoProcessorSetOutputColorSpace(&processor, &color_space);
+                self.video_context
+                    .VideoProcessorSetStreamAutoProcessingMode(&processor, 0, FALSE);
+                self.video_context.VideoProcessorSetOutputAlphaFillMode(
+                    &processor,
+                    D3D11_VIDEO_PROCESSOR_ALPHA_FILL_MODE_OPAQUE,
+                    0,
+                );
+            };
+
+            let output_desc = D3D11_TEXTURE2D_DESC {
+                Width: texture_desc.Width,
+                Height: texture_desc.Height,
+                MipLevels: 1,
+                ArraySize: 1,
+                Format: texture_desc.Format,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Usage: D3D11_USAGE_DEFAULT,
+                BindFlags: D3D11_BIND_RENDER_TARGET.0 as u32,
+                CPUAccessFlags: 0,
+                MiscFlags: 0,
+            };
+
+            let mut output_texture = None;
+            unsafe { d3d_device.CreateTexture2D(&output_desc, None, Some(&mut output_texture))? };
+            let output_texture = output_texture.unwrap();
+
+            let output_view = create_output_view(&self.video_device, &enumerator, &output_texture)?;
+
+            self.output = Some(OpaqueFillerOutput {
+                enumerator,
+                processor,
+                output_texture,
+                output_view,
+            });
+        }
+
+        let output = self.output.as_ref().unwrap();
+        video_processor_blt(
+            &self.video_device,
+            &self.video_context,
+            &output.enumerator,
+            &output.processor,
+            &output.output_view,
+            texture,
+        )?;
+
+        unsafe { context.CopyResource(texture, &output.output_texture) };
+
+        Ok(())
+    }
+}
+
+// Create A Video Processor For The Given Input And Output Sizes
+fn create_video_processor(
+    video_device: &ID3D11VideoDevice,
+    input_width: u32,
+    input_height: u32,
+    output_width: u32,
+    output_height: u32,
+) -> Result<(ID3D11VideoProcessorEnumerator, ID3D11VideoProcessor), Error> {
+    let content_desc = D3D11_VIDEO_PROCESSOR_CONTENT_DESC {
+        InputFrameFormat: D3D11_VIDEO_FRAME_FORMAT_PROGRESSIVE,
+        InputFrameRate: DXGI_RATIONAL {
+            Numerator: 60,
+            Denominator: 1,
+        },
+        InputWidth: input_width,
+        InputHeight: input_height,
+        OutputFrameRate: DXGI_RATIONAL {
+            Numerator: 60,
+            Denominator: 1,
+        },
+        OutputWidth: output_width,
+        OutputHeight: output_height,
+        Usage: D3D11_VIDEO_USAGE_PLAYBACK_NORMAL,
+    };
+
+    let enumerator = unsafe { video_device.CreateVideoProcessorEnumerator(&content_desc)? };
+    let processor = unsafe { video_device.CreateVideoProcessor(&enumerator, 0)? };
+
+    Ok((enumerator, processor))
+}
+
+// Create The Output View Of The Output Texture
+fn create_output_view(
+    video_device: &ID3D11VideoDevice,
+    enumerator: &ID3D11VideoProcessorEnumerator,
+    output_texture: &ID3D11Texture2D,
+) -> Result<ID3D11VideoProcessorOutputView, Error> {
+    let output_view_desc = D3D11_VIDEO_PROCESSOR_OUTPUT_VIEW_DESC {
+        ViewDimension: D3D11_VPOV_DIMENSION_TEXTURE2D,
+        Anonymous: D3D11_VIDEO_PROCESSOR_OUTPUT_VIEW_DESC_0 {
+            Texture2D: D3D11_TEX2D_VPOV { MipSlice: 0 },
+        },
+    };
+
+    let mut output_view = None;
+    unsafe {
+        video_device.CreateVideoProcessorOutputView(
+            output_texture,
+            enumerator,
+            &output_view_desc,
+            Some(&mut output_view),
+        )?;
+    };
+
+    Ok(output_view.unwrap())
+}
+
+// Process A Texture Into An Output View With The Video Processor
+// Clip A Crop Region To The Frame, Returns None If Nothing Of The Frame Is Left
+fn crop_source_rect(crop: CaptureRegion, width: u32, height: u32) -> Option<RECT> {
+    let left = crop.x.min(width);
+    let top = crop.y.min(height);
+    let right = crop.x.saturating_add(crop.width).min(width);
+    let bottom = crop.y.saturating_add(crop.height).min(height);
+
+    if left >= right || top >= bottom {
+        return None;
+    }
+
+    Some(RECT {
+        left: i32::try_from(left).unwrap(),
+        top: i32::try_from(top).unwrap(),
+        right: i32::try_from(right).unwrap(),
+        bottom: i32::try_from(bottom).unwrap(),
+    })
+}
+
+fn video_processor_blt(
+    video_device: &ID3D11VideoDevice,
+    video_context: &ID3D11VideoContext,
+    enumerator: &ID3D11VideoProcessorEnumerator,
+    processor: &ID3D11VideoProcessor,
+    output_view: &ID3D11VideoProcessorOutputView,
+    texture: &ID3D11Texture2D,
+) -> Result<(), Error> {
+    let input_view_desc = D3D11_VIDEO_PROCESSOR_INPUT_VIEW_DESC {
+        FourCC: 0,
+        ViewDimension: D3D11_VPIV_DIMENSION_TEXTURE2D,
+        Anonymous: D3D11_VIDEO_PROCESSOR_INPUT_VIEW_DESC_0 {
+            Texture2D: D3D11_TEX2D_VPIV {
+                MipSlice: 0,
+                ArraySlice: 0,
+            },
+        },
+    };
+
+    let mut input_view = None;
+    unsafe {
+        video_device.CreateVideoProcessorInputView(
+            texture,
+            enumerator,
+            &input_view_desc,
+            Some(&mut input_view),
+        )?;
+    };
+
+    let stream = D3D11_VIDEO_PROCESSOR_STREAM {
+        Enable: TRUE,
+        pInputSurface: ManuallyDrop::new(input_view),
+        ..Default::default()
+    };
+    let streams = [stream];
+
+    let result = unsafe { video_context.VideoProcessorBlt(processor, output_view, 0, &streams) };
+
+    // Release The Input View Owned By The Stream
+    let [mut stream] = streams;
+    unsafe { ManuallyDrop::drop(&mut stream.pInputSurface) };
+    result?;
+
+    Ok(())
+}
+
 /// Create `ID3D11Device` and `ID3D11DeviceContext`
 #[inline]
 pub fn create_d3d_device() -> Result<(ID3D11Device, ID3D11DeviceContext), Error> {
@@ -99,3 +924,326 @@ pub fn create_direct3d_device(d3d_device: &ID3D11Device) -> Result<IDirect3DDevi
 
     Ok(device)
 }
+
+/// Get The Local Video Memory Budget And Usage Of The Primary Adapter
+#[inline]
+pub fn video_memory_info() -> Result<VideoMemoryInfo, Error> {
+    let factory: IDXGIFactory1 = unsafe { CreateDXGIFactory1()? };
+    let adapter: IDXGIAdapter3 = unsafe { factory.EnumAdapters1(0)? }.cast()?;
+
+    let mut info = DXGI_QUERY_VIDEO_MEMORY_INFO::default();
+    unsafe { adapter.QueryVideoMemoryInfo(0, DXGI_MEMORY_SEGMENT_GROUP_LOCAL, &mut info)? };
+
+    Ok(VideoMemoryInfo {
+        budget: info.Budget,
+        current_usage: info.CurrentUsage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::slice;
+
+    use windows::Win32::{
+        Graphics::Direct3D11::{D3D11_MAPPED_SUBRESOURCE, D3D11_MAP_READ, D3D11_SUBRESOURCE_DATA},
+        UI::WindowsAndMessaging::{LoadCursorW, IDC_ARROW},
+    };
+
+    use super::*;
+
+    fn bgra_texture(
+        d3d_device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> ID3D11Texture2D {
+        let texture_desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_RENDER_TARGET.0 as u32 | D3D11_BIND_SHADER_RESOURCE.0 as u32,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
+        };
+        let initial_data = D3D11_SUBRESOURCE_DATA {
+            pSysMem: pixels.as_ptr().cast(),
+            SysMemPitch: width * 4,
+            SysMemSlicePitch: 0,
+        };
+
+        let mut texture = None;
+        unsafe {
+            d3d_device
+                .CreateTexture2D(&texture_desc, Some(&initial_data), Some(&mut texture))
+                .unwrap();
+        };
+
+        texture.unwrap()
+    }
+
+    // Read Back The Luma And The Interleaved Chroma Plane Of An NV12 Texture
+    fn read_nv12(
+        d3d_device: &ID3D11Device,
+        context: &ID3D11DeviceContext,
+        texture: &ID3D11Texture2D,
+    ) -> (Vec<u8>, Vec<u8>) {
+        let mut texture_desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { texture.GetDesc(&mut texture_desc) };
+        texture_desc.Usage = D3D11_USAGE_STAGING;
+        texture_desc.BindFlags = 0;
+        texture_desc.CPUAccessFlags = D3D11_CPU_ACCESS_READ.0 as u32;
+
+        let mut staging = None;
+        unsafe {
+            d3d_device
+                .CreateTexture2D(&texture_desc, None, Some(&mut staging))
+                .unwrap();
+        };
+        let staging = staging.unwrap();
+
+        let mut mapped_resource = D3D11_MAPPED_SUBRESOURCE::default();
+        unsafe {
+            context.CopyResource(&staging, texture);
+            context
+                .Map(&staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped_resource))
+                .unwrap();
+        };
+
+        let width = texture_desc.Width as usize;
+        let height = texture_desc.Height as usize;
+        let row_pitch = mapped_resource.RowPitch as usize;
+        let mapped = unsafe {
+            slice::from_raw_parts(
+                mapped_resource.pData.cast::<u8>(),
+                row_pitch * (height + height / 2),
+            )
+        };
+        let plane = |rows: std::ops::Range<usize>| {
+            rows.flat_map(|y| &mapped[y * row_pitch..y * row_pitch + width])
+                .copied()
+                .collect::<Vec<u8>>()
+        };
+        let planes = (plane(0..height), plane(height..height + height / 2));
+
+        unsafe { context.Unmap(&staging, 0) };
+
+        planes
+    }
+
+    #[test]
+    fn nv12_converter_grayscale_clears_chroma() {
+        let (d3d_device, context) = create_d3d_device().unwrap();
+
+        // Left half red, right half blue
+        let pixels = (0..64 * 32)
+            .flat_map(|i: u32| {
+                if i % 64 < 32 {
+                    [0x00, 0x00, 0xFF, 0xFF]
+                } else {
+                    [0xFF, 0x00, 0x00, 0xFF]
+                }
+            })
+            .collect::<Vec<u8>>();
+        let texture = bgra_texture(&d3d_device, 64, 32, &pixels);
+
+        let mut color =
+            Nv12Converter::new(&d3d_device, &context, 64, 32, 64, 32, false, None).unwrap();
+        let output = color.convert(&texture).unwrap().clone();
+        let (color_luma, color_chroma) = read_nv12(&d3d_device, &context, &output);
+
+        let mut grayscale =
+            Nv12Converter::new(&d3d_device, &context, 64, 32, 64, 32, true, None).unwrap();
+        let output = grayscale.convert(&texture).unwrap().clone();
+        let (grayscale_luma, grayscale_chroma) = read_nv12(&d3d_device, &context, &output);
+
+        // The luma is the same, only the chroma is neutral
+        assert_eq!(grayscale_luma, color_luma);
+        assert!(grayscale_chroma.iter().all(|chroma| *chroma == 128));
+        assert!(color_chroma.iter().any(|chroma| chroma.abs_diff(128) > 32));
+
+        // Red and blue differ in brightness, so the grayscale image keeps the edge
+        assert_ne!(grayscale_luma[0], grayscale_luma[63]);
+    }
+
+    #[test]
+    fn crop_source_rect_clips_to_frame() {
+        let rect = crop_source_rect(CaptureRegion::new(32, 16, 64, 64), 64, 32).unwrap();
+        assert_eq!(
+            (rect.left, rect.top, rect.right, rect.bottom),
+            (32, 16, 64, 32)
+        );
+
+        assert!(crop_source_rect(CaptureRegion::new(64, 0, 16, 16), 64, 32).is_none());
+        assert!(crop_source_rect(CaptureRegion::new(0, 40, 16, 16), 64, 32).is_none());
+        assert!(crop_source_rect(CaptureRegion::new(0, 0, 0, 16), 64, 32).is_none());
+    }
+
+    #[test]
+    fn nv12_converter_crops_and_scales_in_one_pass() {
+        let (d3d_device, context) = create_d3d_device().unwrap();
+
+        // Left half red, right half blue
+        let pixels = (0..64 * 32)
+            .flat_map(|i: u32| {
+                if i % 64 < 32 {
+                    [0x00, 0x00, 0xFF, 0xFF]
+                } else {
+                    [0xFF, 0x00, 0x00, 0xFF]
+                }
+            })
+            .collect::<Vec<u8>>();
+        let texture = bgra_texture(&d3d_device, 64, 32, &pixels);
+
+        // Crop the blue half and scale it down to 16x16
+        let crop = CaptureRegion::new(32, 0, 32, 32);
+        let mut converter =
+            Nv12Converter::new(&d3d_device, &context, 64, 32, 16, 16, false, Some(crop)).unwrap();
+        let output = converter.convert(&texture).unwrap().clone();
+        assert_eq!(converter.passes, 1);
+
+        let (luma, _) = read_nv12(&d3d_device, &context, &output);
+        assert_eq!(luma.len(), 16 * 16);
+        assert!(luma.iter().all(|value| value.abs_diff(luma[0]) <= 1));
+
+        // Without the crop the red half shows up with a different brightness
+        let mut uncropped =
+            Nv12Converter::new(&d3d_device, &context, 64, 32, 16, 16, false, None).unwrap();
+        let output = uncropped.convert(&texture).unwrap().clone();
+        let (uncropped_luma, _) = read_nv12(&d3d_device, &context, &output);
+        assert!(uncropped_luma[0].abs_diff(luma[0]) > 16);
+
+        // A crop outside of the frame is rejected
+        let mut outside = Nv12Converter::new(
+            &d3d_device,
+            &context,
+            64,
+            32,
+            16,
+            16,
+            false,
+            Some(CaptureRegion::new(64, 0, 32, 32)),
+        )
+        .unwrap();
+        assert!(matches!(
+            outside.convert(&texture),
+            Err(Error::CropOutsideFrame)
+        ));
+        assert_eq!(outside.passes, 0);
+    }
+
+    #[test]
+    fn video_memory_info_has_budget() {
+        let info = video_memory_info().unwrap();
+
+        assert!(info.budget > 0);
+    }
+
+    #[test]
+    fn cursor_overlay_draws_only_under_cursor() {
+        let (d3d_device, context) = create_d3d_device().unwrap();
+        let pixels = vec![0xFF; 64 * 32 * 4];
+        let texture = bgra_texture(&d3d_device, 64, 32, &pixels);
+
+        let cursor = unsafe { LoadCursorW(None, IDC_ARROW).unwrap() };
+        let mut cursor_overlay = CursorOverlay::new();
+        let shape = cursor_overlay.shape(cursor).unwrap();
+        assert!(shape.width > 0 && shape.height > 0);
+
+        // The shape is cached until the cursor changes
+        assert_eq!(cursor_overlay.shape(cursor).unwrap().width, shape.width);
+
+        // Partly outside of the top-left corner
+        cursor_overlay
+            .draw(&d3d_device, &context, &texture, shape, -2, -2)
+            .unwrap();
+
+        let staging = StagingTexture::new(&d3d_device, 64, 32, ColorFormat::Bgra8).unwrap();
+        let mut mapped_resource = D3D11_MAPPED_SUBRESOURCE::default();
+        unsafe {
+            context.CopyResource(staging.texture(), &texture);
+            context
+                .Map(
+                    staging.texture(),
+                    0,
+                    D3D11_MAP_READ,
+                    0,
+                    Some(&mut mapped_resource),
+                )
+                .unwrap();
+        };
+
+        let row_pitch = mapped_resource.RowPitch as usize;
+        let mapped =
+            unsafe { slice::from_raw_parts(mapped_resource.pData.cast::<u8>(), row_pitch * 32) };
+        let right = shape.width as usize - 2;
+        let bottom = shape.height as usize - 2;
+        let mut changed = false;
+        for y in 0..32 {
+            for x in 0..64 {
+                let pixel = &mapped[y * row_pitch + x * 4..y * row_pitch + x * 4 + 3];
+                if x < right && y < bottom {
+                    changed |= pixel != [0xFF; 3];
+                } else {
+                    assert_eq!(pixel, [0xFF; 3], "pixel ({x}, {y})");
+                }
+            }
+        }
+        assert!(changed);
+    }
+
+    #[test]
+    fn opaque_filler_sets_every_alpha() {
+        let (d3d_device, context) = create_d3d_device().unwrap();
+
+        // 64x32 Bgra8 pixels with varying colors and a transparent alpha channel
+        let pixels = (0..64 * 32)
+            .flat_map(|i: u32| [(i % 256) as u8, (i / 8) as u8, 0x80, 0x00])
+            .collect::<Vec<u8>>();
+
+        let texture = bgra_texture(&d3d_device, 64, 32, &pixels);
+
+        let mut opaque_filler = OpaqueFiller::new(&d3d_device, &context).unwrap();
+        opaque_filler.fill(&d3d_device, &context, &texture).unwrap();
+
+        let staging = StagingTexture::new(&d3d_device, 64, 32, ColorFormat::Bgra8).unwrap();
+        let mut mapped_resource = D3D11_MAPPED_SUBRESOURCE::default();
+        unsafe {
+            context.CopyResource(staging.texture(), &texture);
+            context
+                .Map(
+                    staging.texture(),
+                    0,
+                    D3D11_MAP_READ,
+                    0,
+                    Some(&mut mapped_resource),
+                )
+                .unwrap();
+        };
+
+        let row_pitch = mapped_resource.RowPitch as usize;
+        let mapped =
+            unsafe { slice::from_raw_parts(mapped_resource.pData.cast::<u8>(), row_pitch * 32) };
+        for y in 0..32 {
+            let row = &mapped[y * row_pitch..y * row_pitch + 64 * 4];
+            let expected = &pixels[y * 64 * 4..(y + 1) * 64 * 4];
+
+            for (pixel, expected) in row.chunks_exact(4).zip(expected.chunks_exact(4)) {
+                // The video processor may round the colors, but must not change them otherwise
+                assert_eq!(pixel[3], 0xFF);
+                assert!(pixel[..3]
+                    .iter()
+                    .zip(&expected[..3])
+                    .all(|(channel, expected)| channel.abs_diff(*expected) <= 1));
+            }
+        }
+
+        unsafe { context.Unmap(staging.texture(), 0) };
+    }
+}
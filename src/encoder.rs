@@ -1,5 +1,8 @@
 use std::{
     fs::{self, File},
+    io::Write,
+    ops::Range,
+    os::windows::ffi::OsStrExt,
     path::Path,
     slice,
     sync::{
@@ -9,13 +12,18 @@ use std::{
     thread::{self, JoinHandle},
 };
 
+use image::{codecs::webp::WebPEncoder, ExtendedColorType};
 use parking_lot::{Condvar, Mutex};
 use windows::{
-    core::HSTRING,
-    Foundation::{EventRegistrationToken, TimeSpan, TypedEventHandler},
+    core::{Interface, GUID, HSTRING},
+    Foundation::{
+        EventRegistrationToken, PropertyType, PropertyValue, TimeSpan, TypedEventHandler,
+    },
     Graphics::{
         DirectX::Direct3D11::IDirect3DSurface,
-        Imaging::{BitmapAlphaMode, BitmapEncoder, BitmapPixelFormat},
+        Imaging::{
+            BitmapAlphaMode, BitmapEncoder, BitmapPixelFormat, BitmapPropertySet, BitmapTypedValue,
+        },
     },
     Media::{
         Core::{
@@ -36,26 +44,123 @@ use windows::{
             Buffer, DataReader, IRandomAccessStream, InMemoryRandomAccessStream, InputStreamOptions,
         },
     },
+    Win32::{
+        Graphics::Dxgi::IDXGISurface,
+        System::WinRT::Direct3D11::CreateDirect3D11SurfaceFromDXGISurface,
+    },
 };
 
 use crate::{
-    d3d11::SendDirectX,
-    frame::{Frame, ImageFormat},
-    settings::ColorFormat,
+    d3d11::{self, Nv12Converter, SendDirectX},
+    frame::{swap_red_blue, Frame, ImageFormat},
+    settings::{CaptureRegion, ColorFormat},
 };
 
-#[derive(thiserror::Error, Eq, PartialEq, Clone, Debug)]
+#[derive(thiserror::Error, Debug)]
 pub enum ImageEncoderError {
     #[error("This color format is not supported for saving as image")]
     UnsupportedFormat,
+    #[error("WebP encoder error: {0}")]
+    WebPEncoderError(#[from] image::ImageError),
+    #[error("IO Error: {0}")]
+    IoError(#[from] std::io::Error),
     #[error("Windows API Error: {0}")]
     WindowsError(#[from] windows::core::Error),
 }
 
+/// The size of the chunks used to copy encoded images to a writer.
+const ENCODE_CHUNK_SIZE: u32 = 64 * 1024;
+
+/// The `PngFilter` enum represents the filters the PNG encoder can apply before compression.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum PngFilter {
+    Unspecified = 0,
+    None = 1,
+    Sub = 2,
+    Up = 3,
+    Average = 4,
+    Paeth = 5,
+    Adaptive = 6,
+}
+
+/// The `ImageEncoderOptions` struct represents the format specific options of the image encoder,
+/// options that don't apply to the image format are ignored.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ImageEncoderOptions {
+    jpeg_quality: Option<f32>,
+    png_filter: PngFilter,
+}
+
+impl ImageEncoderOptions {
+    /// Create new options that leave every setting to the encoder default.
+    #[must_use]
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            jpeg_quality: None,
+            png_filter: PngFilter::Unspecified,
+        }
+    }
+
+    /// Sets the quality of JPEG and JPEG XR images, clamped to `0.0..=1.0`.
+    #[must_use]
+    #[inline]
+    pub const fn jpeg_quality(mut self, jpeg_quality: f32) -> Self {
+        self.jpeg_quality = Some(jpeg_quality);
+        self
+    }
+
+    /// Sets the filter of PNG images, which affects how well the image compresses.
+    #[must_use]
+    #[inline]
+    pub const fn png_filter(mut self, png_filter: PngFilter) -> Self {
+        self.png_filter = png_filter;
+        self
+    }
+
+    fn build(self, format: ImageFormat) -> Result<BitmapPropertySet, ImageEncoderError> {
+        let properties = BitmapPropertySet::new()?;
+
+        match format {
+            ImageFormat::Jpeg | ImageFormat::JpegXr => {
+                if let Some(jpeg_quality) = self.jpeg_quality {
+                    properties.Insert(
+                        &HSTRING::from("ImageQuality"),
+                        &BitmapTypedValue::Create(
+                            &PropertyValue::CreateSingle(jpeg_quality.clamp(0.0, 1.0))?,
+                            PropertyType::Single,
+                        )?,
+                    )?;
+                }
+            }
+            ImageFormat::Png if self.png_filter != PngFilter::Unspecified => {
+                properties.Insert(
+                    &HSTRING::from("FilterOption"),
+                    &BitmapTypedValue::Create(
+                        &PropertyValue::CreateUInt8(self.png_filter as u8)?,
+                        PropertyType::UInt8,
+                    )?,
+                )?;
+            }
+            _ => (),
+        }
+
+        Ok(properties)
+    }
+}
+
+impl Default for ImageEncoderOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// The `ImageEncoder` struct represents an image encoder that can be used to encode image buffers to image bytes with a specified format and color format.
 pub struct ImageEncoder {
     format: ImageFormat,
     color_format: ColorFormat,
+    options: ImageEncoderOptions,
 }
 
 impl ImageEncoder {
@@ -75,6 +180,32 @@ impl ImageEncoder {
         Self {
             format,
             color_format,
+            options: ImageEncoderOptions::new(),
+        }
+    }
+
+    /// Create a new ImageEncoder with the specified format, color format and encoder options.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The desired image format.
+    /// * `color_format` - The desired color format.
+    /// * `options` - The format specific encoder options.
+    ///
+    /// # Returns
+    ///
+    /// A new `ImageEncoder` instance.
+    #[must_use]
+    #[inline]
+    pub const fn new_with_options(
+        format: ImageFormat,
+        color_format: ColorFormat,
+        options: ImageEncoderOptions,
+    ) -> Self {
+        Self {
+            format,
+            color_format,
+            options,
         }
     }
 
@@ -100,6 +231,36 @@ impl ImageEncoder {
         width: u32,
         height: u32,
     ) -> Result<Vec<u8>, ImageEncoderError> {
+        let mut bytes = Vec::new();
+        self.encode_to_writer(image_buffer, width, height, &mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    /// Encode the image buffer with the specified format and write the image bytes to a writer.
+    ///
+    /// The encoded image is copied to the writer in chunks instead of being collected in a single
+    /// buffer first. WebP doesn't go through WIC because Windows doesn't ship a WebP encoder, it is
+    /// encoded losslessly with the `image` crate instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `image_buffer` - The image buffer to encode.
+    /// * `width` - The width of the image.
+    /// * `height` - The height of the image.
+    /// * `writer` - The writer the encoded image bytes are written to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if the encoding or writing fails or if the color format is unsupported.
+    #[inline]
+    pub fn encode_to_writer<W: Write>(
+        &self,
+        image_buffer: &[u8],
+        width: u32,
+        height: u32,
+        writer: &mut W,
+    ) -> Result<(), ImageEncoderError> {
         let encoder = match self.format {
             ImageFormat::Jpeg => BitmapEncoder::JpegEncoderId()?,
             ImageFormat::Png => BitmapEncoder::PngEncoderId()?,
@@ -107,10 +268,14 @@ impl ImageEncoder {
             ImageFormat::Tiff => BitmapEncoder::TiffEncoderId()?,
             ImageFormat::Bmp => BitmapEncoder::BmpEncoderId()?,
             ImageFormat::JpegXr => BitmapEncoder::JpegXREncoderId()?,
+            ImageFormat::WebP => return self.encode_webp(image_buffer, width, height, writer),
         };
 
         let stream = InMemoryRandomAccessStream::new()?;
-        let encoder = BitmapEncoder::CreateAsync(encoder, &stream)?.get()?;
+        let encoding_options = self.options.build(self.format)?;
+        let encoder =
+            BitmapEncoder::CreateWithEncodingOptionsAsync(encoder, &stream, &encoding_options)?
+                .get()?;
 
         let pixelformat = match self.color_format {
             ColorFormat::Bgra8 => BitmapPixelFormat::Bgra8,
@@ -130,17 +295,57 @@ impl ImageEncoder {
 
         encoder.FlushAsync()?.get()?;
 
-        let buffer = Buffer::Create(u32::try_from(stream.Size()?).unwrap())?;
-        stream
-            .ReadAsync(&buffer, buffer.Capacity()?, InputStreamOptions::None)?
-            .get()?;
+        stream.Seek(0)?;
+        let mut remaining = stream.Size()?;
 
-        let data_reader = DataReader::FromBuffer(&buffer)?;
-        let length = data_reader.UnconsumedBufferLength()?;
-        let mut bytes = vec![0u8; length as usize];
-        data_reader.ReadBytes(&mut bytes)?;
+        let buffer = Buffer::Create(ENCODE_CHUNK_SIZE)?;
+        let mut bytes = vec![0u8; ENCODE_CHUNK_SIZE as usize];
+        while remaining > 0 {
+            let chunk = stream
+                .ReadAsync(&buffer, buffer.Capacity()?, InputStreamOptions::None)?
+                .get()?;
 
-        Ok(bytes)
+            let length = chunk.Length()?;
+            if length == 0 {
+                break;
+            }
+
+            let data_reader = DataReader::FromBuffer(&chunk)?;
+            data_reader.ReadBytes(&mut bytes[..length as usize])?;
+            writer.write_all(&bytes[..length as usize])?;
+
+            remaining -= u64::from(length);
+        }
+
+        Ok(())
+    }
+
+    /// Encode the image buffer as a lossless WebP image with the `image` crate.
+    fn encode_webp<W: Write>(
+        &self,
+        image_buffer: &[u8],
+        width: u32,
+        height: u32,
+        writer: &mut W,
+    ) -> Result<(), ImageEncoderError> {
+        let image_buffer = match self.color_format {
+            ColorFormat::Rgba8 => image_buffer.to_vec(),
+            ColorFormat::Bgra8 => {
+                let mut rgba = vec![0u8; image_buffer.len()];
+                swap_red_blue(&mut rgba, image_buffer);
+                rgba
+            }
+            ColorFormat::Rgba16F => return Err(ImageEncoderError::UnsupportedFormat),
+        };
+
+        WebPEncoder::new_lossless(writer).encode(
+            &image_buffer,
+            width,
+            height,
+            ExtendedColorType::Rgba8,
+        )?;
+
+        Ok(())
     }
 }
 
@@ -156,13 +361,165 @@ pub enum VideoEncoderError {
     VideoDisabled,
     #[error("Audio is disabled")]
     AudioDisabled,
+    #[error("Frame color format {0:?} does not match the encoder input color format {1:?}")]
+    ColorFormatMismatch(ColorFormat, ColorFormat),
+    #[error("Frame buffers can't be encoded at a constant frame rate")]
+    ConstantFrameRateUnsupported,
+    #[error("Keyframe interval must be at least one frame")]
+    InvalidKeyframeInterval,
+    #[error("The NV12 intermediate format requires an even width and height")]
+    InvalidIntermediateSize,
+    #[error("The crop region must not be empty")]
+    InvalidCrop,
+    #[error(
+        "The bitrate can't be changed while encoding, MediaTranscoder reads it once when the \
+         transcode is prepared"
+    )]
+    UnsupportedBitrateChange,
+    #[error(
+        "Keyframes can't be requested while encoding, MediaTranscoder doesn't expose its encoder, \
+         use VideoSettingsBuilder::keyframe_interval instead"
+    )]
+    UnsupportedKeyframeRequest,
+    #[error("Frame error: {0}")]
+    FrameError(#[from] crate::frame::Error),
+    #[error("DirectX error: {0}")]
+    DirectXError(#[from] d3d11::Error),
+    #[error("Transcode thread panicked")]
+    TranscodeThreadPanicked,
+    #[error("The transcoder stopped after an earlier error")]
+    TranscodeStopped,
     #[error("IO Error: {0}")]
     IoError(#[from] std::io::Error),
 }
 
+/// The color format the video encoder expects its input frames in, `IntermediateFormat::Nv12`
+/// also accepts `ColorFormat::Rgba8`.
+const VIDEO_ENCODER_COLOR_FORMAT: ColorFormat = ColorFormat::Bgra8;
+
 unsafe impl Send for VideoEncoderError {}
 unsafe impl Sync for VideoEncoderError {}
 
+/// Snaps a timespan to its `1 / fps` slot and returns it together with the slots since the
+/// previous frame that are filled with duplicates, at most `fps` of them so a long gap doesn't
+/// flood the encoder. Returns `None` if the frame falls in the slot of the previous frame.
+fn constant_frame_rate_slots(
+    last_index: Option<i64>,
+    timespan: i64,
+    fps: i64,
+) -> Option<(i64, Range<i64>)> {
+    let index = (timespan * fps + 5_000_000) / 10_000_000;
+
+    match last_index {
+        None => Some((index, index..index)),
+        Some(last) if index > last => Some((index, (last + 1).max(index - fps)..index)),
+        Some(_) => None,
+    }
+}
+
+/// Checks that frames of a color format can be encoded without producing wrong colors.
+///
+/// The uncompressed BGRA input of the encoder has no RGBA counterpart in Media Foundation,
+/// so `Rgba8` frames are only accepted when the video processor converts them to NV12.
+const fn check_frame_color_format(
+    intermediate_format: IntermediateFormat,
+    color_format: ColorFormat,
+) -> Result<(), VideoEncoderError> {
+    match (intermediate_format, color_format) {
+        (_, ColorFormat::Bgra8) | (IntermediateFormat::Nv12, ColorFormat::Rgba8) => Ok(()),
+        _ => Err(VideoEncoderError::ColorFormatMismatch(
+            color_format,
+            VIDEO_ENCODER_COLOR_FORMAT,
+        )),
+    }
+}
+
+/// Computes the average bitrate of an output from its size in bytes and its duration in 100
+/// nanosecond units, `None` if the duration is not positive.
+fn achieved_bitrate(size: u64, duration: i64) -> Option<u32> {
+    if duration <= 0 {
+        return None;
+    }
+
+    let bitrate = u128::from(size) * 8 * 10_000_000 / u128::from(duration.unsigned_abs());
+
+    Some(u32::try_from(bitrate).unwrap_or(u32::MAX))
+}
+
+/// Maps QPC timespans onto the timeline of the video, which starts at the epoch of the frames or
+/// else at the first sample, and excludes the time the encoder was paused.
+struct VideoTimeline {
+    first_timespan: Option<i64>,
+    frame_interval: i64,
+    is_paused: bool,
+    is_resuming: bool,
+    paused_duration: i64,
+    last_timespan: i64,
+}
+
+impl VideoTimeline {
+    const fn new(frame_interval: i64) -> Self {
+        Self {
+            first_timespan: None,
+            frame_interval,
+            is_paused: false,
+            is_resuming: false,
+            paused_duration: 0,
+            last_timespan: 0,
+        }
+    }
+
+    /// Starts the timeline at an epoch in the QPC timebase, unless a sample was already placed
+    /// on it.
+    const fn set_epoch(&mut self, epoch: i64) {
+        if self.first_timespan.is_none() {
+            self.first_timespan = Some(epoch);
+        }
+    }
+
+    /// Converts a QPC timespan into the timeline of the video, samples from before the start of
+    /// the timeline are placed at its start.
+    fn timespan(&mut self, timespan: i64) -> i64 {
+        let first_timespan = *self.first_timespan.get_or_insert(timespan);
+        let elapsed = (timespan - first_timespan).max(0);
+
+        // Continue one frame after the last sample sent before the pause
+        if self.is_resuming {
+            self.is_resuming = false;
+            self.paused_duration =
+                (elapsed - self.last_timespan - self.frame_interval).max(self.paused_duration);
+        }
+
+        let duration = elapsed - self.paused_duration;
+        self.last_timespan = self.last_timespan.max(duration);
+
+        duration
+    }
+
+    const fn pause(&mut self) {
+        self.is_paused = true;
+    }
+
+    const fn resume(&mut self) {
+        if self.is_paused {
+            self.is_paused = false;
+            self.is_resuming = true;
+        }
+    }
+}
+
+/// Statistics of an output finished with `VideoEncoder::finish_with_stats`.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Default)]
+pub struct VideoEncoderStats {
+    /// The size of the output in bytes.
+    pub output_size: u64,
+    /// The duration of the encoded samples, without the time the encoder was paused.
+    pub duration: TimeSpan,
+    /// The average bitrate of the output in bits per second, including the audio and the
+    /// container overhead, `None` if no time was encoded.
+    pub achieved_bitrate: Option<u32>,
+}
+
 /// The `VideoEncoderSource` struct represents all the types that can be send to the encoder.
 pub enum VideoEncoderSource {
     DirectX(SendDirectX<IDirect3DSurface>),
@@ -182,6 +539,11 @@ pub struct VideoSettingsBuilder {
     height: u32,
     frame_rate: u32,
     pixel_aspect_ratio: (u32, u32),
+    keyframe_interval: Option<u32>,
+    constant_frame_rate: bool,
+    intermediate_format: IntermediateFormat,
+    grayscale: bool,
+    crop: Option<CaptureRegion>,
     disabled: bool,
 }
 
@@ -194,6 +556,11 @@ impl VideoSettingsBuilder {
             sub_type: VideoSettingsSubType::HEVC,
             width,
             height,
+            keyframe_interval: None,
+            constant_frame_rate: false,
+            intermediate_format: IntermediateFormat::Bgra8,
+            grayscale: false,
+            crop: None,
             disabled: false,
         }
     }
@@ -233,6 +600,89 @@ impl VideoSettingsBuilder {
         self
     }
 
+    /// Set the maximum number of frames between keyframes, by default the encoder chooses it.
+    ///
+    /// The interval is counted in frames, so the time between keyframes is
+    /// `keyframe_interval / frame_rate` seconds. It must be at least 1, otherwise building the
+    /// encoder fails with `VideoEncoderError::InvalidKeyframeInterval`.
+    pub const fn keyframe_interval(mut self, keyframe_interval: u32) -> Self {
+        self.keyframe_interval = Some(keyframe_interval);
+        self
+    }
+
+    /// Snap `send_frame` and `send_frame_with_audio` timestamps to exact `1 / frame_rate`
+    /// boundaries and duplicate the last frame to fill the gaps between frames, producing a
+    /// constant frame rate video.
+    ///
+    /// Every synthetic frame goes through the encoder like a real one, so a mostly static
+    /// capture is encoded at the full frame rate. Each frame is also copied to a GPU texture to
+    /// keep it around for duplication, and frames arriving faster than the frame rate are
+    /// dropped. At most one second of duplicates is sent for a gap, after longer gaps the last
+    /// frame is simply shown until the duplicates start. `send_frame_buffer` returns
+    /// `VideoEncoderError::ConstantFrameRateUnsupported` with a constant frame rate.
+    pub const fn constant_frame_rate(mut self, constant_frame_rate: bool) -> Self {
+        self.constant_frame_rate = constant_frame_rate;
+        self
+    }
+
+    /// Set the uncompressed format frames are handed to the encoder in, by default
+    /// `IntermediateFormat::Bgra8`.
+    ///
+    /// With `IntermediateFormat::Nv12` frames are converted once on the GPU with the Direct3D 11
+    /// video processor, so hardware encoders that only accept NV12 skip their internal color
+    /// conversion, and frames captured in `ColorFormat::Rgba8` are accepted as well. The width
+    /// and height must be even. `send_frame_buffer` buffers must be in the
+    /// same format.
+    pub const fn intermediate_format(mut self, intermediate_format: IntermediateFormat) -> Self {
+        self.intermediate_format = intermediate_format;
+        self
+    }
+
+    /// Encode the video in grayscale, by default `false`.
+    ///
+    /// The hardware encoders of Media Foundation only take color input, so uncompressed
+    /// `VideoSettingsSubType::L8` frames can't be encoded. Instead the frames go through the
+    /// `IntermediateFormat::Nv12` conversion with the chroma plane cleared to its neutral value,
+    /// which leaves only the luma and lets the encoder spend almost no bits on color. This
+    /// implies `IntermediateFormat::Nv12` and its even width and height.
+    pub const fn grayscale(mut self, grayscale: bool) -> Self {
+        self.grayscale = grayscale;
+        self
+    }
+
+    /// Crop every frame to a region before it is encoded, by default frames are not cropped.
+    ///
+    /// The region is relative to the top-left corner of the frame and is clipped to it, the
+    /// cropped frame is then scaled to the video size. Cropping, scaling and the color
+    /// conversion run together in a single Direct3D 11 video processor pass, so this implies
+    /// `IntermediateFormat::Nv12` and its even width and height. NV12 has no alpha channel, so
+    /// the alpha of the frames is ignored.
+    ///
+    /// An empty region fails building the encoder with `VideoEncoderError::InvalidCrop`, and a
+    /// region that lies entirely outside of a frame fails sending it with
+    /// `VideoEncoderError::DirectXError`. Only frames are cropped, buffers passed to
+    /// `send_frame_buffer` are encoded as is.
+    pub const fn crop(mut self, crop: CaptureRegion) -> Self {
+        self.crop = Some(crop);
+        self
+    }
+
+    const fn effective_intermediate_format(&self) -> IntermediateFormat {
+        if self.grayscale || self.crop.is_some() {
+            IntermediateFormat::Nv12
+        } else {
+            self.intermediate_format
+        }
+    }
+
+    const fn constant_frame_rate_fps(&self) -> Option<u32> {
+        if self.constant_frame_rate && self.frame_rate != 0 {
+            Some(self.frame_rate)
+        } else {
+            None
+        }
+    }
+
     fn build(self) -> Result<(VideoEncodingProperties, bool), VideoEncoderError> {
         let properties = VideoEncodingProperties::new()?;
 
@@ -249,6 +699,30 @@ impl VideoSettingsBuilder {
             .PixelAspectRatio()?
             .SetDenominator(self.pixel_aspect_ratio.1)?;
 
+        if let Some(keyframe_interval) = self.keyframe_interval {
+            if keyframe_interval < 1 {
+                return Err(VideoEncoderError::InvalidKeyframeInterval);
+            }
+
+            properties.Properties()?.Insert(
+                MF_MT_MAX_KEYFRAME_SPACING,
+                &PropertyValue::CreateUInt32(keyframe_interval)?,
+            )?;
+        }
+
+        if self.effective_intermediate_format() == IntermediateFormat::Nv12
+            && (self.width % 2 != 0 || self.height % 2 != 0)
+        {
+            return Err(VideoEncoderError::InvalidIntermediateSize);
+        }
+
+        if self
+            .crop
+            .is_some_and(|crop| crop.width == 0 || crop.height == 0)
+        {
+            return Err(VideoEncoderError::InvalidCrop);
+        }
+
         Ok((properties, self.disabled))
     }
 }
@@ -257,6 +731,7 @@ impl VideoSettingsBuilder {
 pub struct AudioSettingsBuilder {
     bitrate: u32,
     channel_count: u32,
+    channel_layout: Option<ChannelLayout>,
     sample_rate: u32,
     bit_per_sample: u32,
     sub_type: AudioSettingsSubType,
@@ -268,6 +743,7 @@ impl AudioSettingsBuilder {
         Self {
             bitrate: 192000,
             channel_count: 2,
+            channel_layout: None,
             sample_rate: 48000,
             bit_per_sample: 16,
             sub_type: AudioSettingsSubType::AAC,
@@ -284,6 +760,14 @@ impl AudioSettingsBuilder {
         self
     }
 
+    /// Sets the speaker layout of the audio, this also sets the channel count and overrides
+    /// `channel_count`.
+    pub const fn channel_layout(mut self, channel_layout: ChannelLayout) -> Self {
+        self.channel_count = channel_layout.channel_count();
+        self.channel_layout = Some(channel_layout);
+        self
+    }
+
     pub const fn sample_rate(mut self, sample_rate: u32) -> Self {
         self.sample_rate = sample_rate;
         self
@@ -307,7 +791,16 @@ impl AudioSettingsBuilder {
     fn build(self) -> Result<(AudioEncodingProperties, bool), VideoEncoderError> {
         let properties = AudioEncodingProperties::new()?;
         properties.SetBitrate(self.bitrate)?;
-        properties.SetChannelCount(self.channel_count)?;
+        match self.channel_layout {
+            Some(channel_layout) => {
+                properties.SetChannelCount(channel_layout.channel_count())?;
+                properties.Properties()?.Insert(
+                    MF_MT_AUDIO_CHANNEL_MASK,
+                    &PropertyValue::CreateUInt32(channel_layout.channel_mask())?,
+                )?;
+            }
+            None => properties.SetChannelCount(self.channel_count)?,
+        }
         properties.SetSampleRate(self.sample_rate)?;
         properties.SetBitsPerSample(self.bit_per_sample)?;
         properties.SetSubtype(&self.sub_type.to_hstring())?;
@@ -410,6 +903,23 @@ impl VideoSettingsSubType {
     }
 }
 
+/// The `IntermediateFormat` enum represents the uncompressed formats frames are handed to the
+/// video encoder in.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum IntermediateFormat {
+    Bgra8,
+    Nv12,
+}
+
+impl IntermediateFormat {
+    pub fn to_hstring(&self) -> Result<HSTRING, windows::core::Error> {
+        match self {
+            Self::Bgra8 => MediaEncodingSubtypes::Bgra8(),
+            Self::Nv12 => MediaEncodingSubtypes::Nv12(),
+        }
+    }
+}
+
 /// The `AudioSettingsSubType` enum represents the subtypes for the audio encoder.
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
 pub enum AudioSettingsSubType {
@@ -466,6 +976,53 @@ impl AudioSettingsSubType {
     }
 }
 
+/// The Media Foundation attribute holding the WAVEFORMATEXTENSIBLE channel mask of an audio stream.
+const MF_MT_AUDIO_CHANNEL_MASK: GUID = GUID::from_u128(0x55fb5765_644a_4caf_8479_938983bb1588);
+
+/// The Media Foundation attribute holding the maximum number of frames between keyframes.
+const MF_MT_MAX_KEYFRAME_SPACING: GUID = GUID::from_u128(0xc16eb52b_73a1_476f_8d62_839d6a020652);
+
+/// The `ChannelLayout` enum represents the speaker layouts for the audio encoder.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum ChannelLayout {
+    Mono,
+    Stereo,
+    Quad,
+    Surround5_1,
+    Surround7_1,
+}
+
+impl ChannelLayout {
+    /// Returns the number of channels of the layout.
+    #[must_use]
+    pub const fn channel_count(&self) -> u32 {
+        match self {
+            Self::Mono => 1,
+            Self::Stereo => 2,
+            Self::Quad => 4,
+            Self::Surround5_1 => 6,
+            Self::Surround7_1 => 8,
+        }
+    }
+
+    /// Returns the WAVEFORMATEXTENSIBLE channel mask of the layout.
+    #[must_use]
+    pub const fn channel_mask(&self) -> u32 {
+        match self {
+            // FC
+            Self::Mono => 0x4,
+            // FL FR
+            Self::Stereo => 0x3,
+            // FL FR BL BR
+            Self::Quad => 0x33,
+            // FL FR FC LFE BL BR
+            Self::Surround5_1 => 0x3F,
+            // FL FR FC LFE BL BR SL SR
+            Self::Surround7_1 => 0x63F,
+        }
+    }
+}
+
 /// The `Subtype` enum represents the subtypes for the video encoder.
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
 pub enum ContainerSettingsSubType {
@@ -501,8 +1058,16 @@ impl ContainerSettingsSubType {
 }
 
 /// The `VideoEncoder` struct represents a video encoder that can be used to encode video frames and save them to a specified file path.
+///
+/// # Backpressure
+///
+/// Samples are not queued, every `send_*` call hands its sample to the transcoder and blocks
+/// until the transcoder consumed it, so at most one video and one audio sample are buffered and
+/// memory doesn't grow when the transcoder falls behind (e.g. on slow storage). Instead the
+/// calls take longer, which in a frame handler means the capture delivers fewer frames. Callers
+/// that must not block should encode on their own thread and drop frames in front of it, for
+/// example with `SingleBufferSink`.
 pub struct VideoEncoder {
-    first_timespan: Option<TimeSpan>,
     frame_sender: mpsc::Sender<Option<(VideoEncoderSource, TimeSpan)>>,
     audio_sender: mpsc::Sender<Option<(AudioEncoderSource, TimeSpan)>>,
     sample_requested: EventRegistrationToken,
@@ -514,6 +1079,16 @@ pub struct VideoEncoder {
     error_notify: Arc<AtomicBool>,
     is_video_disabled: bool,
     is_audio_disabled: bool,
+    constant_frame_rate: Option<u32>,
+    last_frame_index: Option<i64>,
+    last_frame_surface: Option<SendDirectX<IDirect3DSurface>>,
+    intermediate_format: IntermediateFormat,
+    grayscale: bool,
+    crop: Option<CaptureRegion>,
+    video_size: (u32, u32),
+    timeline: VideoTimeline,
+    nv12_converter: Option<Nv12Converter>,
+    output_stream: IRandomAccessStream,
 }
 
 impl VideoEncoder {
@@ -541,15 +1116,27 @@ impl VideoEncoder {
         let path = path.as_ref();
         let media_encoding_profile = MediaEncodingProfile::new()?;
 
+        let constant_frame_rate = video_settings.constant_frame_rate_fps();
+        let intermediate_format = video_settings.effective_intermediate_format();
+        let grayscale = video_settings.grayscale;
+        let crop = video_settings.crop;
         let (video_encoding_properties, is_video_disabled) = video_settings.build()?;
         media_encoding_profile.SetVideo(&video_encoding_properties)?;
+        let video_size = (
+            video_encoding_properties.Width()?,
+            video_encoding_properties.Height()?,
+        );
+        let frame_interval = match video_encoding_properties.FrameRate()?.Numerator()? {
+            0 => 0,
+            frame_rate => 10_000_000 / i64::from(frame_rate),
+        };
         let (audio_encoding_properties, is_audio_disabled) = audio_settings.build()?;
         media_encoding_profile.SetAudio(&audio_encoding_properties)?;
         let container_encoding_properties = container_settings.build()?;
         media_encoding_profile.SetContainer(&container_encoding_properties)?;
 
         let video_encoding_properties = VideoEncodingProperties::CreateUncompressed(
-            &MediaEncodingSubtypes::Bgra8()?,
+            &intermediate_format.to_hstring()?,
             video_encoding_properties.Width()?,
             video_encoding_properties.Height()?,
         )?;
@@ -694,12 +1281,9 @@ impl VideoEncoder {
         media_transcoder.SetHardwareAccelerationEnabled(true)?;
 
         File::create(path)?;
-        let path = fs::canonicalize(path).unwrap().to_string_lossy()[4..].to_string();
-        let path = Path::new(&path);
-
-        let path = &HSTRING::from(path.as_os_str().to_os_string());
+        let path = Self::storage_file_path(&fs::canonicalize(path)?)?;
 
-        let file = StorageFile::GetFileFromPathAsync(path)?.get()?;
+        let file = StorageFile::GetFileFromPathAsync(&path)?.get()?;
         let media_stream_output = file.OpenAsync(FileAccessMode::ReadWrite)?.get()?;
 
         let transcode = media_transcoder
@@ -713,24 +1297,33 @@ impl VideoEncoder {
         let error_notify = Arc::new(AtomicBool::new(false));
         let transcode_thread = thread::spawn({
             let error_notify = error_notify.clone();
+            let frame_notify = frame_notify.clone();
+            let audio_notify = audio_notify.clone();
 
             move || -> Result<(), VideoEncoderError> {
-                let result = transcode.TranscodeAsync();
+                let result = transcode
+                    .TranscodeAsync()
+                    .and_then(|transcode_operation| transcode_operation.get());
+
+                drop(media_transcoder);
 
                 if result.is_err() {
+                    // Wake up the senders waiting for a sample request that will never come
                     error_notify.store(true, atomic::Ordering::Relaxed);
+                    for notify in [&frame_notify, &audio_notify] {
+                        let (lock, cvar) = &**notify;
+                        let _processed = lock.lock();
+                        cvar.notify_all();
+                    }
                 }
 
-                result?.get()?;
-
-                drop(media_transcoder);
+                result?;
 
                 Ok(())
             }
         });
 
         Ok(Self {
-            first_timespan: None,
             frame_sender,
             audio_sender,
             sample_requested,
@@ -742,9 +1335,42 @@ impl VideoEncoder {
             error_notify,
             is_video_disabled,
             is_audio_disabled,
+            constant_frame_rate,
+            last_frame_index: None,
+            last_frame_surface: None,
+            intermediate_format,
+            grayscale,
+            crop,
+            video_size,
+            timeline: VideoTimeline::new(frame_interval),
+            nv12_converter: None,
+            output_stream: media_stream_output,
         })
     }
 
+    /// Converts a canonicalized path into a path `StorageFile` accepts.
+    ///
+    /// `fs::canonicalize` returns verbatim paths (`\\?\C:\...` or `\\?\UNC\...`) which
+    /// `StorageFile` rejects, so the prefix is stripped from the UTF-16 representation of the
+    /// path. Working on wide characters keeps non-ASCII paths intact.
+    fn storage_file_path(path: &Path) -> Result<HSTRING, VideoEncoderError> {
+        const VERBATIM_PREFIX: [u16; 4] = [b'\\' as u16, b'\\' as u16, b'?' as u16, b'\\' as u16];
+        const UNC_PREFIX: [u16; 4] = [b'U' as u16, b'N' as u16, b'C' as u16, b'\\' as u16];
+
+        let wide = path.as_os_str().encode_wide().collect::<Vec<u16>>();
+
+        let Some(rest) = wide.strip_prefix(VERBATIM_PREFIX.as_slice()) else {
+            return Ok(HSTRING::from_wide(&wide)?);
+        };
+
+        let wide = rest.strip_prefix(UNC_PREFIX.as_slice()).map_or_else(
+            || rest.to_vec(),
+            |unc| [&VERBATIM_PREFIX[..2], unc].concat(),
+        );
+
+        Ok(HSTRING::from_wide(&wide)?)
+    }
+
     /// Creates a new `VideoEncoder` instance with the specified parameters.
     ///
     /// # Arguments
@@ -768,15 +1394,27 @@ impl VideoEncoder {
     ) -> Result<Self, VideoEncoderError> {
         let media_encoding_profile = MediaEncodingProfile::new()?;
 
+        let constant_frame_rate = video_settings.constant_frame_rate_fps();
+        let intermediate_format = video_settings.effective_intermediate_format();
+        let grayscale = video_settings.grayscale;
+        let crop = video_settings.crop;
         let (video_encoding_properties, is_video_disabled) = video_settings.build()?;
         media_encoding_profile.SetVideo(&video_encoding_properties)?;
+        let video_size = (
+            video_encoding_properties.Width()?,
+            video_encoding_properties.Height()?,
+        );
+        let frame_interval = match video_encoding_properties.FrameRate()?.Numerator()? {
+            0 => 0,
+            frame_rate => 10_000_000 / i64::from(frame_rate),
+        };
         let (audio_encoding_properties, is_audio_disabled) = audio_settings.build()?;
         media_encoding_profile.SetAudio(&audio_encoding_properties)?;
         let container_encoding_properties = container_settings.build()?;
         media_encoding_profile.SetContainer(&container_encoding_properties)?;
 
         let video_encoding_properties = VideoEncodingProperties::CreateUncompressed(
-            &MediaEncodingSubtypes::Bgra8()?,
+            &intermediate_format.to_hstring()?,
             video_encoding_properties.Width()?,
             video_encoding_properties.Height()?,
         )?;
@@ -931,24 +1569,33 @@ impl VideoEncoder {
         let error_notify = Arc::new(AtomicBool::new(false));
         let transcode_thread = thread::spawn({
             let error_notify = error_notify.clone();
+            let frame_notify = frame_notify.clone();
+            let audio_notify = audio_notify.clone();
 
             move || -> Result<(), VideoEncoderError> {
-                let result = transcode.TranscodeAsync();
+                let result = transcode
+                    .TranscodeAsync()
+                    .and_then(|transcode_operation| transcode_operation.get());
+
+                drop(media_transcoder);
 
                 if result.is_err() {
+                    // Wake up the senders waiting for a sample request that will never come
                     error_notify.store(true, atomic::Ordering::Relaxed);
+                    for notify in [&frame_notify, &audio_notify] {
+                        let (lock, cvar) = &**notify;
+                        let _processed = lock.lock();
+                        cvar.notify_all();
+                    }
                 }
 
-                result?.get()?;
-
-                drop(media_transcoder);
+                result?;
 
                 Ok(())
             }
         });
 
         Ok(Self {
-            first_timespan: None,
             frame_sender,
             audio_sender,
             sample_requested,
@@ -960,9 +1607,99 @@ impl VideoEncoder {
             error_notify,
             is_video_disabled,
             is_audio_disabled,
+            constant_frame_rate,
+            last_frame_index: None,
+            last_frame_surface: None,
+            intermediate_format,
+            grayscale,
+            crop,
+            video_size,
+            timeline: VideoTimeline::new(frame_interval),
+            nv12_converter: None,
+            output_stream: stream,
         })
     }
 
+    /// Creates a new `VideoEncoder` instance that writes the encoded video to a `Write` sink.
+    ///
+    /// The video is encoded into an in-memory stream that is copied to the writer on the
+    /// transcode thread once encoding is done. MP4 files are only complete after the transcoder
+    /// writes the `moov` atom at the end, so the writer receives nothing until `finish` and the
+    /// whole video is kept in memory until then.
+    ///
+    /// # Arguments
+    ///
+    /// * `video_settings` - The settings for the video encoder.
+    /// * `audio_settings` - The settings for the audio encoder.
+    /// * `container_settings` - The settings for the container.
+    /// * `writer` - The writer the encoded video is written to, for example `std::io::stdout()`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `VideoEncoder` instance if successful, or a
+    /// `VideoEncoderError` if an error occurs.
+    #[inline]
+    pub fn new_to_writer<W: Write + Send + 'static>(
+        video_settings: VideoSettingsBuilder,
+        audio_settings: AudioSettingsBuilder,
+        container_settings: ContainerSettingsBuilder,
+        mut writer: W,
+    ) -> Result<Self, VideoEncoderError> {
+        let stream = InMemoryRandomAccessStream::new()?;
+
+        let mut encoder = Self::new_from_stream(
+            video_settings,
+            audio_settings,
+            container_settings,
+            stream.cast()?,
+        )?;
+
+        if let Some(transcode_thread) = encoder.transcode_thread.take() {
+            encoder.transcode_thread =
+                Some(thread::spawn(move || -> Result<(), VideoEncoderError> {
+                    transcode_thread
+                        .join()
+                        .map_err(|_| VideoEncoderError::TranscodeThreadPanicked)??;
+
+                    Self::copy_stream_to_writer(&stream, &mut writer)
+                }));
+        }
+
+        Ok(encoder)
+    }
+
+    /// Copies an in-memory stream from the start to a writer and flushes it.
+    fn copy_stream_to_writer<W: Write>(
+        stream: &InMemoryRandomAccessStream,
+        writer: &mut W,
+    ) -> Result<(), VideoEncoderError> {
+        stream.Seek(0)?;
+        let mut remaining = stream.Size()?;
+
+        let buffer = Buffer::Create(ENCODE_CHUNK_SIZE)?;
+        let mut bytes = vec![0u8; ENCODE_CHUNK_SIZE as usize];
+        while remaining > 0 {
+            let chunk = stream
+                .ReadAsync(&buffer, buffer.Capacity()?, InputStreamOptions::None)?
+                .get()?;
+
+            let length = chunk.Length()?;
+            if length == 0 {
+                break;
+            }
+
+            let data_reader = DataReader::FromBuffer(&chunk)?;
+            data_reader.ReadBytes(&mut bytes[..length as usize])?;
+            writer.write_all(&bytes[..length as usize])?;
+
+            remaining -= u64::from(length);
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
     /// Sends a video frame to the video encoder for encoding.
     ///
     /// # Arguments
@@ -972,46 +1709,189 @@ impl VideoEncoder {
     /// # Returns
     ///
     /// Returns `Ok(())` if the frame is successfully sent for encoding, or a `VideoEncoderError`
-    /// if an error occurs.
+    /// if an error occurs. Frames must be captured in `ColorFormat::Bgra8`, or in
+    /// `ColorFormat::Rgba8` with `IntermediateFormat::Nv12`, other color formats return
+    /// `VideoEncoderError::ColorFormatMismatch`. With a constant frame rate the frame may
+    /// be preceded by duplicates of the previous frame or dropped, see
+    /// `VideoSettingsBuilder::constant_frame_rate`.
     #[inline]
     pub fn send_frame(&mut self, frame: &mut Frame) -> Result<(), VideoEncoderError> {
         if self.is_video_disabled {
             return Err(VideoEncoderError::VideoDisabled);
         }
 
-        let timespan = match self.first_timespan {
-            Some(timespan) => TimeSpan {
-                Duration: frame.timespan().Duration - timespan.Duration,
-            },
-            None => {
-                let timespan = frame.timespan();
-                self.first_timespan = Some(timespan);
-                TimeSpan { Duration: 0 }
+        check_frame_color_format(self.intermediate_format, frame.color_format())?;
+
+        if self.timeline.is_paused {
+            return Ok(());
+        }
+
+        let timespan = self.frame_timespan(frame);
+
+        let surface = self.intermediate_surface(frame)?;
+
+        if let Some(fps) = self.constant_frame_rate {
+            return self.send_constant_frame_rate_frame(frame, surface, timespan, fps);
+        }
+
+        self.send_video_source(
+            VideoEncoderSource::DirectX(SendDirectX::new(surface)),
+            timespan,
+        )
+    }
+
+    /// Sends a frame snapped to the closest `1 / fps` boundary, preceded by duplicates of the
+    /// previous frame for every boundary that was skipped since it.
+    fn send_constant_frame_rate_frame(
+        &mut self,
+        frame: &Frame,
+        surface: IDirect3DSurface,
+        timespan: TimeSpan,
+        fps: u32,
+    ) -> Result<(), VideoEncoderError> {
+        let fps = i64::from(fps);
+        let index_timespan = |index: i64| TimeSpan {
+            Duration: index * 10_000_000 / fps,
+        };
+
+        if let Some((index, duplicates)) =
+            constant_frame_rate_slots(self.last_frame_index, timespan.Duration, fps)
+        {
+            if let Some(last_surface) = self.last_frame_surface.take() {
+                for duplicate_index in duplicates {
+                    self.send_video_source(
+                        VideoEncoderSource::DirectX(SendDirectX::new(last_surface.0.clone())),
+                        index_timespan(duplicate_index),
+                    )?;
+                }
             }
+
+            self.send_video_source(
+                VideoEncoderSource::DirectX(SendDirectX::new(surface)),
+                index_timespan(index),
+            )?;
+            self.last_frame_index = Some(index);
+        }
+
+        // The frame pool reuses the frame surface, keep a copy of the latest frame so the gaps
+        // are filled with its content
+        let texture = match &self.nv12_converter {
+            Some(nv12_converter) => nv12_converter.copy_output(frame.context())?,
+            None => frame.to_owned_gpu()?.into_texture(),
         };
+        let dxgi_surface = texture.cast::<IDXGISurface>()?;
+        let surface = unsafe {
+            CreateDirect3D11SurfaceFromDXGISurface(&dxgi_surface)?.cast::<IDirect3DSurface>()?
+        };
+        self.last_frame_surface = Some(SendDirectX::new(surface));
 
-        self.frame_sender.send(Some((
-            VideoEncoderSource::DirectX(SendDirectX::new(unsafe {
-                frame.as_raw_surface().clone()
-            })),
-            timespan,
-        )))?;
+        Ok(())
+    }
 
-        let (lock, cvar) = &*self.frame_notify;
+    /// Waits until the encoder consumed the last sent sample or the transcoder stopped.
+    fn wait_until_processed(notify: &(Mutex<bool>, Condvar), error_notify: &AtomicBool) {
+        let (lock, cvar) = notify;
         let mut processed = lock.lock();
-        if !*processed {
+        while !*processed && !error_notify.load(atomic::Ordering::Relaxed) {
             cvar.wait(&mut processed);
         }
         *processed = false;
-        drop(processed);
+    }
 
-        if self.error_notify.load(atomic::Ordering::Relaxed) {
-            if let Some(transcode_thread) = self.transcode_thread.take() {
+    /// Returns the error the transcoder stopped with, or `Ok(())` if it is still running.
+    ///
+    /// The error is returned once, later calls return `VideoEncoderError::TranscodeStopped`.
+    fn transcode_error(&mut self) -> Result<(), VideoEncoderError> {
+        if !self.error_notify.load(atomic::Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        match self.transcode_thread.take() {
+            Some(transcode_thread) => {
                 transcode_thread
                     .join()
-                    .expect("Failed to join transcode thread")?;
+                    .map_err(|_| VideoEncoderError::TranscodeThreadPanicked)??;
+
+                Err(VideoEncoderError::TranscodeStopped)
             }
+            None => Err(VideoEncoderError::TranscodeStopped),
         }
+    }
+
+    /// Converts a QPC timespan into the timeline of the video, see `VideoTimeline`.
+    fn video_timespan(&mut self, timespan: i64) -> TimeSpan {
+        TimeSpan {
+            Duration: self.timeline.timespan(timespan),
+        }
+    }
+
+    /// Converts the time of a frame into the timeline of the video, which starts at the epoch
+    /// of the frame unless it was set with `set_epoch_qpc` or samples were sent before.
+    fn frame_timespan(&mut self, frame: &Frame) -> TimeSpan {
+        if let Some(epoch) = frame.epoch() {
+            self.timeline.set_epoch(epoch.Duration);
+        }
+
+        self.video_timespan(frame.system_relative_time().Duration)
+    }
+
+    /// Starts the video timeline at an epoch instead of the first sample.
+    ///
+    /// Frames captured with `EpochMode::FirstFrame` or `EpochMode::SessionStart` set the epoch on
+    /// their own, so this is only needed to place samples sent before the first frame, or the
+    /// samples of several encoders, on the same timeline, for example with
+    /// `CaptureControl::epoch_qpc`. The epoch can't be moved once a sample was sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `epoch` - The epoch in 100 nanosecond units of the QPC timebase.
+    #[inline]
+    pub const fn set_epoch_qpc(&mut self, epoch: TimeSpan) {
+        self.timeline.set_epoch(epoch.Duration);
+    }
+
+    /// Gets the surface of a frame in the intermediate format, converting it on the GPU if needed.
+    fn intermediate_surface(
+        &mut self,
+        frame: &Frame,
+    ) -> Result<IDirect3DSurface, VideoEncoderError> {
+        if self.intermediate_format == IntermediateFormat::Bgra8 {
+            return Ok(unsafe { frame.as_raw_surface().clone() });
+        }
+
+        let nv12_converter = match &mut self.nv12_converter {
+            Some(nv12_converter) => nv12_converter,
+            None => self.nv12_converter.insert(Nv12Converter::new(
+                frame.d3d_device(),
+                frame.context(),
+                frame.width(),
+                frame.height(),
+                self.video_size.0,
+                self.video_size.1,
+                self.grayscale,
+                self.crop,
+            )?),
+        };
+
+        let texture = nv12_converter.convert(unsafe { frame.as_raw_texture() })?;
+        let dxgi_surface = texture.cast::<IDXGISurface>()?;
+        let surface = unsafe {
+            CreateDirect3D11SurfaceFromDXGISurface(&dxgi_surface)?.cast::<IDirect3DSurface>()?
+        };
+
+        Ok(surface)
+    }
+
+    /// Sends a video source to the encoder and waits for it to be consumed.
+    fn send_video_source(
+        &mut self,
+        source: VideoEncoderSource,
+        timespan: TimeSpan,
+    ) -> Result<(), VideoEncoderError> {
+        self.frame_sender.send(Some((source, timespan)))?;
+
+        Self::wait_until_processed(&self.frame_notify, &self.error_notify);
+        self.transcode_error()?;
 
         Ok(())
     }
@@ -1041,38 +1921,21 @@ impl VideoEncoder {
             return Err(VideoEncoderError::AudioDisabled);
         }
 
-        let timespan = match self.first_timespan {
-            Some(timespan) => TimeSpan {
-                Duration: frame.timespan().Duration - timespan.Duration,
-            },
-            None => {
-                let timespan = frame.timespan();
-                self.first_timespan = Some(timespan);
-                TimeSpan { Duration: 0 }
-            }
-        };
-
-        self.frame_sender.send(Some((
-            VideoEncoderSource::DirectX(SendDirectX::new(unsafe {
-                frame.as_raw_surface().clone()
-            })),
-            timespan,
-        )))?;
+        check_frame_color_format(self.intermediate_format, frame.color_format())?;
 
-        let (lock, cvar) = &*self.frame_notify;
-        let mut processed = lock.lock();
-        if !*processed {
-            cvar.wait(&mut processed);
+        if self.timeline.is_paused {
+            return Ok(());
         }
-        *processed = false;
-        drop(processed);
 
-        if self.error_notify.load(atomic::Ordering::Relaxed) {
-            if let Some(transcode_thread) = self.transcode_thread.take() {
-                transcode_thread
-                    .join()
-                    .expect("Failed to join transcode thread")?;
-            }
+        let timespan = self.frame_timespan(frame);
+
+        let surface = self.intermediate_surface(frame)?;
+        match self.constant_frame_rate {
+            Some(fps) => self.send_constant_frame_rate_frame(frame, surface, timespan, fps)?,
+            None => self.send_video_source(
+                VideoEncoderSource::DirectX(SendDirectX::new(surface)),
+                timespan,
+            )?,
         }
 
         self.audio_sender.send(Some((
@@ -1083,21 +1946,8 @@ impl VideoEncoder {
             timespan,
         )))?;
 
-        let (lock, cvar) = &*self.audio_notify;
-        let mut processed = lock.lock();
-        if !*processed {
-            cvar.wait(&mut processed);
-        }
-        *processed = false;
-        drop(processed);
-
-        if self.error_notify.load(atomic::Ordering::Relaxed) {
-            if let Some(transcode_thread) = self.transcode_thread.take() {
-                transcode_thread
-                    .join()
-                    .expect("Failed to join transcode thread")?;
-            }
-        }
+        Self::wait_until_processed(&self.audio_notify, &self.error_notify);
+        self.transcode_error()?;
 
         Ok(())
     }
@@ -1106,13 +1956,16 @@ impl VideoEncoder {
     ///
     /// # Arguments
     ///
-    /// * `buffer` - A reference to the frame byte slice to be encoded Windows API expect this to be Bgra and bottom-top.
-    /// * `timespan` - The timespan that correlates to the frame buffer.
+    /// * `buffer` - A reference to the frame byte slice to be encoded Windows API expect this to be Bgra and bottom-top, or NV12 with `IntermediateFormat::Nv12`.
+    /// * `timespan` - The time of the frame in 100 nanosecond units of the QPC timebase, like
+    ///   `Frame::system_relative_time`, the epoch of the video timeline is subtracted from it.
     ///
     /// # Returns
     ///
     /// Returns `Ok(())` if the frame is successfully sent for encoding, or a `VideoEncoderError`
-    /// if an error occurs.
+    /// if an error occurs. Frame buffers can't be duplicated to fill gaps, so with a constant
+    /// frame rate `VideoEncoderError::ConstantFrameRateUnsupported` is returned. The buffer is
+    /// encoded as is, `VideoSettingsBuilder::crop` and `grayscale` don't apply to it.
     #[inline]
     pub fn send_frame_buffer(
         &mut self,
@@ -1123,38 +1976,23 @@ impl VideoEncoder {
             return Err(VideoEncoderError::VideoDisabled);
         }
 
-        let frame_timespan = timespan;
-        let timespan = match self.first_timespan {
-            Some(timespan) => TimeSpan {
-                Duration: frame_timespan - timespan.Duration,
-            },
-            None => {
-                let timespan = frame_timespan;
-                self.first_timespan = Some(TimeSpan { Duration: timespan });
-                TimeSpan { Duration: 0 }
-            }
-        };
+        if self.constant_frame_rate.is_some() {
+            return Err(VideoEncoderError::ConstantFrameRateUnsupported);
+        }
+
+        if self.timeline.is_paused {
+            return Ok(());
+        }
+
+        let timespan = self.video_timespan(timespan);
 
         self.frame_sender.send(Some((
             VideoEncoderSource::Buffer((SendDirectX::new(buffer.as_ptr()), buffer.len())),
             timespan,
         )))?;
 
-        let (lock, cvar) = &*self.frame_notify;
-        let mut processed = lock.lock();
-        if !*processed {
-            cvar.wait(&mut processed);
-        }
-        *processed = false;
-        drop(processed);
-
-        if self.error_notify.load(atomic::Ordering::Relaxed) {
-            if let Some(transcode_thread) = self.transcode_thread.take() {
-                transcode_thread
-                    .join()
-                    .expect("Failed to join transcode thread")?;
-            }
-        }
+        Self::wait_until_processed(&self.frame_notify, &self.error_notify);
+        self.transcode_error()?;
 
         Ok(())
     }
@@ -1164,7 +2002,9 @@ impl VideoEncoder {
     /// # Arguments
     ///
     /// * `buffer` - A reference to the audio byte slice to be encoded.
-    /// * `timespan` - The timespan that correlates to the frame buffer.
+    /// * `timespan` - The time of the first sample in 100 nanosecond units of the QPC timebase,
+    ///   like the timestamps of `LoopbackCapture`, the epoch of the video timeline is subtracted
+    ///   from it.
     ///
     /// # Returns
     ///
@@ -1180,40 +2020,109 @@ impl VideoEncoder {
             return Err(VideoEncoderError::AudioDisabled);
         }
 
-        let audio_timespan = timespan;
-        let timespan = match self.first_timespan {
-            Some(timespan) => TimeSpan {
-                Duration: audio_timespan - timespan.Duration,
-            },
-            None => {
-                let timespan = audio_timespan;
-                self.first_timespan = Some(TimeSpan { Duration: timespan });
-                TimeSpan { Duration: 0 }
-            }
-        };
+        if self.timeline.is_paused {
+            return Ok(());
+        }
+
+        let timespan = self.video_timespan(timespan);
 
         self.audio_sender.send(Some((
             AudioEncoderSource::Buffer((SendDirectX::new(buffer.as_ptr()), buffer.len())),
             timespan,
         )))?;
 
-        let (lock, cvar) = &*self.audio_notify;
-        let mut processed = lock.lock();
-        if !*processed {
-            cvar.wait(&mut processed);
+        Self::wait_until_processed(&self.audio_notify, &self.error_notify);
+        self.transcode_error()?;
+
+        Ok(())
+    }
+
+    /// Pauses encoding without finishing the video.
+    ///
+    /// Frames and audio sent while paused are dropped. When encoding is resumed the timeline
+    /// continues one frame after the last sample sent before the pause, so the paused time is cut
+    /// out of the video instead of showing up as a frozen frame.
+    #[inline]
+    pub const fn pause(&mut self) {
+        self.timeline.pause();
+    }
+
+    /// Resumes encoding after `pause`, see `pause` for how the timeline continues.
+    #[inline]
+    pub const fn resume(&mut self) {
+        self.timeline.resume();
+    }
+
+    /// Returns `true` if encoding is paused.
+    #[must_use]
+    #[inline]
+    pub const fn is_paused(&self) -> bool {
+        self.timeline.is_paused
+    }
+
+    /// Changes the video bitrate while encoding.
+    ///
+    /// The encoder runs on `MediaTranscoder`, which reads the `VideoEncodingProperties` once when
+    /// the transcode is prepared and doesn't expose the underlying Media Foundation encoder, so
+    /// the bitrate of a running encoder can't be changed. The bitrate is chosen with
+    /// `VideoSettingsBuilder::bitrate`, a different bitrate needs a new `VideoEncoder`.
+    ///
+    /// # Arguments
+    ///
+    /// * `bitrate` - The new video bitrate in bits per second.
+    ///
+    /// # Errors
+    ///
+    /// Returns `VideoEncoderError::VideoDisabled` if video is disabled, and
+    /// `VideoEncoderError::UnsupportedBitrateChange` otherwise.
+    #[inline]
+    pub const fn set_bitrate(&mut self, bitrate: u32) -> Result<(), VideoEncoderError> {
+        if self.is_video_disabled {
+            return Err(VideoEncoderError::VideoDisabled);
         }
-        *processed = false;
-        drop(processed);
 
-        if self.error_notify.load(atomic::Ordering::Relaxed) {
-            if let Some(transcode_thread) = self.transcode_thread.take() {
-                transcode_thread
-                    .join()
-                    .expect("Failed to join transcode thread")?;
-            }
+        let _ = bitrate;
+        Err(VideoEncoderError::UnsupportedBitrateChange)
+    }
+
+    /// Requests a keyframe for the next video sample.
+    ///
+    /// Forcing a keyframe needs the `ICodecAPI` of the Media Foundation encoder, which
+    /// `MediaTranscoder` doesn't expose. Marking the next sample with `MFSampleExtension_CleanPoint`
+    /// only marks the uncompressed input, it doesn't make the encoder emit an IDR frame, so
+    /// keyframes can't be requested on demand. `VideoSettingsBuilder::keyframe_interval` bounds
+    /// the time until the next keyframe instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns `VideoEncoderError::VideoDisabled` if video is disabled, and
+    /// `VideoEncoderError::UnsupportedKeyframeRequest` otherwise.
+    #[inline]
+    pub const fn request_keyframe(&mut self) -> Result<(), VideoEncoderError> {
+        if self.is_video_disabled {
+            return Err(VideoEncoderError::VideoDisabled);
         }
 
-        Ok(())
+        Err(VideoEncoderError::UnsupportedKeyframeRequest)
+    }
+
+    /// Gets the average bitrate the encoder achieved so far.
+    ///
+    /// The bitrate is estimated from the number of bytes written to the output over the duration
+    /// of the samples sent, so it includes the audio and the container overhead. The transcoder
+    /// buffers some samples before writing them, which makes the estimate lag behind early in a
+    /// recording and become more accurate the longer it runs.
+    ///
+    /// # Returns
+    ///
+    /// The achieved bitrate in bits per second, or `None` if no time has been encoded yet or the
+    /// output size can't be queried. Use `finish_with_stats` for the final bitrate.
+    #[must_use]
+    #[inline]
+    pub fn achieved_bitrate(&self) -> Option<u32> {
+        let size = self.output_stream.Size().ok()?;
+
+        achieved_bitrate(size, self.timeline.last_timespan)
     }
 
     /// Finishes encoding the video and performs any necessary cleanup.
@@ -1223,21 +2132,42 @@ impl VideoEncoder {
     /// Returns `Ok(())` if the encoding is successfully finished, or a `VideoEncoderError` if an
     /// error occurs.
     #[inline]
-    pub fn finish(mut self) -> Result<(), VideoEncoderError> {
+    pub fn finish(self) -> Result<(), VideoEncoderError> {
+        self.finish_with_stats().map(|_| ())
+    }
+
+    /// Finishes encoding the video like `finish` and returns the statistics of the output.
+    ///
+    /// Unlike `achieved_bitrate` during the recording, the bitrate is computed after the
+    /// transcoder wrote all samples, so it is exact for the whole output.
+    ///
+    /// # Returns
+    ///
+    /// Returns the statistics of the finished output, or a `VideoEncoderError` if an error occurs.
+    #[inline]
+    pub fn finish_with_stats(mut self) -> Result<VideoEncoderStats, VideoEncoderError> {
         self.frame_sender.send(None)?;
         self.audio_sender.send(None)?;
 
-        if let Some(transcode_thread) = self.transcode_thread.take() {
-            transcode_thread
+        match self.transcode_thread.take() {
+            Some(transcode_thread) => transcode_thread
                 .join()
-                .expect("Failed to join transcode thread")?;
+                .map_err(|_| VideoEncoderError::TranscodeThreadPanicked)??,
+            None => self.transcode_error()?,
         }
 
         self.media_stream_source.RemoveStarting(self.starting)?;
         self.media_stream_source
             .RemoveSampleRequested(self.sample_requested)?;
 
-        Ok(())
+        let output_size = self.output_stream.Size()?;
+        let duration = self.timeline.last_timespan;
+
+        Ok(VideoEncoderStats {
+            output_size,
+            duration: TimeSpan { Duration: duration },
+            achieved_bitrate: achieved_bitrate(output_size, duration),
+        })
     }
 }
 
@@ -1254,3 +2184,325 @@ impl Drop for VideoEncoder {
 
 #[allow(clippy::non_send_fields_in_send_ty)]
 unsafe impl Send for VideoEncoder {}
+
+#[cfg(test)]
+mod tests {
+    use windows::Foundation::IPropertyValue;
+
+    use super::*;
+
+    const CHANNEL_LAYOUTS: [ChannelLayout; 5] = [
+        ChannelLayout::Mono,
+        ChannelLayout::Stereo,
+        ChannelLayout::Quad,
+        ChannelLayout::Surround5_1,
+        ChannelLayout::Surround7_1,
+    ];
+
+    #[test]
+    fn storage_file_path_keeps_non_ascii_names() {
+        let path =
+            VideoEncoder::storage_file_path(Path::new(r"\\?\C:\Users\Jürgen\Vidéos\録画 🎬.mp4"))
+                .unwrap();
+        assert_eq!(path, HSTRING::from(r"C:\Users\Jürgen\Vidéos\録画 🎬.mp4"));
+
+        let path =
+            VideoEncoder::storage_file_path(Path::new(r"\\?\UNC\server\共有\vidéo.mp4")).unwrap();
+        assert_eq!(path, HSTRING::from(r"\\server\共有\vidéo.mp4"));
+
+        let path = VideoEncoder::storage_file_path(Path::new(r"D:\ñ\ø.mp4")).unwrap();
+        assert_eq!(path, HSTRING::from(r"D:\ñ\ø.mp4"));
+    }
+
+    #[test]
+    fn channel_mask_matches_channel_count() {
+        for channel_layout in CHANNEL_LAYOUTS {
+            assert_eq!(
+                channel_layout.channel_mask().count_ones(),
+                channel_layout.channel_count(),
+                "{channel_layout:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn channel_mask_values() {
+        // SPEAKER_FRONT_CENTER
+        assert_eq!(ChannelLayout::Mono.channel_mask(), 0x4);
+        // KSAUDIO_SPEAKER_STEREO
+        assert_eq!(ChannelLayout::Stereo.channel_mask(), 0x3);
+        // KSAUDIO_SPEAKER_QUAD
+        assert_eq!(ChannelLayout::Quad.channel_mask(), 0x33);
+        // KSAUDIO_SPEAKER_5POINT1
+        assert_eq!(ChannelLayout::Surround5_1.channel_mask(), 0x3F);
+        // KSAUDIO_SPEAKER_7POINT1_SURROUND
+        assert_eq!(ChannelLayout::Surround7_1.channel_mask(), 0x63F);
+    }
+
+    #[test]
+    fn channel_layout_overrides_channel_count() {
+        let builder = AudioSettingsBuilder::new()
+            .channel_count(2)
+            .channel_layout(ChannelLayout::Surround5_1);
+
+        assert_eq!(builder.channel_count, 6);
+        assert_eq!(builder.channel_layout, Some(ChannelLayout::Surround5_1));
+    }
+
+    #[test]
+    fn keyframe_interval_sets_max_keyframe_spacing() {
+        let (properties, _) = VideoSettingsBuilder::new(1920, 1080)
+            .keyframe_interval(60)
+            .build()
+            .unwrap();
+        let spacing = properties
+            .Properties()
+            .unwrap()
+            .Lookup(MF_MT_MAX_KEYFRAME_SPACING)
+            .unwrap()
+            .cast::<IPropertyValue>()
+            .unwrap();
+
+        assert_eq!(spacing.GetUInt32().unwrap(), 60);
+    }
+
+    #[test]
+    fn keyframe_interval_rejects_zero() {
+        assert!(matches!(
+            VideoSettingsBuilder::new(1920, 1080)
+                .keyframe_interval(0)
+                .build(),
+            Err(VideoEncoderError::InvalidKeyframeInterval)
+        ));
+    }
+
+    #[test]
+    fn effective_intermediate_format_implied_by_grayscale_and_crop() {
+        let settings = VideoSettingsBuilder::new(1920, 1080);
+        assert_eq!(
+            settings.effective_intermediate_format(),
+            IntermediateFormat::Bgra8
+        );
+
+        let settings =
+            VideoSettingsBuilder::new(1920, 1080).intermediate_format(IntermediateFormat::Nv12);
+        assert_eq!(
+            settings.effective_intermediate_format(),
+            IntermediateFormat::Nv12
+        );
+
+        let settings = VideoSettingsBuilder::new(1920, 1080).grayscale(true);
+        assert_eq!(
+            settings.effective_intermediate_format(),
+            IntermediateFormat::Nv12
+        );
+
+        let settings =
+            VideoSettingsBuilder::new(1920, 1080).crop(CaptureRegion::new(0, 0, 640, 480));
+        assert_eq!(
+            settings.effective_intermediate_format(),
+            IntermediateFormat::Nv12
+        );
+    }
+
+    #[test]
+    fn grayscale_requires_even_size() {
+        assert!(VideoSettingsBuilder::new(1920, 1080)
+            .grayscale(true)
+            .build()
+            .is_ok());
+        assert!(matches!(
+            VideoSettingsBuilder::new(1921, 1080)
+                .grayscale(true)
+                .build(),
+            Err(VideoEncoderError::InvalidIntermediateSize)
+        ));
+        assert!(matches!(
+            VideoSettingsBuilder::new(1920, 1081)
+                .grayscale(true)
+                .build(),
+            Err(VideoEncoderError::InvalidIntermediateSize)
+        ));
+
+        // Without grayscale the Bgra8 intermediate format accepts odd sizes
+        assert!(VideoSettingsBuilder::new(1921, 1081).build().is_ok());
+    }
+
+    #[test]
+    fn crop_must_not_be_empty() {
+        assert!(VideoSettingsBuilder::new(1920, 1080)
+            .crop(CaptureRegion::new(0, 0, 640, 480))
+            .build()
+            .is_ok());
+        assert!(matches!(
+            VideoSettingsBuilder::new(1920, 1080)
+                .crop(CaptureRegion::new(0, 0, 0, 480))
+                .build(),
+            Err(VideoEncoderError::InvalidCrop)
+        ));
+        assert!(matches!(
+            VideoSettingsBuilder::new(1920, 1080)
+                .crop(CaptureRegion::new(0, 0, 640, 0))
+                .build(),
+            Err(VideoEncoderError::InvalidCrop)
+        ));
+    }
+
+    #[test]
+    fn set_bitrate_is_unsupported() {
+        let mut encoder = VideoEncoder::new_to_writer(
+            VideoSettingsBuilder::new(64, 64),
+            AudioSettingsBuilder::default().disabled(true),
+            ContainerSettingsBuilder::default(),
+            std::io::sink(),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            encoder.set_bitrate(1_000_000),
+            Err(VideoEncoderError::UnsupportedBitrateChange)
+        ));
+    }
+
+    #[test]
+    fn request_keyframe_is_unsupported() {
+        let mut encoder = VideoEncoder::new_to_writer(
+            VideoSettingsBuilder::new(64, 64),
+            AudioSettingsBuilder::default().disabled(true),
+            ContainerSettingsBuilder::default(),
+            std::io::sink(),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            encoder.request_keyframe(),
+            Err(VideoEncoderError::UnsupportedKeyframeRequest)
+        ));
+    }
+
+    #[test]
+    fn check_frame_color_format_accepts_encodable_formats() {
+        for intermediate_format in [IntermediateFormat::Bgra8, IntermediateFormat::Nv12] {
+            assert!(check_frame_color_format(intermediate_format, ColorFormat::Bgra8).is_ok());
+            assert!(matches!(
+                check_frame_color_format(intermediate_format, ColorFormat::Rgba16F),
+                Err(VideoEncoderError::ColorFormatMismatch(
+                    ColorFormat::Rgba16F,
+                    ColorFormat::Bgra8
+                ))
+            ));
+        }
+
+        assert!(check_frame_color_format(IntermediateFormat::Nv12, ColorFormat::Rgba8).is_ok());
+        assert!(matches!(
+            check_frame_color_format(IntermediateFormat::Bgra8, ColorFormat::Rgba8),
+            Err(VideoEncoderError::ColorFormatMismatch(
+                ColorFormat::Rgba8,
+                ColorFormat::Bgra8
+            ))
+        ));
+    }
+
+    #[test]
+    fn constant_frame_rate_slots_snap_and_fill() {
+        // 30 fps, one slot is 333_333 100-nanosecond units
+        assert_eq!(constant_frame_rate_slots(None, 0, 30), Some((0, 0..0)));
+        assert_eq!(
+            constant_frame_rate_slots(Some(0), 340_000, 30),
+            Some((1, 1..1))
+        );
+        assert_eq!(
+            constant_frame_rate_slots(Some(1), 1_000_000, 30),
+            Some((3, 2..3))
+        );
+    }
+
+    #[test]
+    fn constant_frame_rate_slots_drop_same_slot() {
+        assert_eq!(constant_frame_rate_slots(Some(3), 1_100_000, 30), None);
+        assert_eq!(constant_frame_rate_slots(Some(3), 900_000, 30), None);
+    }
+
+    #[test]
+    fn constant_frame_rate_slots_cap_duplicates() {
+        // A 100 second gap only gets one second of duplicates
+        let (index, duplicates) = constant_frame_rate_slots(Some(0), 1_000_000_000, 30).unwrap();
+
+        assert_eq!(index, 3000);
+        assert_eq!(duplicates, 2970..3000);
+    }
+
+    #[test]
+    fn achieved_bitrate_of_output() {
+        // 1 MB over 2 seconds
+        assert_eq!(achieved_bitrate(1_000_000, 20_000_000), Some(4_000_000));
+        assert_eq!(achieved_bitrate(0, 20_000_000), Some(0));
+        assert_eq!(achieved_bitrate(1_000_000, 0), None);
+        assert_eq!(achieved_bitrate(1_000_000, -1), None);
+
+        // Saturates instead of wrapping
+        assert_eq!(achieved_bitrate(u64::MAX, 1), Some(u32::MAX));
+    }
+
+    #[test]
+    fn video_timeline_starts_at_first_sample() {
+        let mut timeline = VideoTimeline::new(333_333);
+
+        assert_eq!(timeline.timespan(50_000_000), 0);
+        assert_eq!(timeline.timespan(50_333_333), 333_333);
+
+        // The epoch can't move once a sample was placed
+        timeline.set_epoch(40_000_000);
+        assert_eq!(timeline.timespan(50_666_666), 666_666);
+    }
+
+    #[test]
+    fn video_timeline_starts_at_epoch() {
+        // A session start epoch shared by video and audio samples
+        let mut timeline = VideoTimeline::new(333_333);
+        timeline.set_epoch(50_000_000);
+
+        assert_eq!(timeline.timespan(50_500_000), 500_000);
+        assert_eq!(timeline.timespan(50_400_000), 400_000);
+        assert_eq!(timeline.timespan(50_833_333), 833_333);
+
+        // Samples from before the epoch are clamped to its start
+        assert_eq!(timeline.timespan(49_000_000), 0);
+    }
+
+    #[test]
+    fn video_timeline_cuts_out_pause() {
+        let mut timeline = VideoTimeline::new(333_333);
+        assert_eq!(timeline.timespan(10_000_000), 0);
+        assert_eq!(timeline.timespan(10_333_333), 333_333);
+
+        timeline.pause();
+        assert!(timeline.is_paused);
+        timeline.resume();
+        assert!(!timeline.is_paused);
+
+        // The first sample after the pause continues one frame after the last one
+        assert_eq!(timeline.timespan(20_000_000), 666_666);
+        assert_eq!(timeline.timespan(20_333_333), 999_999);
+
+        // A second pause adds to the time that is cut out
+        timeline.pause();
+        timeline.resume();
+        assert_eq!(timeline.timespan(30_000_000), 1_333_332);
+    }
+
+    #[test]
+    fn video_timeline_short_pause_keeps_timing() {
+        let mut timeline = VideoTimeline::new(333_333);
+        assert_eq!(timeline.timespan(0), 0);
+
+        // A pause shorter than a frame doesn't shift the timeline backwards
+        timeline.pause();
+        timeline.resume();
+        assert_eq!(timeline.timespan(333_333), 333_333);
+
+        // Resuming without a pause changes nothing
+        timeline.resume();
+        assert_eq!(timeline.timespan(1_000_000), 1_000_000);
+    }
+}
@@ -1,27 +1,49 @@
 use std::{
     fs::{self},
-    io,
+    io, mem,
     path::Path,
     ptr, slice,
+    sync::Arc,
+    time::Duration,
 };
 
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use parking_lot::Mutex;
+
+use rayon::{
+    iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator},
+    slice::ParallelSliceMut,
+};
 use windows::{
+    core::Interface,
     Foundation::TimeSpan,
-    Graphics::DirectX::Direct3D11::IDirect3DSurface,
-    Win32::Graphics::{
-        Direct3D11::{
-            ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, D3D11_BOX, D3D11_CPU_ACCESS_READ,
-            D3D11_CPU_ACCESS_WRITE, D3D11_MAPPED_SUBRESOURCE, D3D11_MAP_READ_WRITE,
-            D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING,
+    Graphics::{DirectX::Direct3D11::IDirect3DSurface, RectInt32},
+    Win32::{
+        Foundation::{CloseHandle, HANDLE},
+        Graphics::{
+            Direct3D11::{
+                ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, D3D11_BOX,
+                D3D11_CPU_ACCESS_READ, D3D11_CPU_ACCESS_WRITE, D3D11_MAPPED_SUBRESOURCE,
+                D3D11_MAP_READ, D3D11_MAP_READ_WRITE, D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION,
+                D3D11_RESOURCE_MISC_SHARED_KEYEDMUTEX, D3D11_RESOURCE_MISC_SHARED_NTHANDLE,
+                D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT, D3D11_USAGE_STAGING,
+            },
+            Dxgi::{
+                Common::{DXGI_FORMAT, DXGI_SAMPLE_DESC},
+                IDXGIKeyedMutex, IDXGIResource1, DXGI_SHARED_RESOURCE_READ,
+                DXGI_SHARED_RESOURCE_WRITE,
+            },
+        },
+        System::{
+            Performance::{QueryPerformanceCounter, QueryPerformanceFrequency},
+            Threading::INFINITE,
         },
-        Dxgi::Common::{DXGI_FORMAT, DXGI_SAMPLE_DESC},
     },
 };
 
 use crate::{
+    d3d11::{self, SendDirectX, StagingTexture},
     encoder::{self, ImageEncoder},
-    settings::ColorFormat,
+    settings::{ColorFormat, EpochMode},
 };
 
 #[derive(thiserror::Error, Debug)]
@@ -30,15 +52,27 @@ pub enum Error {
     InvalidSize,
     #[error("This color format is not supported for saving as image")]
     UnsupportedFormat,
+    #[error("Conversion between these color formats is not supported")]
+    UnsupportedConversion,
+    #[error("Staging texture size or color format doesn't match the frame")]
+    InvalidStagingTexture,
+    #[error("Frame buffer is not backed by a frame texture to write back to")]
+    WriteBackUnsupported,
     #[error("Failed to encode image buffer to image bytes with specified format: {0}")]
     ImageEncoderError(#[from] encoder::ImageEncoderError),
     #[error("IO error: {0}")]
     IoError(#[from] io::Error),
+    #[error("DirectX error: {0}")]
+    DirectXError(#[from] d3d11::Error),
     #[error("Windows API error: {0}")]
     WindowsError(#[from] windows::core::Error),
 }
 
+/// The image formats frames can be saved as.
+///
+/// New formats may be added in minor releases, so matches on it need a wildcard arm.
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
+#[non_exhaustive]
 pub enum ImageFormat {
     Jpeg,
     Png,
@@ -46,6 +80,57 @@ pub enum ImageFormat {
     Tiff,
     Bmp,
     JpegXr,
+    WebP,
+}
+
+/// A drawing operation applied to a frame with `Frame::annotate`.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum DrawOp {
+    /// Fill a rectangle with a solid color, the color is in sRGB RGBA order and is written as is,
+    /// without blending with the frame. On `Rgba16F` frames the color channels are converted to
+    /// linear values, the alpha channel is already linear.
+    FillRect { rect: RectInt32, color: [u8; 4] },
+}
+
+/// Common interface of the captured frame types.
+///
+/// Lets processing code be written once for both a `Frame` inside the frame handler and an
+/// `OwnedFrame` that was copied out of it.
+///
+/// # Example
+/// ```ignore
+/// fn save_thumbnail(frame: &mut impl CapturedFrame) -> Result<(), Error> {
+///     let rgba = frame.to_rgba_packed()?;
+///     let image = image::RgbaImage::from_raw(frame.width(), frame.height(), rgba).unwrap();
+///     // ...
+///     Ok(())
+/// }
+/// ```
+pub trait CapturedFrame {
+    /// Get the width of the frame.
+    fn width(&self) -> u32;
+
+    /// Get the height of the frame.
+    fn height(&self) -> u32;
+
+    /// Get the color format of the frame.
+    fn color_format(&self) -> ColorFormat;
+
+    /// Get the pixel data as top-down packed 8-bit RGBA.
+    ///
+    /// # Returns
+    ///
+    /// The packed pixel data of the frame.
+    fn to_rgba_packed(&mut self) -> Result<Vec<u8>, Error>;
+
+    /// Save the frame as an image to the specified path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path where the image will be saved.
+    /// * `format` - The image format to use for saving.
+    fn save_as_image<T: AsRef<Path>>(&mut self, path: T, format: ImageFormat) -> Result<(), Error>;
 }
 
 /// Represents a frame captured from a graphics capture item.
@@ -61,13 +146,27 @@ pub struct Frame<'a> {
     frame_surface: IDirect3DSurface,
     frame_texture: ID3D11Texture2D,
     time: TimeSpan,
+    previous_time: Option<TimeSpan>,
+    epoch_mode: EpochMode,
+    epoch: TimeSpan,
+    dirty_regions: Vec<RectInt32>,
     context: &'a ID3D11DeviceContext,
     buffer: &'a mut Vec<u8>,
+    staging_cache: Option<&'a mut StagingCache>,
     width: u32,
     height: u32,
     color_format: ColorFormat,
 }
 
+/// Staging textures reused by the frames of a capture session.
+#[derive(Default)]
+pub(crate) struct StagingCache {
+    /// The texture the pixels sampled by `Frame::sample_signature` are copied to.
+    samples: Option<SendDirectX<ID3D11Texture2D>>,
+    /// The texture the whole frame is copied to by `Frame::copy_to_vec`.
+    frame: Option<SendDirectX<StagingTexture>>,
+}
+
 impl<'a> Frame<'a> {
     /// Create a new Frame.
     ///
@@ -105,8 +204,13 @@ impl<'a> Frame<'a> {
             frame_surface,
             frame_texture,
             time,
+            previous_time: None,
+            epoch_mode: EpochMode::QpcZero,
+            epoch: TimeSpan::default(),
+            dirty_regions: Vec::new(),
             context,
             buffer,
+            staging_cache: None,
             width,
             height,
             color_format,
@@ -137,15 +241,238 @@ impl<'a> Frame<'a> {
 
     /// Get the time of the frame.
     ///
+    /// The time is measured from the epoch chosen with `Settings::with_timestamp_epoch`, by
+    /// default the zero of the QPC timebase. Content presented before the epoch, like a frame
+    /// rendered just before the capture started with `EpochMode::SessionStart`, gets a time of
+    /// zero.
+    ///
     /// # Returns
     ///
     /// The time of the frame.
     #[must_use]
     #[inline]
     pub const fn timespan(&self) -> TimeSpan {
+        let duration = self.time.Duration - self.epoch.Duration;
+
+        TimeSpan {
+            Duration: if duration < 0 { 0 } else { duration },
+        }
+    }
+
+    /// Draw on the frame texture on the GPU.
+    ///
+    /// The operations are applied in order directly to the frame surface, so everything that
+    /// reads the frame afterwards, like `buffer`, `save_as_image` or `VideoEncoder::send_frame`,
+    /// sees the result. This makes it suitable for redacting regions before a frame is saved or
+    /// encoded. Rectangles are clipped to the frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `ops` - The drawing operations to apply.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the operations were applied, or an `Error` if an error occurred.
+    #[inline]
+    pub fn annotate(&mut self, ops: &[DrawOp]) -> Result<(), Error> {
+        for op in ops {
+            match *op {
+                DrawOp::FillRect { rect, color } => {
+                    let left = u32::try_from(rect.X.max(0)).unwrap().min(self.width);
+                    let top = u32::try_from(rect.Y.max(0)).unwrap().min(self.height);
+                    let right = u32::try_from(rect.X.saturating_add(rect.Width).max(0))
+                        .unwrap()
+                        .min(self.width);
+                    let bottom = u32::try_from(rect.Y.saturating_add(rect.Height).max(0))
+                        .unwrap()
+                        .min(self.height);
+
+                    if left >= right || top >= bottom {
+                        continue;
+                    }
+
+                    // Color in the layout of the frame color format
+                    let pixel = match self.color_format {
+                        ColorFormat::Rgba16F => color
+                            .iter()
+                            .enumerate()
+                            .flat_map(|(i, channel)| {
+                                let value = f32::from(*channel) / 255.0;
+                                let value = if i == 3 { value } else { srgb_to_linear(value) };
+                                f32_to_half(value).to_le_bytes()
+                            })
+                            .collect::<Vec<u8>>(),
+                        ColorFormat::Rgba8 => color.to_vec(),
+                        ColorFormat::Bgra8 => vec![color[2], color[1], color[0], color[3]],
+                    };
+
+                    let row_pitch = (right - left) as usize * pixel.len();
+                    let data = pixel.repeat(((right - left) * (bottom - top)) as usize);
+
+                    let resource_box = D3D11_BOX {
+                        left,
+                        top,
+                        front: 0,
+                        right,
+                        bottom,
+                        back: 1,
+                    };
+
+                    unsafe {
+                        self.context.UpdateSubresource(
+                            &self.frame_texture,
+                            0,
+                            Some(&resource_box),
+                            data.as_ptr().cast(),
+                            u32::try_from(row_pitch).unwrap(),
+                            0,
+                        );
+                    };
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the raw `SystemRelativeTime` of the frame.
+    ///
+    /// This is the time the content was presented in 100 nanosecond units of the QPC timebase,
+    /// the same value as `timespan` with `EpochMode::QpcZero`.
+    ///
+    /// # Returns
+    ///
+    /// The system relative time of the frame.
+    #[must_use]
+    #[inline]
+    pub const fn system_relative_time(&self) -> TimeSpan {
         self.time
     }
 
+    /// Get the presentation time of the frame in QPC ticks.
+    ///
+    /// The value can be compared directly with `QueryPerformanceCounter` readings of other
+    /// subsystems, for example audio capture timestamps.
+    ///
+    /// # Returns
+    ///
+    /// The presentation time of the frame in QPC ticks.
+    #[inline]
+    pub fn presentation_time_qpc(&self) -> Result<i64, Error> {
+        let mut frequency = 0;
+        unsafe { QueryPerformanceFrequency(&mut frequency)? };
+
+        let ticks = i128::from(self.time.Duration) * i128::from(frequency) / 10_000_000;
+
+        Ok(i64::try_from(ticks).unwrap_or(i64::MAX))
+    }
+
+    /// Get the time between the previously delivered frame and this frame.
+    ///
+    /// Only frames that reached the frame handler are taken into account, frames dropped by
+    /// throttling, warm-up or `deliver_on_change_only` are not.
+    ///
+    /// # Returns
+    ///
+    /// The difference between the presentation times, or `None` for the first delivered frame.
+    #[must_use]
+    #[inline]
+    pub fn duration_since_previous(&self) -> Option<Duration> {
+        let previous_time = self.previous_time?;
+        let duration = u64::try_from(self.time.Duration - previous_time.Duration).unwrap_or(0);
+
+        Some(Duration::from_nanos(duration * 100))
+    }
+
+    /// Set the presentation time of the previously delivered frame.
+    #[inline]
+    pub(crate) const fn set_previous_timespan(&mut self, previous_time: Option<TimeSpan>) {
+        self.previous_time = previous_time;
+    }
+
+    /// Set the regions of the frame that changed since the previous frame.
+    #[inline]
+    pub(crate) fn set_dirty_regions(&mut self, dirty_regions: Vec<RectInt32>) {
+        self.dirty_regions = dirty_regions;
+    }
+
+    /// Set the staging textures reused by the frames of the capture session.
+    #[inline]
+    pub(crate) const fn set_staging_cache(&mut self, staging_cache: &'a mut StagingCache) {
+        self.staging_cache = Some(staging_cache);
+    }
+
+    /// Set the epoch `timespan` is measured from.
+    #[inline]
+    pub(crate) const fn set_epoch(&mut self, epoch_mode: EpochMode, epoch: TimeSpan) {
+        self.epoch_mode = epoch_mode;
+        self.epoch = epoch;
+    }
+
+    /// Get the epoch `timespan` is measured from in the QPC timebase, `None` with
+    /// `EpochMode::QpcZero`.
+    #[must_use]
+    #[inline]
+    pub(crate) const fn epoch(&self) -> Option<TimeSpan> {
+        match self.epoch_mode {
+            EpochMode::QpcZero => None,
+            EpochMode::FirstFrame | EpochMode::SessionStart => Some(self.epoch),
+        }
+    }
+
+    /// Get the time between the content being presented and the frame being delivered.
+    ///
+    /// The frame time and the current time are both read from the QPC timebase, so the latency
+    /// includes the time spent in the OS, the frame pool and the frame handler so far.
+    ///
+    /// # Returns
+    ///
+    /// The delivery latency of the frame, zero if the frame time is in the future.
+    #[inline]
+    pub fn delivery_latency(&self) -> Result<Duration, Error> {
+        let mut counter = 0;
+        let mut frequency = 0;
+        unsafe {
+            QueryPerformanceCounter(&mut counter)?;
+            QueryPerformanceFrequency(&mut frequency)?;
+        };
+
+        // Convert the counter to 100 nanosecond units like the frame time
+        let now = i128::from(counter) * 10_000_000 / i128::from(frequency);
+        let latency = u64::try_from(now - i128::from(self.time.Duration)).unwrap_or(0);
+
+        Ok(Duration::from_nanos(latency * 100))
+    }
+
+    /// Get the regions of the frame that changed since the previous frame.
+    ///
+    /// With `DirtyRegionSettings::ReportOnly` or `DirtyRegionSettings::ReportAndRender` this
+    /// contains the rectangles the OS reported for this specific frame. An empty slice means
+    /// nothing changed, for example when only the cursor moved.
+    ///
+    /// A full frame update is always reported as a single region covering the whole frame, this
+    /// is also the case for every frame when dirty region reporting is not enabled.
+    ///
+    /// # Returns
+    ///
+    /// The dirty regions of the frame.
+    #[must_use]
+    #[inline]
+    pub fn dirty_regions(&self) -> &[RectInt32] {
+        &self.dirty_regions
+    }
+
+    /// Check if any region of the frame changed since the previous frame.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the frame has at least one dirty region, `false` if nothing changed.
+    #[must_use]
+    #[inline]
+    pub fn has_dirty_regions(&self) -> bool {
+        !self.dirty_regions.is_empty()
+    }
+
     /// Get the color format of the frame.
     ///
     /// # Returns
@@ -185,6 +512,20 @@ impl<'a> Frame<'a> {
         &self.frame_texture
     }
 
+    /// Get the DirectX device the frame was captured with.
+    #[must_use]
+    #[inline]
+    pub(crate) const fn d3d_device(&self) -> &ID3D11Device {
+        self.d3d_device
+    }
+
+    /// Get the device context of the DirectX device the frame was captured with.
+    #[must_use]
+    #[inline]
+    pub(crate) const fn context(&self) -> &ID3D11DeviceContext {
+        self.context
+    }
+
     /// Get the frame buffer.
     ///
     /// # Returns
@@ -192,48 +533,67 @@ impl<'a> Frame<'a> {
     /// The FrameBuffer containing the frame data.
     #[inline]
     pub fn buffer(&mut self) -> Result<FrameBuffer, Error> {
-        // Texture Settings
-        let texture_desc = D3D11_TEXTURE2D_DESC {
-            Width: self.width,
-            Height: self.height,
-            MipLevels: 1,
-            ArraySize: 1,
-            Format: DXGI_FORMAT(self.color_format as i32),
-            SampleDesc: DXGI_SAMPLE_DESC {
-                Count: 1,
-                Quality: 0,
-            },
-            Usage: D3D11_USAGE_STAGING,
-            BindFlags: 0,
-            CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as u32 | D3D11_CPU_ACCESS_WRITE.0 as u32,
-            MiscFlags: 0,
-        };
-
         // Create a texture that CPU can read
-        let mut texture = None;
-        unsafe {
-            self.d3d_device
-                .CreateTexture2D(&texture_desc, None, Some(&mut texture))?;
+        let mut staging =
+            StagingTexture::new(self.d3d_device, self.width, self.height, self.color_format)?;
+        let mapped_resource = map_frame_copy(self.context, &self.frame_texture, &mut staging)?;
+
+        // Get the mapped resource data slice
+        let mapped_frame_data = unsafe {
+            slice::from_raw_parts_mut(
+                mapped_resource.pData.cast(),
+                (self.height * mapped_resource.RowPitch) as usize,
+            )
         };
 
-        let texture = texture.unwrap();
+        // Create frame buffer from slice
+        let frame_buffer = FrameBuffer::new(
+            mapped_frame_data,
+            self.buffer,
+            self.width,
+            self.height,
+            mapped_resource.RowPitch,
+            mapped_resource.DepthPitch,
+            self.color_format,
+        )
+        .with_write_back(WriteBackTarget {
+            context: self.context.clone(),
+            staging: staging.texture().clone(),
+            is_mapped: None,
+            destination: self.frame_texture.clone(),
+            x: 0,
+            y: 0,
+        });
 
-        // Copy the real texture to copy texture
-        unsafe {
-            self.context.CopyResource(&texture, &self.frame_texture);
-        };
+        Ok(frame_buffer)
+    }
 
-        // Map the texture to enable CPU access
-        let mut mapped_resource = D3D11_MAPPED_SUBRESOURCE::default();
-        unsafe {
-            self.context.Map(
-                &texture,
-                0,
-                D3D11_MAP_READ_WRITE,
-                0,
-                Some(&mut mapped_resource),
-            )?;
-        };
+    /// Get the frame buffer using a reusable staging texture.
+    ///
+    /// Works like `buffer` without creating a staging texture for every frame. The staging
+    /// texture is unmapped first if a previous frame buffer still has it mapped.
+    ///
+    /// # Arguments
+    ///
+    /// * `staging` - The staging texture, it must have the size and color format of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The FrameBuffer containing the frame data, or `Error::InvalidStagingTexture` if the
+    /// staging texture doesn't match the frame.
+    #[inline]
+    pub fn buffer_with<'s>(
+        &'s mut self,
+        staging: &'s mut StagingTexture,
+    ) -> Result<FrameBuffer<'s>, Error> {
+        if staging.width() != self.width
+            || staging.height() != self.height
+            || staging.color_format() != self.color_format
+        {
+            return Err(Error::InvalidStagingTexture);
+        }
+
+        let mapped_resource = map_frame_copy(self.context, &self.frame_texture, staging)?;
 
         // Get the mapped resource data slice
         let mapped_frame_data = unsafe {
@@ -252,11 +612,113 @@ impl<'a> Frame<'a> {
             mapped_resource.RowPitch,
             mapped_resource.DepthPitch,
             self.color_format,
-        );
+        )
+        .with_write_back(WriteBackTarget {
+            context: self.context.clone(),
+            staging: staging.texture().clone(),
+            is_mapped: Some(staging.is_mapped_mut()),
+            destination: self.frame_texture.clone(),
+            x: 0,
+            y: 0,
+        });
 
         Ok(frame_buffer)
     }
 
+    /// Copy the pixel data of the frame without padding into a caller provided buffer.
+    ///
+    /// The buffer is resized to the frame size, which only allocates when its capacity is too
+    /// small, so reusing the same buffer for every frame avoids allocating per frame. The staging
+    /// texture the frame is read back through is kept by the capture session and only recreated
+    /// when the frame size changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - The buffer the pixel data is copied to.
+    ///
+    /// # Returns
+    ///
+    /// The width and height of the copied frame.
+    #[inline]
+    pub fn copy_to_vec(&mut self, out: &mut Vec<u8>) -> Result<(u32, u32), Error> {
+        // Reuse the staging texture of the capture session
+        let mut local_staging = None;
+        let mut staging_cache = self.staging_cache.take();
+        let staging = match &mut staging_cache {
+            Some(staging_cache) => &mut staging_cache.frame,
+            None => &mut local_staging,
+        };
+
+        let result = self.copy_to_vec_through(staging, out);
+        self.staging_cache = staging_cache;
+
+        result
+    }
+
+    /// Copy the pixel data of the frame without padding through a reusable staging texture.
+    ///
+    /// The staging texture is created on the first call and recreated when it doesn't match the
+    /// size, color format or device of the frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `staging` - The staging texture kept between calls.
+    /// * `out` - The buffer the pixel data is copied to.
+    ///
+    /// # Returns
+    ///
+    /// The width and height of the copied frame.
+    pub(crate) fn copy_to_vec_through(
+        &mut self,
+        staging: &mut Option<SendDirectX<StagingTexture>>,
+        out: &mut Vec<u8>,
+    ) -> Result<(u32, u32), Error> {
+        if staging.as_ref().is_none_or(|staging| {
+            staging.0.width() != self.width
+                || staging.0.height() != self.height
+                || staging.0.color_format() != self.color_format
+                || unsafe { staging.0.texture().GetDevice() }.ok().as_ref() != Some(self.d3d_device)
+        }) {
+            *staging = Some(SendDirectX::new(StagingTexture::new(
+                self.d3d_device,
+                self.width,
+                self.height,
+                self.color_format,
+            )?));
+        }
+
+        let staging = &mut staging.as_mut().unwrap().0;
+        let mapped_resource = map_frame_copy(self.context, &self.frame_texture, staging)?;
+
+        let multiplyer = match self.color_format {
+            ColorFormat::Rgba16F => 8,
+            ColorFormat::Rgba8 => 4,
+            ColorFormat::Bgra8 => 4,
+        };
+
+        let width_size = (self.width * multiplyer) as usize;
+        let row_pitch = mapped_resource.RowPitch as usize;
+        out.resize(width_size * self.height as usize, 0);
+
+        // Copy the rows without their padding
+        let mapped_frame_data = unsafe {
+            slice::from_raw_parts(
+                mapped_resource.pData.cast::<u8>(),
+                self.height as usize * row_pitch,
+            )
+        };
+        out.par_chunks_exact_mut(width_size)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let index = y * row_pitch;
+                row.copy_from_slice(&mapped_frame_data[index..index + width_size]);
+            });
+
+        staging.unmap(self.context);
+
+        Ok((self.width, self.height))
+    }
+
     /// Get a cropped frame buffer.
     ///
     /// # Arguments
@@ -362,196 +824,653 @@ impl<'a> Frame<'a> {
             mapped_resource.RowPitch,
             mapped_resource.DepthPitch,
             self.color_format,
-        );
+        )
+        .with_write_back(WriteBackTarget {
+            context: self.context.clone(),
+            staging: texture,
+            is_mapped: None,
+            destination: self.frame_texture.clone(),
+            x: start_width,
+            y: start_height,
+        });
 
         Ok(frame_buffer)
     }
 
-    /// Save the frame buffer as an image to the specified path.
+    /// Compute a cheap signature of the frame from a fixed set of sampled pixels.
+    ///
+    /// The sampled positions are pseudo-random but only depend on the frame size, so two frames
+    /// of the same size are always sampled at the same positions. A different signature means the
+    /// frame changed, the same signature means the sampled pixels did not change. More samples
+    /// make it less likely to miss a change at the cost of reading more pixels.
+    ///
+    /// Only the sampled pixels are copied from the GPU, into a small staging texture that is
+    /// reused by the frames of a capture session.
     ///
     /// # Arguments
     ///
-    /// * `path` - The path where the image will be saved.
-    /// * `format` - The ImageFormat of the saved image.
+    /// * `samples` - The number of pixels to sample, at most 16384.
     ///
     /// # Returns
     ///
-    /// An empty Result if successful, or an Error if there was an issue saving the image.
+    /// The FNV-1a hash of the sampled pixels.
     #[inline]
-    pub fn save_as_image<T: AsRef<Path>>(
-        &mut self,
-        path: T,
-        format: ImageFormat,
-    ) -> Result<(), Error> {
-        let mut frame_buffer = self.buffer()?;
-
-        frame_buffer.save_as_image(path, format)?;
+    pub fn sample_signature(&mut self, samples: usize) -> Result<u64, Error> {
+        let samples = samples.min(D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION as usize);
+        if samples == 0 || self.width == 0 || self.height == 0 {
+            return Ok(signature_hash(&[]));
+        }
 
-        Ok(())
+        let multiplyer = match self.color_format {
+            ColorFormat::Rgba16F => 8,
+            ColorFormat::Rgba8 => 4,
+            ColorFormat::Bgra8 => 4,
+        };
+
+        // Reuse the sample texture of the capture session if it has the right size and format
+        let mut local_texture = None;
+        let sample_texture = match &mut self.staging_cache {
+            Some(staging_cache) => &mut staging_cache.samples,
+            None => &mut local_texture,
+        };
+
+        let mut sample_desc = D3D11_TEXTURE2D_DESC::default();
+        if let Some(texture) = sample_texture.as_ref() {
+            unsafe { texture.0.GetDesc(&mut sample_desc) };
+        }
+
+        if sample_texture.is_none()
+            || sample_desc.Width as usize != samples
+            || sample_desc.Format != DXGI_FORMAT(self.color_format as i32)
+        {
+            // Texture Settings
+            let texture_desc = D3D11_TEXTURE2D_DESC {
+                Width: u32::try_from(samples).unwrap(),
+                Height: 1,
+                MipLevels: 1,
+                ArraySize: 1,
+                Format: DXGI_FORMAT(self.color_format as i32),
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Usage: D3D11_USAGE_STAGING,
+                BindFlags: 0,
+                CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as u32,
+                MiscFlags: 0,
+            };
+
+            let mut texture = None;
+            unsafe {
+                self.d3d_device
+                    .CreateTexture2D(&texture_desc, None, Some(&mut texture))?;
+            };
+            *sample_texture = Some(SendDirectX::new(texture.unwrap()));
+        }
+
+        let texture = &sample_texture.as_ref().unwrap().0;
+
+        // Copy every sampled pixel next to each other in the sample texture
+        for (i, (x, y)) in signature_positions(self.width, self.height, samples).enumerate() {
+            let resource_box = D3D11_BOX {
+                left: x,
+                top: y,
+                front: 0,
+                right: x + 1,
+                bottom: y + 1,
+                back: 1,
+            };
+
+            unsafe {
+                self.context.CopySubresourceRegion(
+                    texture,
+                    0,
+                    u32::try_from(i).unwrap(),
+                    0,
+                    0,
+                    &self.frame_texture,
+                    0,
+                    Some(&resource_box),
+                );
+            };
+        }
+
+        // Map the texture to enable CPU access
+        let mut mapped_resource = D3D11_MAPPED_SUBRESOURCE::default();
+        unsafe {
+            self.context
+                .Map(texture, 0, D3D11_MAP_READ, 0, Some(&mut mapped_resource))?;
+        };
+
+        let sampled_pixels = unsafe {
+            slice::from_raw_parts(mapped_resource.pData.cast::<u8>(), samples * multiplyer)
+        };
+        let hash = signature_hash(sampled_pixels);
+
+        unsafe { self.context.Unmap(texture, 0) };
+
+        Ok(hash)
+    }
+
+    /// Copy the frame into a CPU buffer that can outlive the frame and be sent to another thread.
+    ///
+    /// Unlike `to_owned_gpu` this is not a zero-copy path, the frame is copied from the GPU to
+    /// the CPU and its padding is removed, which costs the same as `buffer` plus an allocation.
+    ///
+    /// # Returns
+    ///
+    /// The OwnedFrame containing the pixel data and the metadata of the frame.
+    #[inline]
+    pub fn to_owned(&mut self) -> Result<OwnedFrame, Error> {
+        let time = self.timespan();
+        let width = self.width;
+        let height = self.height;
+        let color_format = self.color_format;
+
+        let mut buffer = self.buffer()?;
+        let buffer = buffer.as_nopadding_buffer()?.to_vec();
+
+        Ok(OwnedFrame::new(buffer, time, width, height, color_format))
+    }
+
+    /// Copy the frame into a GPU texture that can outlive the frame and be sent to another thread.
+    ///
+    /// The copy is done with `CopyResource` on the GPU, the pixel data never goes through the
+    /// CPU. See `OwnedGpuFrame` for the device affinity requirements of the copied texture.
+    ///
+    /// # Returns
+    ///
+    /// The OwnedGpuFrame containing the copied texture.
+    #[inline]
+    pub fn to_owned_gpu(&self) -> Result<OwnedGpuFrame, Error> {
+        // Texture Settings
+        let mut texture_desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { self.frame_texture.GetDesc(&mut texture_desc) };
+        texture_desc.Usage = D3D11_USAGE_DEFAULT;
+        texture_desc.CPUAccessFlags = 0;
+        texture_desc.MiscFlags = 0;
+
+        // Create a texture owned by the frame
+        let mut texture = None;
+        unsafe {
+            self.d3d_device
+                .CreateTexture2D(&texture_desc, None, Some(&mut texture))?;
+        };
+        let texture = texture.unwrap();
+
+        // Copy the real texture to the owned texture
+        unsafe {
+            self.context.CopyResource(&texture, &self.frame_texture);
+        };
+
+        Ok(OwnedGpuFrame::new(
+            texture,
+            self.timespan(),
+            self.width,
+            self.height,
+            self.color_format,
+        ))
+    }
+
+    /// Copy the frame into a texture that can be opened on another Direct3D device.
+    ///
+    /// The frame is copied on the GPU into a texture created with a keyed mutex and an NT shared
+    /// handle. The copy releases the keyed mutex with key `1`, open the handle with
+    /// `ID3D11Device1::OpenSharedResource1` and acquire key `1` before reading the texture.
+    ///
+    /// # Returns
+    ///
+    /// The SharedTexture containing the shared handle and the description of the texture.
+    #[inline]
+    pub fn share_texture(&self) -> Result<SharedTexture, Error> {
+        // Texture Settings
+        let mut texture_desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { self.frame_texture.GetDesc(&mut texture_desc) };
+        texture_desc.Usage = D3D11_USAGE_DEFAULT;
+        texture_desc.CPUAccessFlags = 0;
+        texture_desc.MiscFlags = D3D11_RESOURCE_MISC_SHARED_KEYEDMUTEX.0 as u32
+            | D3D11_RESOURCE_MISC_SHARED_NTHANDLE.0 as u32;
+
+        // Create a texture that can be shared with other devices
+        let mut texture = None;
+        unsafe {
+            self.d3d_device
+                .CreateTexture2D(&texture_desc, None, Some(&mut texture))?;
+        };
+        let texture = texture.unwrap();
+
+        // Copy the real texture to the shared texture while holding the keyed mutex
+        let keyed_mutex = texture.cast::<IDXGIKeyedMutex>()?;
+        unsafe {
+            keyed_mutex.AcquireSync(0, INFINITE)?;
+            self.context.CopyResource(&texture, &self.frame_texture);
+            keyed_mutex.ReleaseSync(1)?;
+        };
+
+        let handle = unsafe {
+            texture.cast::<IDXGIResource1>()?.CreateSharedHandle(
+                None,
+                DXGI_SHARED_RESOURCE_READ.0 | DXGI_SHARED_RESOURCE_WRITE.0,
+                None,
+            )?
+        };
+
+        Ok(SharedTexture {
+            texture: SendDirectX::new(texture),
+            handle,
+            width: self.width,
+            height: self.height,
+            color_format: self.color_format,
+        })
+    }
+
+    /// Save the frame buffer as an image to the specified path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path where the image will be saved.
+    /// * `format` - The ImageFormat of the saved image.
+    ///
+    /// # Returns
+    ///
+    /// An empty Result if successful, or an Error if there was an issue saving the image.
+    #[inline]
+    pub fn save_as_image<T: AsRef<Path>>(
+        &mut self,
+        path: T,
+        format: ImageFormat,
+    ) -> Result<(), Error> {
+        let mut frame_buffer = self.buffer()?;
+
+        frame_buffer.save_as_image(path, format)?;
+
+        Ok(())
     }
 }
 
-/// Represents a frame buffer containing pixel data.
+impl CapturedFrame for Frame<'_> {
+    #[inline]
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    fn color_format(&self) -> ColorFormat {
+        self.color_format
+    }
+
+    #[inline]
+    fn to_rgba_packed(&mut self) -> Result<Vec<u8>, Error> {
+        let (bytes, _, _) = self.buffer()?.to_rgba_packed();
+
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn save_as_image<T: AsRef<Path>>(&mut self, path: T, format: ImageFormat) -> Result<(), Error> {
+        Frame::save_as_image(self, path, format)
+    }
+}
+
+/// Represents a frame copied into a GPU texture that is owned by the frame.
+///
+/// Unlike `Frame`, an `OwnedGpuFrame` is not tied to the frame pool and can be sent to another
+/// thread without a CPU round-trip. `FrameStream::new_gpu` delivers them through an async stream.
+///
+/// The texture was created on the capture's `ID3D11Device` (the `device` of the handler's
+/// `Context`) and can only be used with that device and its contexts. The device itself is free
+/// threaded but its immediate context is not, calls on the immediate context from other threads
+/// must be synchronized by the user.
 ///
 /// # Example
 /// ```ignore
-/// // Get frame from the capture session
-/// let mut buffer = frame.buffer()?;
-/// buffer.save_as_image("screenshot.png", ImageFormat::Png)?;
+/// // Copy the frame inside the frame handler
+/// let gpu_frame = frame.to_owned_gpu()?;
+/// sender.send(gpu_frame)?;
 /// ```
-pub struct FrameBuffer<'a> {
-    raw_buffer: &'a mut [u8],
-    buffer: &'a mut Vec<u8>,
+pub struct OwnedGpuFrame {
+    texture: SendDirectX<ID3D11Texture2D>,
+    time: TimeSpan,
     width: u32,
     height: u32,
-    row_pitch: u32,
-    depth_pitch: u32,
     color_format: ColorFormat,
 }
 
-impl<'a> FrameBuffer<'a> {
-    /// Create a new Frame Buffer.
+impl OwnedGpuFrame {
+    /// Create a new OwnedGpuFrame.
     ///
     /// # Arguments
     ///
-    /// * `raw_buffer` - A mutable reference to the raw pixel data buffer.
-    /// * `buffer` - A mutable reference to the buffer used for copying pixel data without padding.
-    /// * `width` - The width of the frame buffer.
-    /// * `height` - The height of the frame buffer.
-    /// * `row_pitch` - The row pitch of the frame buffer.
-    /// * `depth_pitch` - The depth pitch of the frame buffer.
-    /// * `color_format` - The color format of the frame buffer.
+    /// * `texture` - The ID3D11Texture2D owned by the frame.
+    /// * `time` - The TimeSpan representing the frame time.
+    /// * `width` - The width of the frame.
+    /// * `height` - The height of the frame.
+    /// * `color_format` - The ColorFormat of the frame.
     ///
     /// # Returns
     ///
-    /// A new `FrameBuffer` instance.
+    /// A new OwnedGpuFrame instance.
     #[must_use]
     #[inline]
-    pub fn new(
-        raw_buffer: &'a mut [u8],
-        buffer: &'a mut Vec<u8>,
+    pub const fn new(
+        texture: ID3D11Texture2D,
+        time: TimeSpan,
         width: u32,
         height: u32,
-        row_pitch: u32,
-        depth_pitch: u32,
         color_format: ColorFormat,
     ) -> Self {
         Self {
-            raw_buffer,
-            buffer,
+            texture: SendDirectX::new(texture),
+            time,
             width,
             height,
-            row_pitch,
-            depth_pitch,
             color_format,
         }
     }
 
-    /// Get the width of the frame buffer.
+    /// Get the width of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The width of the frame.
     #[must_use]
     #[inline]
     pub const fn width(&self) -> u32 {
         self.width
     }
 
-    /// Get the height of the frame buffer.
+    /// Get the height of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The height of the frame.
     #[must_use]
     #[inline]
     pub const fn height(&self) -> u32 {
         self.height
     }
 
-    /// Get the row pitch of the frame buffer.
+    /// Get the time of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The time of the frame.
     #[must_use]
     #[inline]
-    pub const fn row_pitch(&self) -> u32 {
-        self.row_pitch
+    pub const fn timespan(&self) -> TimeSpan {
+        self.time
     }
 
-    /// Get the depth pitch of the frame buffer.
+    /// Get the color format of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The color format of the frame.
     #[must_use]
     #[inline]
-    pub const fn depth_pitch(&self) -> u32 {
-        self.depth_pitch
+    pub const fn color_format(&self) -> ColorFormat {
+        self.color_format
     }
 
-    /// Check if the buffer has padding.
+    /// Get the texture of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The ID3D11Texture2D owned by the frame.
     #[must_use]
     #[inline]
-    pub const fn has_padding(&self) -> bool {
-        self.width * 4 != self.row_pitch
+    pub const fn texture(&self) -> &ID3D11Texture2D {
+        &self.texture.0
     }
 
-    /// Get the raw pixel data with possible padding.
+    /// Take the texture out of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The ID3D11Texture2D owned by the frame.
     #[must_use]
     #[inline]
-    pub fn as_raw_buffer(&mut self) -> &mut [u8] {
-        self.raw_buffer
+    pub fn into_texture(self) -> ID3D11Texture2D {
+        self.texture.0
     }
+}
 
-    /// Get the raw pixel data without padding.
+/// Represents a frame copied into a texture that is shared with other Direct3D devices.
+///
+/// The texture is kept alive until the struct is dropped, which also closes the shared handle.
+/// Devices that opened the handle keep their own reference to the texture.
+///
+/// # Example
+/// ```ignore
+/// // Share the frame inside the frame handler
+/// let shared_texture = frame.share_texture()?;
+/// renderer.open_shared(shared_texture.handle())?;
+/// ```
+pub struct SharedTexture {
+    texture: SendDirectX<ID3D11Texture2D>,
+    handle: HANDLE,
+    width: u32,
+    height: u32,
+    color_format: ColorFormat,
+}
+
+impl SharedTexture {
+    /// Get the NT handle of the shared texture.
     ///
     /// # Returns
     ///
-    /// A mutable reference to the buffer containing pixel data without padding.
+    /// The raw shared handle, it is only valid while the SharedTexture is alive.
+    #[must_use]
     #[inline]
-    pub fn as_nopadding_buffer(&mut self) -> Result<&mut [u8], Error> {
-        if !self.has_padding() {
-            return Ok(self.raw_buffer);
-        }
-
-        let multiplyer = match self.color_format {
-            ColorFormat::Rgba16F => 8,
-            ColorFormat::Rgba8 => 4,
-            ColorFormat::Bgra8 => 4,
-        };
-
-        let frame_size = (self.width * self.height * multiplyer) as usize;
-        if self.buffer.capacity() < frame_size {
-            self.buffer.resize(frame_size, 0);
-        }
-
-        let width_size = (self.width * multiplyer) as usize;
-        let buffer_address = self.buffer.as_mut_ptr() as isize;
-        (0..self.height).into_par_iter().for_each(|y| {
-            let index = (y * self.row_pitch) as usize;
-            let ptr = buffer_address as *mut u8;
-
-            unsafe {
-                ptr::copy_nonoverlapping(
-                    self.raw_buffer.as_ptr().add(index),
-                    ptr.add(y as usize * width_size),
-                    width_size,
-                );
-            }
-        });
-
-        Ok(&mut self.buffer[0..frame_size])
+    pub const fn handle(&self) -> *mut std::ffi::c_void {
+        self.handle.0
     }
 
-    /// Save the frame buffer as an image to the specified path.
+    /// Get the width of the texture.
     ///
-    /// # Arguments
+    /// # Returns
     ///
-    /// * `path` - The path where the image will be saved.
-    /// * `format` - The image format to use for saving.
+    /// The width of the texture.
+    #[must_use]
+    #[inline]
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Get the height of the texture.
     ///
     /// # Returns
     ///
-    /// An `Ok` result if the image is successfully saved, or an `Err` result if there was an error.
+    /// The height of the texture.
+    #[must_use]
     #[inline]
-    pub fn save_as_image<T: AsRef<Path>>(
-        &mut self,
-        path: T,
-        format: ImageFormat,
-    ) -> Result<(), Error> {
-        let width = self.width;
-        let height = self.height;
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
 
-        let bytes = ImageEncoder::new(format, self.color_format).encode(
-            self.as_nopadding_buffer()?,
+    /// Get the color format of the texture.
+    ///
+    /// # Returns
+    ///
+    /// The color format of the texture.
+    #[must_use]
+    #[inline]
+    pub const fn color_format(&self) -> ColorFormat {
+        self.color_format
+    }
+
+    /// Get the shared texture on the capture device.
+    ///
+    /// # Returns
+    ///
+    /// The ID3D11Texture2D that is shared.
+    #[must_use]
+    #[inline]
+    pub const fn texture(&self) -> &ID3D11Texture2D {
+        &self.texture.0
+    }
+}
+
+impl Drop for SharedTexture {
+    #[inline]
+    fn drop(&mut self) {
+        let _ = unsafe { CloseHandle(self.handle) };
+    }
+}
+
+unsafe impl Send for SharedTexture {}
+
+/// Represents a frame copied into a CPU buffer without padding that is owned by the frame.
+///
+/// # Example
+/// ```ignore
+/// let frame = capture::screenshot_foreground(ColorFormat::Rgba8, Duration::from_secs(5))?;
+/// frame.save_as_image("screenshot.png", ImageFormat::Png)?;
+/// ```
+pub struct OwnedFrame {
+    buffer: Vec<u8>,
+    time: TimeSpan,
+    width: u32,
+    height: u32,
+    color_format: ColorFormat,
+}
+
+impl OwnedFrame {
+    /// Create a new OwnedFrame.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The pixel data of the frame without padding.
+    /// * `time` - The TimeSpan representing the frame time.
+    /// * `width` - The width of the frame.
+    /// * `height` - The height of the frame.
+    /// * `color_format` - The ColorFormat of the frame.
+    ///
+    /// # Returns
+    ///
+    /// A new OwnedFrame instance.
+    #[must_use]
+    #[inline]
+    pub const fn new(
+        buffer: Vec<u8>,
+        time: TimeSpan,
+        width: u32,
+        height: u32,
+        color_format: ColorFormat,
+    ) -> Self {
+        Self {
+            buffer,
+            time,
             width,
             height,
+            color_format,
+        }
+    }
+
+    /// Get the width of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The width of the frame.
+    #[must_use]
+    #[inline]
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Get the height of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The height of the frame.
+    #[must_use]
+    #[inline]
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Get the time of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The time of the frame.
+    #[must_use]
+    #[inline]
+    pub const fn timespan(&self) -> TimeSpan {
+        self.time
+    }
+
+    /// Get the color format of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The color format of the frame.
+    #[must_use]
+    #[inline]
+    pub const fn color_format(&self) -> ColorFormat {
+        self.color_format
+    }
+
+    /// Get the row pitch of the frame, rows are tightly packed so it is the width in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes of a row of the frame.
+    #[must_use]
+    #[inline]
+    pub const fn row_pitch(&self) -> u32 {
+        let multiplyer = match self.color_format {
+            ColorFormat::Rgba16F => 8,
+            ColorFormat::Rgba8 => 4,
+            ColorFormat::Bgra8 => 4,
+        };
+
+        self.width * multiplyer
+    }
+
+    /// Get the pixel data of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The pixel data of the frame without padding.
+    #[must_use]
+    #[inline]
+    pub fn buffer(&self) -> &[u8] {
+        self.buffer.as_slice()
+    }
+
+    /// Take the pixel data out of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The pixel data of the frame without padding.
+    #[must_use]
+    #[inline]
+    pub fn into_buffer(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    /// Save the frame as an image to the specified path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path where the image will be saved.
+    /// * `format` - The ImageFormat of the saved image.
+    ///
+    /// # Returns
+    ///
+    /// An empty Result if successful, or an Error if there was an issue saving the image.
+    #[inline]
+    pub fn save_as_image<T: AsRef<Path>>(&self, path: T, format: ImageFormat) -> Result<(), Error> {
+        let bytes = ImageEncoder::new(format, self.color_format).encode(
+            &self.buffer,
+            self.width,
+            self.height,
         )?;
 
         fs::write(path, bytes)?;
@@ -559,3 +1478,1566 @@ impl<'a> FrameBuffer<'a> {
         Ok(())
     }
 }
+
+impl CapturedFrame for OwnedFrame {
+    #[inline]
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    fn color_format(&self) -> ColorFormat {
+        self.color_format
+    }
+
+    #[inline]
+    fn to_rgba_packed(&mut self) -> Result<Vec<u8>, Error> {
+        let mut buffer = Vec::new();
+        let row_pitch = self.row_pitch();
+
+        let frame_buffer = FrameBuffer::new(
+            &mut self.buffer,
+            &mut buffer,
+            self.width,
+            self.height,
+            row_pitch,
+            row_pitch * self.height,
+            self.color_format,
+        );
+        let (bytes, _, _) = frame_buffer.to_rgba_packed();
+
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn save_as_image<T: AsRef<Path>>(&mut self, path: T, format: ImageFormat) -> Result<(), Error> {
+        Self::save_as_image(self, path, format)
+    }
+}
+
+/// The latest frame stored in a `SingleBufferSink`.
+struct LatestFrame {
+    buffer: Vec<u8>,
+    time: TimeSpan,
+    width: u32,
+    height: u32,
+    is_new: bool,
+}
+
+/// A sink that hands the latest frame from the frame handler to a consumer without allocating.
+///
+/// The sink double buffers: the frame handler copies each frame into a back buffer and then swaps
+/// it with the front buffer under a short lock, while the consumer reads the front buffer. Both
+/// buffers and the staging texture the frames are read back through are reused, so once they
+/// reached the frame size no further allocation happens on the CPU or the GPU.
+///
+/// There is no queue, the consumer only ever sees the latest frame and frames that arrive before
+/// the consumer reads are overwritten. The sink can be cloned to share it between the frame
+/// handler and the consumer.
+///
+/// # Example
+/// ```ignore
+/// // Inside the frame handler
+/// sink.push(frame)?;
+///
+/// // On the consumer thread
+/// sink.read_latest(|buffer, width, height, _| process(buffer, width, height));
+/// ```
+#[derive(Clone)]
+pub struct SingleBufferSink {
+    back: Arc<Mutex<BackBuffer>>,
+    front: Arc<Mutex<Option<LatestFrame>>>,
+}
+
+/// The buffer of a `SingleBufferSink` the next frame is copied into.
+#[derive(Default)]
+struct BackBuffer {
+    buffer: Vec<u8>,
+    /// The staging texture frames are read back through, reused while the frame size is the same.
+    staging: Option<SendDirectX<StagingTexture>>,
+}
+
+impl SingleBufferSink {
+    /// Create a new empty SingleBufferSink.
+    ///
+    /// # Returns
+    ///
+    /// A new SingleBufferSink instance.
+    #[must_use]
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            back: Arc::new(Mutex::new(BackBuffer::default())),
+            front: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Copy a frame into the back buffer and make it the latest frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The frame to store.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the frame was stored, or an `Error` if copying the frame failed.
+    #[inline]
+    pub fn push(&self, frame: &mut Frame) -> Result<(), Error> {
+        let time = frame.timespan();
+
+        let mut back_guard = self.back.lock();
+        let back = &mut *back_guard;
+        let (width, height) = frame.copy_to_vec_through(&mut back.staging, &mut back.buffer)?;
+
+        let mut front = self.front.lock();
+        match front.as_mut() {
+            Some(latest) => {
+                mem::swap(&mut back.buffer, &mut latest.buffer);
+                latest.time = time;
+                latest.width = width;
+                latest.height = height;
+                latest.is_new = true;
+            }
+            None => {
+                *front = Some(LatestFrame {
+                    buffer: mem::take(&mut back.buffer),
+                    time,
+                    width,
+                    height,
+                    is_new: true,
+                });
+            }
+        }
+        drop(front);
+        drop(back_guard);
+
+        Ok(())
+    }
+
+    /// Read the latest frame.
+    ///
+    /// The frame handler waits for `f` to return before it can swap in a new frame, so `f` should
+    /// be short or copy what it needs.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Called with the packed pixel data, width, height and timestamp of the latest frame.
+    ///
+    /// # Returns
+    ///
+    /// The result of `f`, or `None` if no frame was pushed yet.
+    #[inline]
+    pub fn read_latest<R>(&self, f: impl FnOnce(&[u8], u32, u32, TimeSpan) -> R) -> Option<R> {
+        let mut front = self.front.lock();
+        let latest = front.as_mut()?;
+        latest.is_new = false;
+
+        let result = f(&latest.buffer, latest.width, latest.height, latest.time);
+        drop(front);
+
+        Some(result)
+    }
+
+    /// Check if a frame was pushed since the last `read_latest`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the latest frame has not been read yet, `false` otherwise.
+    #[must_use]
+    #[inline]
+    pub fn has_new_frame(&self) -> bool {
+        self.front
+            .lock()
+            .as_ref()
+            .is_some_and(|latest| latest.is_new)
+    }
+}
+
+impl Default for SingleBufferSink {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Represents a frame buffer containing pixel data.
+///
+/// # Example
+/// ```ignore
+/// // Get frame from the capture session
+/// let mut buffer = frame.buffer()?;
+/// buffer.save_as_image("screenshot.png", ImageFormat::Png)?;
+/// ```
+pub struct FrameBuffer<'a> {
+    raw_buffer: &'a mut [u8],
+    buffer: &'a mut Vec<u8>,
+    width: u32,
+    height: u32,
+    row_pitch: u32,
+    depth_pitch: u32,
+    color_format: ColorFormat,
+    write_back: Option<WriteBackTarget<'a>>,
+}
+
+// Staging Texture Of A Frame Buffer And The Frame Texture It Was Copied From
+struct WriteBackTarget<'a> {
+    context: ID3D11DeviceContext,
+    staging: ID3D11Texture2D,
+    // Mapped State Of A Reusable `StagingTexture`, Kept In Sync When The Texture Is Remapped
+    is_mapped: Option<&'a mut bool>,
+    destination: ID3D11Texture2D,
+    x: u32,
+    y: u32,
+}
+
+impl<'a> FrameBuffer<'a> {
+    /// Create a new Frame Buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw_buffer` - A mutable reference to the raw pixel data buffer.
+    /// * `buffer` - A mutable reference to the buffer used for copying pixel data without padding.
+    /// * `width` - The width of the frame buffer.
+    /// * `height` - The height of the frame buffer.
+    /// * `row_pitch` - The row pitch of the frame buffer.
+    /// * `depth_pitch` - The depth pitch of the frame buffer.
+    /// * `color_format` - The color format of the frame buffer.
+    ///
+    /// # Returns
+    ///
+    /// A new `FrameBuffer` instance.
+    #[must_use]
+    #[inline]
+    pub fn new(
+        raw_buffer: &'a mut [u8],
+        buffer: &'a mut Vec<u8>,
+        width: u32,
+        height: u32,
+        row_pitch: u32,
+        depth_pitch: u32,
+        color_format: ColorFormat,
+    ) -> Self {
+        Self {
+            raw_buffer,
+            buffer,
+            width,
+            height,
+            row_pitch,
+            depth_pitch,
+            color_format,
+            write_back: None,
+        }
+    }
+
+    // Set The Frame Texture The Buffer Is Written Back To
+    fn with_write_back(mut self, target: WriteBackTarget<'a>) -> Self {
+        self.write_back = Some(target);
+        self
+    }
+
+    /// Get the width of the frame buffer.
+    #[must_use]
+    #[inline]
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Get the height of the frame buffer.
+    #[must_use]
+    #[inline]
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Get the row pitch of the frame buffer.
+    #[must_use]
+    #[inline]
+    pub const fn row_pitch(&self) -> u32 {
+        self.row_pitch
+    }
+
+    /// Get the depth pitch of the frame buffer.
+    #[must_use]
+    #[inline]
+    pub const fn depth_pitch(&self) -> u32 {
+        self.depth_pitch
+    }
+
+    /// Check if the buffer has padding.
+    #[must_use]
+    #[inline]
+    pub const fn has_padding(&self) -> bool {
+        self.width * 4 != self.row_pitch
+    }
+
+    /// Get the raw pixel data with possible padding.
+    #[must_use]
+    #[inline]
+    pub fn as_raw_buffer(&mut self) -> &mut [u8] {
+        self.raw_buffer
+    }
+
+    /// Set the alpha channel of every pixel to fully opaque.
+    ///
+    /// Captured alpha values are undefined for most windows, this makes sure buffers that are
+    /// saved or sent to an encoder are treated as opaque. The padding of the rows is left as is.
+    /// This only changes the CPU buffer, `Settings::with_force_opaque` makes every frame opaque
+    /// on the GPU instead, which also covers frames sent to an encoder without a CPU copy.
+    #[inline]
+    pub fn force_opaque(&mut self) {
+        // Alpha is the last channel of a pixel, 1.0 in half precision for Rgba16F
+        let (multiplyer, opaque): (usize, &[u8]) = match self.color_format {
+            ColorFormat::Rgba16F => (8, &[0x00, 0x3C]),
+            ColorFormat::Rgba8 => (4, &[0xFF]),
+            ColorFormat::Bgra8 => (4, &[0xFF]),
+        };
+
+        let width_size = self.width as usize * multiplyer;
+        let row_pitch = self.row_pitch as usize;
+        let height = self.height as usize;
+        self.raw_buffer
+            .par_chunks_mut(row_pitch)
+            .take(height)
+            .for_each(|row| {
+                for pixel in row[..width_size].chunks_exact_mut(multiplyer) {
+                    pixel[multiplyer - opaque.len()..].copy_from_slice(opaque);
+                }
+            });
+    }
+
+    /// Get a view of the pixel data that handles the row pitch for 2D indexing.
+    ///
+    /// # Returns
+    ///
+    /// A FrameView over the raw pixel data.
+    #[must_use]
+    #[inline]
+    pub const fn view(&self) -> FrameView<'_> {
+        let multiplyer = match self.color_format {
+            ColorFormat::Rgba16F => 8,
+            ColorFormat::Rgba8 => 4,
+            ColorFormat::Bgra8 => 4,
+        };
+
+        FrameView::new(
+            self.raw_buffer,
+            self.width,
+            self.height,
+            self.row_pitch,
+            multiplyer,
+        )
+    }
+
+    /// Copy the modified pixel data back to the frame texture.
+    ///
+    /// The buffer is backed by a mapped staging texture, so edits to it (e.g. redacting a region)
+    /// only reach the GPU once written back. After this call the frame surface contains the
+    /// edited pixels, so a following `VideoEncoder::send_frame` encodes them. A cropped buffer is
+    /// written back to the same region it was copied from.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the buffer was written back, or `Error::WriteBackUnsupported` if the buffer was
+    /// not created from a frame. If the staging texture can't be mapped again after the copy, the
+    /// buffer is left empty with a width and height of `0` and the error is returned.
+    #[inline]
+    pub fn write_back(&mut self) -> Result<(), Error> {
+        let Some(target) = &mut self.write_back else {
+            return Err(Error::WriteBackUnsupported);
+        };
+
+        // The staging texture must be unmapped before the GPU can copy from it, the buffer
+        // points into the mapping so it must not be used until the texture is mapped again
+        self.raw_buffer = &mut [];
+        unsafe { target.context.Unmap(&target.staging, 0) };
+        if let Some(is_mapped) = &mut target.is_mapped {
+            **is_mapped = false;
+        }
+
+        unsafe {
+            target.context.CopySubresourceRegion(
+                &target.destination,
+                0,
+                target.x,
+                target.y,
+                0,
+                &target.staging,
+                0,
+                None,
+            );
+        };
+
+        // Map the texture again so the buffer stays usable, if that fails the buffer stays empty
+        let mut mapped_resource = D3D11_MAPPED_SUBRESOURCE::default();
+        if let Err(error) = unsafe {
+            target.context.Map(
+                &target.staging,
+                0,
+                D3D11_MAP_READ_WRITE,
+                0,
+                Some(&mut mapped_resource),
+            )
+        } {
+            self.write_back = None;
+            self.width = 0;
+            self.height = 0;
+            self.row_pitch = 0;
+            self.depth_pitch = 0;
+
+            return Err(Error::WindowsError(error));
+        }
+        if let Some(is_mapped) = &mut target.is_mapped {
+            **is_mapped = true;
+        }
+
+        self.raw_buffer = unsafe {
+            slice::from_raw_parts_mut(
+                mapped_resource.pData.cast(),
+                (self.height * mapped_resource.RowPitch) as usize,
+            )
+        };
+        self.row_pitch = mapped_resource.RowPitch;
+        self.depth_pitch = mapped_resource.DepthPitch;
+
+        Ok(())
+    }
+
+    /// Compute the CRC-32 checksum of the pixel data without padding.
+    ///
+    /// The checksum uses the IEEE polynomial, the same as zlib and PNG, so it can be verified by
+    /// the receiver of the packed buffer with any standard CRC-32 implementation.
+    ///
+    /// # Returns
+    ///
+    /// The CRC-32 checksum of the packed pixel data.
+    #[must_use]
+    #[inline]
+    pub fn crc32(&self) -> u32 {
+        let view = self.view();
+
+        crc32((0..self.height).filter_map(|y| view.row(y)).flatten())
+    }
+
+    /// Get the raw pixel data without padding.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the buffer containing pixel data without padding.
+    #[inline]
+    pub fn as_nopadding_buffer(&mut self) -> Result<&mut [u8], Error> {
+        if !self.has_padding() {
+            return Ok(self.raw_buffer);
+        }
+
+        let multiplyer = match self.color_format {
+            ColorFormat::Rgba16F => 8,
+            ColorFormat::Rgba8 => 4,
+            ColorFormat::Bgra8 => 4,
+        };
+
+        let frame_size = (self.width * self.height * multiplyer) as usize;
+        if self.buffer.capacity() < frame_size {
+            self.buffer.resize(frame_size, 0);
+        }
+
+        let width_size = (self.width * multiplyer) as usize;
+        let buffer_address = self.buffer.as_mut_ptr() as isize;
+        (0..self.height).into_par_iter().for_each(|y| {
+            let index = (y * self.row_pitch) as usize;
+            let ptr = buffer_address as *mut u8;
+
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    self.raw_buffer.as_ptr().add(index),
+                    ptr.add(y as usize * width_size),
+                    width_size,
+                );
+            }
+        });
+
+        Ok(&mut self.buffer[0..frame_size])
+    }
+
+    /// Convert the pixel data to another color format without padding.
+    ///
+    /// The red and blue channels are swapped for `Bgra8` to `Rgba8` conversions and the other way
+    /// around, the padding is stripped in the same pass. Converting to the same color format only
+    /// strips the padding.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The color format to convert the pixel data to.
+    /// * `out` - The buffer used to store the converted pixel data, resized only if it is too
+    ///   small.
+    ///
+    /// # Returns
+    ///
+    /// The converted pixel data, or `Error::UnsupportedConversion` if the conversion involves
+    /// `Rgba16F`.
+    #[inline]
+    pub fn convert_to<'b>(
+        &self,
+        target: ColorFormat,
+        out: &'b mut Vec<u8>,
+    ) -> Result<&'b [u8], Error> {
+        let swap = match (self.color_format, target) {
+            (source, target) if source == target => false,
+            (ColorFormat::Bgra8, ColorFormat::Rgba8) | (ColorFormat::Rgba8, ColorFormat::Bgra8) => {
+                true
+            }
+            _ => return Err(Error::UnsupportedConversion),
+        };
+
+        let multiplyer = match self.color_format {
+            ColorFormat::Rgba16F => 8,
+            ColorFormat::Rgba8 => 4,
+            ColorFormat::Bgra8 => 4,
+        };
+
+        let width_size = (self.width * multiplyer) as usize;
+        let frame_size = width_size * self.height as usize;
+        if out.len() < frame_size {
+            out.resize(frame_size, 0);
+        }
+
+        let raw_buffer = &*self.raw_buffer;
+        let row_pitch = self.row_pitch as usize;
+        out[0..frame_size]
+            .par_chunks_exact_mut(width_size)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let index = y * row_pitch;
+                let source = &raw_buffer[index..index + width_size];
+
+                if swap {
+                    swap_red_blue(row, source);
+                } else {
+                    row.copy_from_slice(source);
+                }
+            });
+
+        Ok(&out[0..frame_size])
+    }
+
+    /// Convert the pixel data to top-down packed 8-bit RGBA.
+    ///
+    /// This is the layout most image libraries accept, for example
+    /// `image::RgbaImage::from_raw(width, height, bytes)`. The padding is stripped, `Bgra8` is
+    /// swizzled and the linear color channels of `Rgba16F` are encoded with the sRGB transfer
+    /// function, then clamped to `[0, 1]` and quantized without any tone mapping. The alpha
+    /// channel stays linear.
+    ///
+    /// # Returns
+    ///
+    /// The packed pixel data together with the width and height of the frame.
+    #[must_use]
+    #[inline]
+    pub fn to_rgba_packed(&self) -> (Vec<u8>, u32, u32) {
+        let mut out = Vec::new();
+
+        match self.color_format {
+            ColorFormat::Rgba8 | ColorFormat::Bgra8 => {
+                self.convert_to(ColorFormat::Rgba8, &mut out)
+                    .expect("Bgra8 and Rgba8 always convert to Rgba8");
+            }
+            ColorFormat::Rgba16F => {
+                let width = self.width as usize;
+                let row_pitch = self.row_pitch as usize;
+                out.resize(width * self.height as usize * 4, 0);
+
+                let raw_buffer = &*self.raw_buffer;
+                out.par_chunks_exact_mut(width * 4)
+                    .enumerate()
+                    .for_each(|(y, row)| {
+                        let index = y * row_pitch;
+                        let source = &raw_buffer[index..index + width * 8];
+
+                        for (target, source) in row.chunks_exact_mut(4).zip(source.chunks_exact(8))
+                        {
+                            for (channel, (target, source)) in
+                                target.iter_mut().zip(source.chunks_exact(2)).enumerate()
+                            {
+                                let value = half_to_f32(u16::from_le_bytes([source[0], source[1]]));
+                                let value = if channel == 3 {
+                                    value
+                                } else {
+                                    linear_to_srgb(value)
+                                };
+                                *target = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                            }
+                        }
+                    });
+            }
+        }
+
+        (out, self.width, self.height)
+    }
+
+    /// Save the frame buffer as an image to the specified path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path where the image will be saved.
+    /// * `format` - The image format to use for saving.
+    ///
+    /// # Returns
+    ///
+    /// An `Ok` result if the image is successfully saved, or an `Err` result if there was an error.
+    #[inline]
+    pub fn save_as_image<T: AsRef<Path>>(
+        &mut self,
+        path: T,
+        format: ImageFormat,
+    ) -> Result<(), Error> {
+        let width = self.width;
+        let height = self.height;
+
+        let bytes = ImageEncoder::new(format, self.color_format).encode(
+            self.as_nopadding_buffer()?,
+            width,
+            height,
+        )?;
+
+        fs::write(path, bytes)?;
+
+        Ok(())
+    }
+}
+
+/// Represents a read-only view of padded pixel data with 2D indexing.
+///
+/// # Example
+/// ```ignore
+/// let buffer = frame.buffer()?;
+/// let view = buffer.view();
+/// let pixel = view.get(10, 20).unwrap();
+/// ```
+pub struct FrameView<'a> {
+    buffer: &'a [u8],
+    width: u32,
+    height: u32,
+    row_pitch: u32,
+    bytes_per_pixel: u32,
+}
+
+impl<'a> FrameView<'a> {
+    /// Create a new FrameView.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The raw pixel data with possible padding.
+    /// * `width` - The width of the frame.
+    /// * `height` - The height of the frame.
+    /// * `row_pitch` - The number of bytes between the start of two rows.
+    /// * `bytes_per_pixel` - The number of bytes of a single pixel.
+    ///
+    /// # Returns
+    ///
+    /// A new FrameView instance.
+    #[must_use]
+    #[inline]
+    pub const fn new(
+        buffer: &'a [u8],
+        width: u32,
+        height: u32,
+        row_pitch: u32,
+        bytes_per_pixel: u32,
+    ) -> Self {
+        Self {
+            buffer,
+            width,
+            height,
+            row_pitch,
+            bytes_per_pixel,
+        }
+    }
+
+    /// Get the width of the frame.
+    #[must_use]
+    #[inline]
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Get the height of the frame.
+    #[must_use]
+    #[inline]
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Get the number of bytes of a single pixel.
+    #[must_use]
+    #[inline]
+    pub const fn bytes_per_pixel(&self) -> u32 {
+        self.bytes_per_pixel
+    }
+
+    /// Get a row of pixels without padding.
+    ///
+    /// # Arguments
+    ///
+    /// * `y` - The row index.
+    ///
+    /// # Returns
+    ///
+    /// The pixel data of the row, or `None` if the row is out of bounds.
+    #[must_use]
+    #[inline]
+    pub fn row(&self, y: u32) -> Option<&'a [u8]> {
+        if y >= self.height {
+            return None;
+        }
+
+        let index = (y * self.row_pitch) as usize;
+        let width_size = (self.width * self.bytes_per_pixel) as usize;
+
+        self.buffer.get(index..index + width_size)
+    }
+
+    /// Get a single pixel.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The column index.
+    /// * `y` - The row index.
+    ///
+    /// # Returns
+    ///
+    /// The bytes of the pixel, or `None` if the pixel is out of bounds.
+    #[must_use]
+    #[inline]
+    pub fn get(&self, x: u32, y: u32) -> Option<&'a [u8]> {
+        if x >= self.width {
+            return None;
+        }
+
+        let index = (x * self.bytes_per_pixel) as usize;
+
+        self.row(y)?
+            .get(index..index + self.bytes_per_pixel as usize)
+    }
+}
+
+// Copy A Frame Texture To A Staging Texture And Map It For CPU Access
+fn map_frame_copy(
+    context: &ID3D11DeviceContext,
+    frame_texture: &ID3D11Texture2D,
+    staging: &mut StagingTexture,
+) -> Result<D3D11_MAPPED_SUBRESOURCE, Error> {
+    staging.unmap(context);
+
+    // Copy the real texture to copy texture
+    unsafe { context.CopyResource(staging.texture(), frame_texture) };
+
+    // Map the texture to enable CPU access
+    let mut mapped_resource = D3D11_MAPPED_SUBRESOURCE::default();
+    unsafe {
+        context.Map(
+            staging.texture(),
+            0,
+            D3D11_MAP_READ_WRITE,
+            0,
+            Some(&mut mapped_resource),
+        )?;
+    };
+    staging.set_mapped();
+
+    Ok(mapped_resource)
+}
+
+// Swap The Red And Blue Channels Of 8-Bit Pixels, Converting Between Bgra8 And Rgba8
+pub(crate) fn swap_red_blue(target: &mut [u8], source: &[u8]) {
+    for (target, source) in target.chunks_exact_mut(4).zip(source.chunks_exact(4)) {
+        target[0] = source[2];
+        target[1] = source[1];
+        target[2] = source[0];
+        target[3] = source[3];
+    }
+}
+
+// Pseudo-Random Pixel Positions Of A Frame Signature, They Only Depend On The Frame Size
+fn signature_positions(
+    width: u32,
+    height: u32,
+    samples: usize,
+) -> impl Iterator<Item = (u32, u32)> {
+    let width = u64::from(width);
+    let pixel_count = width * u64::from(height);
+
+    // Xorshift state seeded from the frame size so the positions are stable per size
+    let mut state = ((width << 32) | u64::from(height)) ^ 0x9E37_79B9_7F4A_7C15;
+
+    (0..if pixel_count == 0 { 0 } else { samples }).map(move |_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+
+        let pixel = state % pixel_count;
+        ((pixel % width) as u32, (pixel / width) as u32)
+    })
+}
+
+// FNV-1a Hash Of The Sampled Pixels Of A Frame Signature
+fn signature_hash(sampled_pixels: &[u8]) -> u64 {
+    sampled_pixels
+        .iter()
+        .fold(0xCBF2_9CE4_8422_2325, |hash: u64, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01B3)
+        })
+}
+
+// CRC-32 Checksum With The IEEE Polynomial
+fn crc32<'b>(bytes: impl IntoIterator<Item = &'b u8>) -> u32 {
+    !bytes.into_iter().fold(u32::MAX, |crc, byte| {
+        CRC32_TABLE[((crc ^ u32::from(*byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+// Lookup Table Of The Reflected IEEE CRC-32 Polynomial
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xEDB8_8320
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+};
+
+// Convert A Single Precision Float To A Half Precision Float, Rounded To Nearest Even
+const fn f32_to_half(value: f32) -> u16 {
+    // Shift A Mantissa Right Rounding To Nearest Even
+    const fn round_shift(mantissa: u32, shift: u32) -> u32 {
+        let shifted = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+
+        if remainder > halfway || (remainder == halfway && shifted & 1 == 1) {
+            shifted + 1
+        } else {
+            shifted
+        }
+    }
+
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x007F_FFFF;
+
+    // Infinity And NaN
+    if exponent == 0xFF {
+        return sign | 0x7C00 | if mantissa == 0 { 0 } else { 0x0200 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1F {
+        sign | 0x7C00
+    } else if exponent > 0 {
+        // A Carry Out Of The Mantissa Increments The Exponent, Up To Infinity
+        let half = ((exponent as u32) << 10) + round_shift(mantissa, 13);
+        sign | half as u16
+    } else if exponent >= -10 {
+        // Subnormal, The Implicit Leading Bit Becomes Part Of The Mantissa
+        let half = round_shift(mantissa | 0x0080_0000, (14 - exponent) as u32);
+        sign | half as u16
+    } else {
+        sign
+    }
+}
+
+// Decode An sRGB Encoded Color Channel To A Linear Value
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Encode A Linear Color Channel With The sRGB Transfer Function
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055f32.mul_add(value.powf(1.0 / 2.4), -0.055)
+    }
+}
+
+// Convert A Half Precision Float To A Single Precision Float
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exponent = i32::from((half >> 10) & 0x1F);
+    let mantissa = f32::from(half & 0x03FF);
+
+    match exponent {
+        0 => sign * mantissa * 2.0_f32.powi(-24),
+        0x1F if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2.0_f32.powi(exponent - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use windows::Win32::{
+        Graphics::{
+            Direct3D11::{D3D11_BIND_SHADER_RESOURCE, D3D11_SUBRESOURCE_DATA},
+            Dxgi::IDXGISurface,
+        },
+        System::WinRT::Direct3D11::CreateDirect3D11SurfaceFromDXGISurface,
+    };
+
+    use super::*;
+    use crate::d3d11::create_d3d_device;
+
+    // Create A Bgra8 Frame Texture Filled With The Given Pixels
+    fn frame_texture(
+        d3d_device: &ID3D11Device,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> (ID3D11Texture2D, IDirect3DSurface) {
+        let texture_desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT(ColorFormat::Bgra8 as i32),
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as u32,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
+        };
+        let initial_data = D3D11_SUBRESOURCE_DATA {
+            pSysMem: pixels.as_ptr().cast(),
+            SysMemPitch: width * 4,
+            SysMemSlicePitch: 0,
+        };
+
+        let mut texture = None;
+        unsafe {
+            d3d_device
+                .CreateTexture2D(&texture_desc, Some(&initial_data), Some(&mut texture))
+                .unwrap();
+        };
+        let texture = texture.unwrap();
+
+        let surface = unsafe {
+            CreateDirect3D11SurfaceFromDXGISurface(&texture.cast::<IDXGISurface>().unwrap())
+                .unwrap()
+                .cast::<IDirect3DSurface>()
+                .unwrap()
+        };
+
+        (texture, surface)
+    }
+
+    #[test]
+    fn signature_positions_depend_only_on_size() {
+        let positions = signature_positions(64, 32, 100).collect::<Vec<_>>();
+
+        assert_eq!(positions.len(), 100);
+        assert!(positions.iter().all(|&(x, y)| x < 64 && y < 32));
+        assert_eq!(
+            positions,
+            signature_positions(64, 32, 100).collect::<Vec<_>>()
+        );
+        assert_ne!(
+            positions,
+            signature_positions(32, 64, 100).collect::<Vec<_>>()
+        );
+        assert_eq!(signature_positions(0, 32, 100).count(), 0);
+    }
+
+    #[test]
+    fn signature_hash_fnv1a() {
+        assert_eq!(signature_hash(&[]), 0xCBF2_9CE4_8422_2325);
+        assert_eq!(signature_hash(b"a"), 0xAF63_DC4C_8601_EC8C);
+        assert_ne!(signature_hash(&[0, 0, 0, 0]), signature_hash(&[0, 0, 0, 1]));
+    }
+
+    #[test]
+    fn sample_signature_unchanged_frames() {
+        let (d3d_device, context) = create_d3d_device().unwrap();
+        let pixels = (0..64 * 32 * 4)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<u8>>();
+        let mut changed_pixels = pixels.clone();
+        changed_pixels.iter_mut().for_each(|byte| *byte = !*byte);
+
+        let mut staging_cache = StagingCache::default();
+        let mut buffer = Vec::new();
+        let mut signature = |pixels: &[u8], staging_cache: &mut StagingCache| {
+            let (texture, surface) = frame_texture(&d3d_device, 64, 32, pixels);
+            let mut frame = Frame::new(
+                &d3d_device,
+                surface,
+                texture,
+                TimeSpan::default(),
+                &context,
+                &mut buffer,
+                64,
+                32,
+                ColorFormat::Bgra8,
+            );
+            frame.set_staging_cache(staging_cache);
+            frame.sample_signature(256).unwrap()
+        };
+
+        let first = signature(&pixels, &mut staging_cache);
+        let second = signature(&pixels, &mut staging_cache);
+        let changed = signature(&changed_pixels, &mut staging_cache);
+
+        assert_eq!(first, second);
+        assert_ne!(first, changed);
+    }
+
+    #[test]
+    fn copy_to_vec_reuses_cached_staging_texture() {
+        let (d3d_device, context) = create_d3d_device().unwrap();
+        let pixels = (0..64 * 32 * 4)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<u8>>();
+        let mut changed_pixels = pixels.clone();
+        changed_pixels.iter_mut().for_each(|byte| *byte = !*byte);
+
+        let mut staging_cache = StagingCache::default();
+        let mut buffer = Vec::new();
+        let mut out = Vec::new();
+        let mut copy = |pixels: &[u8], staging_cache: &mut StagingCache, out: &mut Vec<u8>| {
+            let (texture, surface) = frame_texture(&d3d_device, 64, 32, pixels);
+            let mut frame = Frame::new(
+                &d3d_device,
+                surface,
+                texture,
+                TimeSpan::default(),
+                &context,
+                &mut buffer,
+                64,
+                32,
+                ColorFormat::Bgra8,
+            );
+            frame.set_staging_cache(staging_cache);
+            frame.copy_to_vec(out).unwrap()
+        };
+
+        assert_eq!(copy(&pixels, &mut staging_cache, &mut out), (64, 32));
+        assert_eq!(out, pixels);
+        let staging = staging_cache.frame.as_ref().unwrap().0.texture().clone();
+
+        assert_eq!(
+            copy(&changed_pixels, &mut staging_cache, &mut out),
+            (64, 32)
+        );
+        assert_eq!(out, changed_pixels);
+        assert_eq!(staging_cache.frame.as_ref().unwrap().0.texture(), &staging);
+    }
+
+    #[test]
+    fn write_back_edits_frame_texture() {
+        let (d3d_device, context) = create_d3d_device().unwrap();
+        let pixels = (0..64 * 32 * 4)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<u8>>();
+        let (texture, surface) = frame_texture(&d3d_device, 64, 32, &pixels);
+
+        let mut buffer = Vec::new();
+        let mut frame = Frame::new(
+            &d3d_device,
+            surface,
+            texture,
+            TimeSpan::default(),
+            &context,
+            &mut buffer,
+            64,
+            32,
+            ColorFormat::Bgra8,
+        );
+
+        // Black out a 4x2 region at (8, 4)
+        let mut staging = StagingTexture::new(&d3d_device, 64, 32, ColorFormat::Bgra8).unwrap();
+        let mut expected = pixels;
+        {
+            let mut frame_buffer = frame.buffer_with(&mut staging).unwrap();
+            let row_pitch = frame_buffer.row_pitch() as usize;
+            let raw_buffer = frame_buffer.as_raw_buffer();
+            for y in 4..6 {
+                raw_buffer[y * row_pitch + 8 * 4..y * row_pitch + 12 * 4].fill(0);
+                expected[y * 64 * 4 + 8 * 4..y * 64 * 4 + 12 * 4].fill(0);
+            }
+
+            frame_buffer.write_back().unwrap();
+
+            // The buffer is mapped again and still shows the edit
+            assert_eq!(frame_buffer.view().get(8, 4), Some(&[0, 0, 0, 0][..]));
+        }
+
+        let mut out = Vec::new();
+        frame.copy_to_vec(&mut out).unwrap();
+        assert_eq!(out, expected);
+
+        // The staging texture can be reused after writing back
+        let mut frame_buffer = frame.buffer_with(&mut staging).unwrap();
+        assert_eq!(frame_buffer.as_nopadding_buffer().unwrap(), &expected[..]);
+    }
+
+    #[test]
+    fn owned_gpu_frame_is_read_on_another_thread() {
+        let (d3d_device, context) = create_d3d_device().unwrap();
+        let pixels = (0..64 * 32 * 4)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<u8>>();
+        let (texture, surface) = frame_texture(&d3d_device, 64, 32, &pixels);
+
+        let mut buffer = Vec::new();
+        let frame = Frame::new(
+            &d3d_device,
+            surface,
+            texture,
+            TimeSpan { Duration: 42 },
+            &context,
+            &mut buffer,
+            64,
+            32,
+            ColorFormat::Bgra8,
+        );
+        let gpu_frame = frame.to_owned_gpu().unwrap();
+
+        // Copy the texture into a CPU readable texture on the device it belongs to
+        let out = thread::spawn(move || {
+            assert_eq!(gpu_frame.timespan().Duration, 42);
+
+            let texture = gpu_frame.texture();
+            let d3d_device = unsafe { texture.GetDevice().unwrap() };
+            let context = unsafe { d3d_device.GetImmediateContext().unwrap() };
+            let staging = StagingTexture::new(
+                &d3d_device,
+                gpu_frame.width(),
+                gpu_frame.height(),
+                gpu_frame.color_format(),
+            )
+            .unwrap();
+
+            let mut mapped_resource = D3D11_MAPPED_SUBRESOURCE::default();
+            unsafe {
+                context.CopyResource(staging.texture(), texture);
+                context
+                    .Map(
+                        staging.texture(),
+                        0,
+                        D3D11_MAP_READ,
+                        0,
+                        Some(&mut mapped_resource),
+                    )
+                    .unwrap();
+            };
+
+            let row_pitch = mapped_resource.RowPitch as usize;
+            let mapped = unsafe {
+                slice::from_raw_parts(mapped_resource.pData.cast::<u8>(), row_pitch * 32)
+            };
+            let out = mapped
+                .chunks(row_pitch)
+                .flat_map(|row| &row[..64 * 4])
+                .copied()
+                .collect::<Vec<u8>>();
+            unsafe { context.Unmap(staging.texture(), 0) };
+
+            out
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(out, pixels);
+    }
+
+    #[test]
+    fn single_buffer_sink_reuses_buffers() {
+        let (d3d_device, context) = create_d3d_device().unwrap();
+        let sink = SingleBufferSink::new();
+
+        let mut buffer = Vec::new();
+        let mut push = |value: u8| {
+            let pixels = vec![value; 64 * 32 * 4];
+            let (texture, surface) = frame_texture(&d3d_device, 64, 32, &pixels);
+            let mut frame = Frame::new(
+                &d3d_device,
+                surface,
+                texture,
+                TimeSpan::default(),
+                &context,
+                &mut buffer,
+                64,
+                32,
+                ColorFormat::Bgra8,
+            );
+            sink.push(&mut frame).unwrap();
+        };
+        let allocations = |sink: &SingleBufferSink| {
+            let back = sink.back.lock();
+            let front = sink.front.lock();
+            let mut buffers = [
+                back.buffer.as_ptr() as usize,
+                front.as_ref().unwrap().buffer.as_ptr() as usize,
+            ];
+            drop(front);
+            buffers.sort_unstable();
+
+            let staging = back.staging.as_ref().unwrap().0.texture().clone();
+            drop(back);
+
+            (buffers, staging)
+        };
+
+        // The first two frames allocate the back and the front buffer
+        push(1);
+        push(2);
+        let (buffers, staging) = allocations(&sink);
+
+        for value in 3..8 {
+            push(value);
+            let (next_buffers, next_staging) = allocations(&sink);
+
+            assert_eq!(next_buffers, buffers);
+            assert_eq!(next_staging, staging);
+            assert_eq!(
+                sink.read_latest(|buffer, _, _, _| buffer.iter().all(|byte| *byte == value)),
+                Some(true)
+            );
+        }
+    }
+
+    #[test]
+    fn convert_to_round_trip() {
+        // 3x2 Bgra8 pixels with 4 bytes of padding at the end of every row
+        let mut raw_buffer = (0..32).collect::<Vec<u8>>();
+        let expected = raw_buffer
+            .chunks_exact(16)
+            .flat_map(|row| row[..12].to_vec())
+            .collect::<Vec<u8>>();
+
+        let mut buffer = Vec::new();
+        let bgra = FrameBuffer::new(
+            &mut raw_buffer,
+            &mut buffer,
+            3,
+            2,
+            16,
+            32,
+            ColorFormat::Bgra8,
+        );
+        let mut rgba = Vec::new();
+        let mut rgba = bgra
+            .convert_to(ColorFormat::Rgba8, &mut rgba)
+            .unwrap()
+            .to_vec();
+        assert_eq!(&rgba[0..4], &[2, 1, 0, 3]);
+
+        let mut buffer = Vec::new();
+        let rgba = FrameBuffer::new(&mut rgba, &mut buffer, 3, 2, 12, 24, ColorFormat::Rgba8);
+        let mut bgra = Vec::new();
+        let bgra = rgba.convert_to(ColorFormat::Bgra8, &mut bgra).unwrap();
+
+        assert_eq!(bgra, expected.as_slice());
+    }
+
+    #[test]
+    fn frame_view_skips_row_padding() {
+        // 2x2 Bgra8 pixels with 4 bytes of padding at the end of every row
+        let buffer = (0..24).collect::<Vec<u8>>();
+        let view = FrameView::new(&buffer, 2, 2, 12, 4);
+
+        assert_eq!(view.row(0), Some(&buffer[0..8]));
+        assert_eq!(view.row(1), Some(&buffer[12..20]));
+        assert_eq!(view.row(2), None);
+
+        assert_eq!(view.get(1, 0), Some(&buffer[4..8]));
+        assert_eq!(view.get(0, 1), Some(&buffer[12..16]));
+        assert_eq!(view.get(2, 0), None);
+        assert_eq!(view.get(0, 2), None);
+    }
+
+    #[test]
+    fn frame_buffer_view_rgba16f() {
+        let mut raw_buffer = (0..32).collect::<Vec<u8>>();
+        let mut buffer = Vec::new();
+        let frame_buffer = FrameBuffer::new(
+            &mut raw_buffer,
+            &mut buffer,
+            1,
+            2,
+            16,
+            32,
+            ColorFormat::Rgba16F,
+        );
+        let view = frame_buffer.view();
+
+        assert_eq!(view.bytes_per_pixel(), 8);
+        assert_eq!(view.get(0, 1), Some(&(16..24).collect::<Vec<u8>>()[..]));
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn frame_buffer_crc32_skips_row_padding() {
+        // 2x2 Rgba8 pixels with 8 bytes of padding at the end of every row
+        let mut raw_buffer = (0..32).collect::<Vec<u8>>();
+        let packed = raw_buffer
+            .chunks_exact(16)
+            .flat_map(|row| row[..8].to_vec())
+            .collect::<Vec<u8>>();
+
+        let mut buffer = Vec::new();
+        let frame_buffer = FrameBuffer::new(
+            &mut raw_buffer,
+            &mut buffer,
+            2,
+            2,
+            16,
+            32,
+            ColorFormat::Rgba8,
+        );
+
+        assert_eq!(frame_buffer.crc32(), crc32(&packed));
+    }
+
+    #[test]
+    fn f32_to_half_values() {
+        assert_eq!(f32_to_half(0.0), 0x0000);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+        assert_eq!(f32_to_half(1.0), 0x3C00);
+        assert_eq!(f32_to_half(0.5), 0x3800);
+        assert_eq!(f32_to_half(-2.0), 0xC000);
+        assert_eq!(f32_to_half(65504.0), 0x7BFF);
+        assert_eq!(f32_to_half(65520.0), 0x7C00);
+        assert_eq!(f32_to_half(f32::INFINITY), 0x7C00);
+        assert_eq!(f32_to_half(f32::NEG_INFINITY), 0xFC00);
+        assert!(half_to_f32(f32_to_half(f32::NAN)).is_nan());
+    }
+
+    #[test]
+    fn f32_to_half_rounds_to_nearest_even() {
+        // Halfway between 1.0 and the next half rounds down to the even mantissa
+        assert_eq!(f32_to_half(1.0 + 2.0_f32.powi(-11)), 0x3C00);
+        // Halfway between the next half and the one after rounds up to the even mantissa
+        assert_eq!(f32_to_half(3.0_f32.mul_add(2.0_f32.powi(-11), 1.0)), 0x3C02);
+        // Above halfway rounds up
+        assert_eq!(
+            f32_to_half(1.0 + 2.0_f32.powi(-11) + 2.0_f32.powi(-20)),
+            0x3C01
+        );
+        // A channel value of 9 / 255 is closer to 0x2885 than to the truncated 0x2884
+        assert_eq!(f32_to_half(9.0 / 255.0), 0x2885);
+    }
+
+    #[test]
+    fn f32_to_half_subnormals() {
+        assert_eq!(f32_to_half(2.0_f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_half(2.0_f32.powi(-15)), 0x0200);
+        assert_eq!(f32_to_half(-2.0_f32.powi(-20)), 0x8010);
+        // Half of the smallest subnormal rounds to even, above it rounds up
+        assert_eq!(f32_to_half(2.0_f32.powi(-25)), 0x0000);
+        assert_eq!(f32_to_half(1.5 * 2.0_f32.powi(-25)), 0x0001);
+        assert_eq!(f32_to_half(2.0_f32.powi(-30)), 0x0000);
+        // The largest subnormal rounds up to the smallest normal
+        assert_eq!(f32_to_half(2.0_f32.powi(-14) - 2.0_f32.powi(-30)), 0x0400);
+
+        for half in 1..0x0400 {
+            assert_eq!(f32_to_half(half_to_f32(half)), half);
+        }
+    }
+
+    #[test]
+    fn srgb_to_linear_round_trip() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_to_linear(0.5) - 0.214_041).abs() < 1e-5);
+
+        for channel in 0..=255_u8 {
+            let value = f32::from(channel) / 255.0;
+            assert!((linear_to_srgb(srgb_to_linear(value)) - value).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn timespan_is_measured_from_epoch() {
+        let (d3d_device, context) = create_d3d_device().unwrap();
+        let (texture, surface) = frame_texture(&d3d_device, 64, 32, &[0; 64 * 32 * 4]);
+
+        let mut buffer = Vec::new();
+        let mut frame = Frame::new(
+            &d3d_device,
+            surface,
+            texture,
+            TimeSpan { Duration: 1000 },
+            &context,
+            &mut buffer,
+            64,
+            32,
+            ColorFormat::Bgra8,
+        );
+        assert_eq!(frame.timespan(), TimeSpan { Duration: 1000 });
+        assert_eq!(frame.epoch(), None);
+
+        frame.set_epoch(EpochMode::FirstFrame, TimeSpan { Duration: 400 });
+        assert_eq!(frame.timespan(), TimeSpan { Duration: 600 });
+        assert_eq!(frame.epoch(), Some(TimeSpan { Duration: 400 }));
+
+        // Content presented before the session started is clamped to the epoch
+        frame.set_epoch(EpochMode::SessionStart, TimeSpan { Duration: 1500 });
+        assert_eq!(frame.timespan(), TimeSpan::default());
+        assert_eq!(frame.system_relative_time(), TimeSpan { Duration: 1000 });
+    }
+
+    #[test]
+    fn annotate_fills_black_rect() {
+        let (d3d_device, context) = create_d3d_device().unwrap();
+        let pixels = vec![255; 64 * 32 * 4];
+        let (texture, surface) = frame_texture(&d3d_device, 64, 32, &pixels);
+
+        let mut buffer = Vec::new();
+        let mut frame = Frame::new(
+            &d3d_device,
+            surface,
+            texture,
+            TimeSpan::default(),
+            &context,
+            &mut buffer,
+            64,
+            32,
+            ColorFormat::Bgra8,
+        );
+
+        // A rectangle at (8, 4) and one clipped to the bottom right corner
+        frame
+            .annotate(&[
+                DrawOp::FillRect {
+                    rect: RectInt32 {
+                        X: 8,
+                        Y: 4,
+                        Width: 4,
+                        Height: 2,
+                    },
+                    color: [0, 0, 0, 255],
+                },
+                DrawOp::FillRect {
+                    rect: RectInt32 {
+                        X: 62,
+                        Y: 30,
+                        Width: 10,
+                        Height: 10,
+                    },
+                    color: [0, 0, 0, 255],
+                },
+            ])
+            .unwrap();
+
+        let mut out = Vec::new();
+        frame.copy_to_vec(&mut out).unwrap();
+
+        for y in 0..32 {
+            for x in 0..64 {
+                let black = ((8..12).contains(&x) && (4..6).contains(&y)) || (x >= 62 && y >= 30);
+                let expected = if black { [0, 0, 0, 255] } else { [255; 4] };
+                let offset = (y * 64 + x) * 4;
+                assert_eq!(out[offset..offset + 4], expected, "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn half_to_f32_values() {
+        assert_eq!(half_to_f32(0x0000), 0.0);
+        assert_eq!(half_to_f32(0x3C00), 1.0);
+        assert_eq!(half_to_f32(0x3800), 0.5);
+        assert_eq!(half_to_f32(0x4000), 2.0);
+        assert_eq!(half_to_f32(0xC000), -2.0);
+        assert_eq!(half_to_f32(0x7BFF), 65504.0);
+        assert_eq!(half_to_f32(0x0001), 2.0_f32.powi(-24));
+        assert_eq!(half_to_f32(0x7C00), f32::INFINITY);
+        assert!(half_to_f32(0x7E00).is_nan());
+    }
+
+    #[test]
+    fn to_rgba_packed_rgba16f() {
+        // Two pixels of 0.0, 1.0, 0.5 and above 1.0 in different channel orders
+        let halves = [
+            0x0000, 0x3C00, 0x3800, 0x3800, // 0.0, 1.0, 0.5, alpha 0.5
+            0x4000, 0x3800, 0x0000, 0x3C00, // 2.0, 0.5, 0.0, alpha 1.0
+        ];
+        let mut raw_buffer = halves
+            .iter()
+            .flat_map(|half: &u16| half.to_le_bytes())
+            .collect::<Vec<u8>>();
+        // Row padding that must be skipped
+        raw_buffer.extend_from_slice(&[0xFF; 8]);
+
+        let mut buffer = Vec::new();
+        let frame_buffer = FrameBuffer::new(
+            &mut raw_buffer,
+            &mut buffer,
+            2,
+            1,
+            24,
+            24,
+            ColorFormat::Rgba16F,
+        );
+
+        // Linear 0.5 is encoded as sRGB 0.7354, alpha stays linear and values above 1.0 clip
+        assert_eq!(
+            frame_buffer.to_rgba_packed(),
+            (vec![0, 255, 188, 128, 255, 188, 0, 255], 2, 1)
+        );
+    }
+
+    #[test]
+    fn convert_to_rejects_rgba16f() {
+        let mut raw_buffer = vec![0; 16];
+        let mut buffer = Vec::new();
+        let frame_buffer = FrameBuffer::new(
+            &mut raw_buffer,
+            &mut buffer,
+            2,
+            1,
+            16,
+            16,
+            ColorFormat::Rgba16F,
+        );
+
+        let mut out = Vec::new();
+        assert!(matches!(
+            frame_buffer.convert_to(ColorFormat::Rgba8, &mut out),
+            Err(Error::UnsupportedConversion)
+        ));
+    }
+}
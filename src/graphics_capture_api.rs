@@ -1,31 +1,63 @@
-use std::sync::{
-    atomic::{self, AtomicBool},
-    Arc,
+use std::{
+    mem, ptr,
+    sync::{
+        atomic::{self, AtomicBool, AtomicI64, AtomicU64},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use parking_lot::Mutex;
 use windows::{
     core::{IInspectable, Interface, HSTRING},
-    Foundation::{EventRegistrationToken, Metadata::ApiInformation, TypedEventHandler},
+    Foundation::{EventRegistrationToken, Metadata::ApiInformation, TimeSpan, TypedEventHandler},
     Graphics::{
-        Capture::{Direct3D11CaptureFramePool, GraphicsCaptureItem, GraphicsCaptureSession},
-        DirectX::{Direct3D11::IDirect3DDevice, DirectXPixelFormat},
+        Capture::{
+            Direct3D11CaptureFramePool, GraphicsCaptureDirtyRegionMode, GraphicsCaptureItem,
+            GraphicsCaptureSession,
+        },
+        DirectX::{
+            Direct3D11::{IDirect3DDevice, IDirect3DSurface},
+            DirectXPixelFormat,
+        },
+        RectInt32,
     },
     Win32::{
-        Foundation::{LPARAM, WPARAM},
-        Graphics::Direct3D11::{
-            ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, D3D11_TEXTURE2D_DESC,
+        Foundation::{HWND, LPARAM, POINT, RECT, WPARAM},
+        Graphics::{
+            Direct3D11::{
+                ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, D3D11_BOX,
+                D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT,
+            },
+            Dwm::{DwmGetWindowAttribute, DWMWA_EXTENDED_FRAME_BOUNDS},
+            Dxgi::IDXGISurface,
+        },
+        System::{
+            Performance::{QueryPerformanceCounter, QueryPerformanceFrequency},
+            WinRT::Direct3D11::{
+                CreateDirect3D11SurfaceFromDXGISurface, IDirect3DDxgiInterfaceAccess,
+            },
+        },
+        UI::{
+            HiDpi::LogicalToPhysicalPointForPerMonitorDPI,
+            WindowsAndMessaging::{
+                GetAncestor, GetCursorInfo, GetPhysicalCursorPos, PostThreadMessageW,
+                WindowFromPhysicalPoint, CURSORINFO, CURSOR_SHOWING, GA_ROOT, WM_QUIT,
+            },
         },
-        System::WinRT::Direct3D11::IDirect3DDxgiInterfaceAccess,
-        UI::WindowsAndMessaging::{PostThreadMessageW, WM_QUIT},
     },
 };
 
 use crate::{
     capture::GraphicsCaptureApiHandler,
-    d3d11::{self, create_direct3d_device, SendDirectX},
-    frame::Frame,
-    settings::{ColorFormat, CursorCaptureSettings, DrawBorderSettings},
+    d3d11::{self, create_direct3d_device, CursorOverlay, CursorShape, OpaqueFiller, SendDirectX},
+    frame::{Frame, StagingCache},
+    settings::{
+        CaptureAreaSettings, CaptureItemType, CaptureRegion, ColorFormat, CursorCaptureSettings,
+        DirtyRegionSettings, DrawBorderSettings, EpochMode,
+    },
+    window::Window,
 };
 
 #[derive(thiserror::Error, Eq, PartialEq, Clone, Debug)]
@@ -36,6 +68,16 @@ pub enum Error {
     CursorConfigUnsupported,
     #[error("Graphics capture API toggling border capture is not supported")]
     BorderConfigUnsupported,
+    #[error("Graphics capture API dirty region reporting is not supported")]
+    DirtyRegionConfigUnsupported,
+    #[error("Capture region is empty or outside of the capture item")]
+    InvalidCaptureRegion,
+    #[error("Capturing only the client area requires a window capture item")]
+    ClientAreaUnsupported,
+    #[error("The software cursor overlay requires a monitor or window capture item")]
+    CursorOverlayUnsupported,
+    #[error("Forcing an opaque alpha channel is only supported for Bgra8 and Rgba8 frames")]
+    OpaqueUnsupported,
     #[error("Already started")]
     AlreadyStarted,
     #[error("DirectX error: {0}")]
@@ -72,6 +114,132 @@ impl InternalCaptureControl {
     }
 }
 
+/// Frame statistics of a capture session.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Default)]
+pub struct CaptureStats {
+    /// The number of frames delivered to the frame handler.
+    pub delivered: u64,
+    /// The number of frames dropped because the capture was halted, throttled or unchanged.
+    pub dropped: u64,
+    /// The timestamp of the last delivered frame, `None` if no frame was delivered yet.
+    pub last_timestamp: Option<TimeSpan>,
+    /// The lowest delivery latency of the delivered frames, see `Frame::delivery_latency`.
+    pub min_latency: Option<Duration>,
+    /// The average delivery latency of the delivered frames.
+    pub avg_latency: Option<Duration>,
+    /// The highest delivery latency of the delivered frames.
+    pub max_latency: Option<Duration>,
+}
+
+/// Frame counters shared between the frame arrived handler and the capture control.
+#[derive(Debug)]
+pub struct CaptureCounters {
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    last_timestamp: AtomicI64,
+    // Delivery latencies in 100 nanosecond units
+    latency_count: AtomicU64,
+    latency_total: AtomicU64,
+    latency_min: AtomicU64,
+    latency_max: AtomicU64,
+    // Epoch of the frame timestamps in 100 nanosecond units of the QPC timebase, `i64::MIN`
+    // until it is known
+    epoch: AtomicI64,
+}
+
+impl Default for CaptureCounters {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            delivered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            last_timestamp: AtomicI64::new(0),
+            latency_count: AtomicU64::new(0),
+            latency_total: AtomicU64::new(0),
+            latency_min: AtomicU64::new(u64::MAX),
+            latency_max: AtomicU64::new(0),
+            epoch: AtomicI64::new(i64::MIN),
+        }
+    }
+}
+
+impl CaptureCounters {
+    /// Count a frame delivered to the frame handler.
+    #[inline]
+    fn delivered(&self, timespan: TimeSpan, latency: Option<Duration>) {
+        if let Some(latency) = latency {
+            let latency = u64::try_from(latency.as_nanos() / 100).unwrap_or(u64::MAX);
+
+            self.latency_total
+                .fetch_add(latency, atomic::Ordering::Relaxed);
+            self.latency_min
+                .fetch_min(latency, atomic::Ordering::Relaxed);
+            self.latency_max
+                .fetch_max(latency, atomic::Ordering::Relaxed);
+            self.latency_count.fetch_add(1, atomic::Ordering::Release);
+        }
+
+        self.last_timestamp
+            .store(timespan.Duration, atomic::Ordering::Relaxed);
+        self.delivered.fetch_add(1, atomic::Ordering::Release);
+    }
+
+    /// Count a frame that was not delivered to the frame handler.
+    #[inline]
+    fn dropped(&self) {
+        self.dropped.fetch_add(1, atomic::Ordering::Relaxed);
+    }
+
+    /// Record the epoch the frame timestamps are measured from.
+    #[inline]
+    fn set_epoch(&self, epoch: TimeSpan) {
+        self.epoch.store(epoch.Duration, atomic::Ordering::Relaxed);
+    }
+
+    /// Get the epoch the frame timestamps are measured from.
+    ///
+    /// # Returns
+    ///
+    /// Returns the epoch in 100 nanosecond units of the QPC timebase, or `None` if it is not
+    /// known yet, which is before the capture started with `EpochMode::SessionStart` and before
+    /// the first frame with `EpochMode::FirstFrame`.
+    #[must_use]
+    #[inline]
+    pub fn epoch(&self) -> Option<TimeSpan> {
+        let epoch = self.epoch.load(atomic::Ordering::Relaxed);
+
+        (epoch != i64::MIN).then_some(TimeSpan { Duration: epoch })
+    }
+
+    /// Get a snapshot of the counters.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `CaptureStats` at the time of the call.
+    #[must_use]
+    #[inline]
+    pub fn stats(&self) -> CaptureStats {
+        let delivered = self.delivered.load(atomic::Ordering::Acquire);
+        let latency_count = self.latency_count.load(atomic::Ordering::Acquire);
+        let latency = |latency: u64| {
+            (latency_count != 0).then(|| Duration::from_nanos(latency.saturating_mul(100)))
+        };
+
+        CaptureStats {
+            delivered,
+            dropped: self.dropped.load(atomic::Ordering::Relaxed),
+            last_timestamp: (delivered != 0).then(|| TimeSpan {
+                Duration: self.last_timestamp.load(atomic::Ordering::Relaxed),
+            }),
+            min_latency: latency(self.latency_min.load(atomic::Ordering::Relaxed)),
+            avg_latency: latency(
+                self.latency_total.load(atomic::Ordering::Relaxed) / latency_count.max(1),
+            ),
+            max_latency: latency(self.latency_max.load(atomic::Ordering::Relaxed)),
+        }
+    }
+}
+
 /// Represents the GraphicsCaptureApi struct.
 pub struct GraphicsCaptureApi {
     /// The GraphicsCaptureItem associated with the GraphicsCaptureApi.
@@ -90,6 +258,18 @@ pub struct GraphicsCaptureApi {
     halt: Arc<AtomicBool>,
     /// Indicates whether the GraphicsCaptureApi is active or not.
     active: bool,
+    /// The frame counters of the capture session.
+    counters: Arc<CaptureCounters>,
+    /// The time the capture was started in 100 nanosecond units of the QPC timebase.
+    session_start: Arc<AtomicI64>,
+    /// The point in time frame timestamps are measured from.
+    timestamp_epoch: EpochMode,
+    /// Indicates whether a frame handler call exceeded the handler timeout.
+    handler_timed_out: Arc<AtomicBool>,
+    /// The error that stopped the capture inside the frame arrived handler.
+    capture_error: Arc<Mutex<Option<Error>>>,
+    /// The optional handler timeout watchdog thread.
+    watchdog: Option<JoinHandle<()>>,
     /// The EventRegistrationToken associated with the capture closed event.
     capture_closed_event_token: EventRegistrationToken,
     /// The EventRegistrationToken associated with the frame arrived event.
@@ -104,11 +284,21 @@ impl GraphicsCaptureApi {
     /// * `d3d_device` - The ID3D11Device to use for the capture.
     /// * `d3d_device_context` - The ID3D11DeviceContext to use for the capture.
     /// * `item` - The graphics capture item to capture.
+    /// * `item_type` - What the capture item was created from.
     /// * `callback` - The callback handler for capturing frames.
     /// * `capture_cursor` - Optional flag to capture the cursor.
     /// * `draw_border` - Optional flag to draw a border around the captured region.
+    /// * `dirty_region` - Optional flag to report the dirty regions of each frame.
+    /// * `deliver_on_change_only` - Whether to skip frames without dirty regions when dirty region reporting is supported.
+    /// * `warmup_frames` - The number of frames to discard after the capture started.
+    /// * `timestamp_epoch` - The point in time frame timestamps are measured from.
+    /// * `handler_timeout` - Optional maximum duration of a single frame handler call.
+    /// * `capture_area` - The area of the capture item to crop each frame to.
+    /// * `capture_region` - Optional region of the capture item to crop each frame to.
+    /// * `target_frame_rate` - Optional maximum number of frames per second to deliver.
+    /// * `force_opaque` - Whether to make the alpha channel of each frame opaque on the GPU.
     /// * `color_format` - The color format for the captured frames.
-    /// * `thread_id` - The ID of the thread where the capture is running.
+    /// * `thread_id` - The ID of the thread where the capture is running, `None` if the thread's message loop is not owned by the capture and must not be stopped.
     /// * `result` - The result of the capture operation.
     ///
     /// # Returns
@@ -123,11 +313,21 @@ impl GraphicsCaptureApi {
         d3d_device: ID3D11Device,
         d3d_device_context: ID3D11DeviceContext,
         item: GraphicsCaptureItem,
+        item_type: CaptureItemType,
         callback: Arc<Mutex<T>>,
         cursor_capture: CursorCaptureSettings,
         draw_border: DrawBorderSettings,
+        dirty_region: DirtyRegionSettings,
+        deliver_on_change_only: bool,
+        warmup_frames: u32,
+        timestamp_epoch: EpochMode,
+        handler_timeout: Option<Duration>,
+        capture_area: CaptureAreaSettings,
+        capture_region: Option<CaptureRegion>,
+        target_frame_rate: Option<u32>,
+        force_opaque: bool,
         color_format: ColorFormat,
-        thread_id: u32,
+        thread_id: Option<u32>,
         result: Arc<Mutex<Option<E>>>,
     ) -> Result<Self, Error> {
         // Check support
@@ -135,8 +335,10 @@ impl GraphicsCaptureApi {
             return Err(Error::Unsupported);
         }
 
-        if cursor_capture != CursorCaptureSettings::Default
-            && !Self::is_cursor_settings_supported()?
+        if !matches!(
+            cursor_capture,
+            CursorCaptureSettings::Default | CursorCaptureSettings::SoftwareOverlay
+        ) && !Self::is_cursor_settings_supported()?
         {
             return Err(Error::CursorConfigUnsupported);
         }
@@ -145,6 +347,52 @@ impl GraphicsCaptureApi {
             return Err(Error::BorderConfigUnsupported);
         }
 
+        if dirty_region != DirtyRegionSettings::Default
+            && !Self::is_dirty_region_settings_supported()?
+        {
+            return Err(Error::DirtyRegionConfigUnsupported);
+        }
+
+        if capture_area == CaptureAreaSettings::ClientOnly
+            && !matches!(item_type, CaptureItemType::Window(_))
+        {
+            return Err(Error::ClientAreaUnsupported);
+        }
+
+        if cursor_capture == CursorCaptureSettings::SoftwareOverlay
+            && item_type == CaptureItemType::Unknown
+        {
+            return Err(Error::CursorOverlayUnsupported);
+        }
+
+        if force_opaque && color_format == ColorFormat::Rgba16F {
+            return Err(Error::OpaqueUnsupported);
+        }
+
+        if let Some(capture_region) = capture_region {
+            let size = item.Size()?;
+            if Self::clamp_capture_region(
+                capture_region,
+                u32::try_from(size.Width).unwrap_or(0),
+                u32::try_from(size.Height).unwrap_or(0),
+            )
+            .is_none()
+            {
+                return Err(Error::InvalidCaptureRegion);
+            }
+        }
+
+        // Dirty regions are needed to tell unchanged frames apart, without them every frame is
+        // delivered
+        let deliver_on_change_only =
+            deliver_on_change_only && Self::is_dirty_region_settings_supported()?;
+        let dirty_region = if deliver_on_change_only && dirty_region == DirtyRegionSettings::Default
+        {
+            DirtyRegionSettings::ReportOnly
+        } else {
+            dirty_region
+        };
+
         // Create DirectX devices
         let direct3d_device = create_direct3d_device(&d3d_device)?;
 
@@ -160,10 +408,23 @@ impl GraphicsCaptureApi {
 
         // Preallocate memory
         let mut buffer = vec![0u8; 3840 * 2160 * 4];
+        let mut staging_cache = StagingCache::default();
 
         // Indicates if the capture is closed
         let halt = Arc::new(AtomicBool::new(false));
 
+        // Delivered and dropped frame counters
+        let counters = Arc::new(CaptureCounters::default());
+        if timestamp_epoch == EpochMode::QpcZero {
+            counters.set_epoch(TimeSpan::default());
+        }
+        let session_start = Arc::new(AtomicI64::new(0));
+
+        // Start time of the frame handler call that is currently running
+        let handler_started = Arc::new(Mutex::new(None::<Instant>));
+        let handler_timed_out = Arc::new(AtomicBool::new(false));
+        let capture_error = Arc::new(Mutex::new(None::<Error>));
+
         // Set capture session closed event
         let capture_closed_event_token = item.Closed(&TypedEventHandler::<
             GraphicsCaptureItem,
@@ -184,9 +445,16 @@ impl GraphicsCaptureApi {
                 }
 
                 // To stop message loop
-                unsafe {
-                    PostThreadMessageW(thread_id, WM_QUIT, WPARAM::default(), LPARAM::default())?;
-                };
+                if let Some(thread_id) = thread_id {
+                    unsafe {
+                        PostThreadMessageW(
+                            thread_id,
+                            WM_QUIT,
+                            WPARAM::default(),
+                            LPARAM::default(),
+                        )?;
+                    };
+                }
 
                 Result::Ok(())
             }
@@ -203,14 +471,59 @@ impl GraphicsCaptureApi {
             let d3d_device_frame_pool = d3d_device.clone();
             let context = d3d_device_context.clone();
             let result_frame_pool = result;
+            let handler_started_frame_pool = handler_started.clone();
+            let counters_frame_pool = counters.clone();
+            let session_start_frame_pool = session_start.clone();
+            let capture_error_frame_pool = capture_error.clone();
 
             let mut last_size = item.Size()?;
+
+            // The client area only moves inside the item when the window is resized
+            let client_window = match (capture_area, item_type) {
+                (CaptureAreaSettings::ClientOnly, CaptureItemType::Window(window)) => Some(window),
+                _ => None,
+            };
+            let mut client_region = client_window.and_then(Self::client_area_region);
             let callback_frame_pool = callback;
             let direct3d_device_recreate = SendDirectX::new(direct3d_device.clone());
+            let mut crop_texture: Option<(
+                SendDirectX<ID3D11Texture2D>,
+                SendDirectX<IDirect3DSurface>,
+            )> = None;
+            let mut cursor_overlay = CursorOverlay::new();
+            // A monitor only moves on the virtual desktop when the display settings change
+            let overlay_monitor = match (cursor_capture, item_type) {
+                (CursorCaptureSettings::SoftwareOverlay, CaptureItemType::Monitor(monitor)) => {
+                    Some(monitor)
+                }
+                _ => None,
+            };
+            let mut overlay_monitor_rect =
+                overlay_monitor.and_then(|monitor| monitor.physical_rect().ok());
+            let mut opaque_filler = if force_opaque {
+                Some(SendDirectX::new(OpaqueFiller::new(
+                    &d3d_device,
+                    &d3d_device_context,
+                )?))
+            } else {
+                None
+            };
+
+            // Minimum time between delivered frames in 100 nanosecond units
+            let frame_interval = target_frame_rate
+                .filter(|target_frame_rate| *target_frame_rate != 0)
+                .map(|target_frame_rate| 10_000_000 / i64::from(target_frame_rate));
+            let mut last_delivered: Option<i64> = None;
+            // Dirty regions of frames dropped by the throttle, reported with the next frame
+            let mut pending_dirty_regions: Vec<RectInt32> = Vec::new();
+            let mut warmup_remaining = warmup_frames;
+            let mut previous_timespan: Option<TimeSpan> = None;
+            let mut first_timespan: Option<TimeSpan> = None;
 
             move |frame, _| {
                 // Return early if the capture is closed
                 if halt_frame_pool.load(atomic::Ordering::Relaxed) {
+                    counters_frame_pool.dropped();
                     return Ok(());
                 }
 
@@ -249,13 +562,252 @@ impl GraphicsCaptureApi {
                     )?;
 
                     last_size = frame_content_size;
+                    pending_dirty_regions.clear();
+                    if let Some(window) = client_window {
+                        client_region = Self::client_area_region(window);
+                    }
+                    if let Some(monitor) = overlay_monitor {
+                        overlay_monitor_rect = monitor.physical_rect().ok();
+                    }
+
+                    // Notify the struct that the size changed, the pending frame still has the
+                    // old size so the next delivered frame is the first one with the new size
+                    let result = callback_frame_pool.lock().on_size_changed(
+                        u32::try_from(frame_content_size.Width).unwrap_or(0),
+                        u32::try_from(frame_content_size.Height).unwrap_or(0),
+                    );
+
+                    if let Err(e) = result {
+                        *result_frame_pool.lock() = Some(e);
+
+                        halt_frame_pool.store(true, atomic::Ordering::Relaxed);
 
+                        // To stop the message loop
+                        if let Some(thread_id) = thread_id {
+                            unsafe {
+                                PostThreadMessageW(
+                                    thread_id,
+                                    WM_QUIT,
+                                    WPARAM::default(),
+                                    LPARAM::default(),
+                                )?;
+                            };
+                        }
+                    }
+
+                    counters_frame_pool.dropped();
+                    return Ok(());
+                }
+
+                // Drop the frames captured while the pipeline warms up
+                if Self::warmup_frame(&mut warmup_remaining) {
+                    counters_frame_pool.dropped();
+                    return Ok(());
+                }
+
+                let frame_dirty_regions = if dirty_region == DirtyRegionSettings::Default {
+                    Vec::new()
+                } else {
+                    frame.DirtyRegions()?.into_iter().collect::<Vec<_>>()
+                };
+
+                // Drop the frame if nothing changed since the previously delivered frame
+                if deliver_on_change_only
+                    && frame_dirty_regions.is_empty()
+                    && pending_dirty_regions.is_empty()
+                {
+                    counters_frame_pool.dropped();
                     return Ok(());
                 }
 
+                // Drop the frame if it arrived too soon after the previously delivered frame,
+                // its changes are kept so the next delivered frame still reports them
+                if let Some(frame_interval) = frame_interval {
+                    match Self::throttle_frame(last_delivered, timespan.Duration, frame_interval) {
+                        Some(delivered) => last_delivered = Some(delivered),
+                        None => {
+                            pending_dirty_regions.extend(frame_dirty_regions);
+                            counters_frame_pool.dropped();
+                            return Ok(());
+                        }
+                    }
+                }
+
+                // Crop the frame to the capture region, which is relative to the client area
+                // when only the client area is captured
+                let frame_region = client_region.map_or(capture_region, |client_region| {
+                    Some(Self::offset_capture_region(client_region, capture_region))
+                });
+
+                let (frame_surface, frame_texture, crop_box) = match frame_region {
+                    Some(capture_region) => {
+                        // Skip the frame if the item shrank and the region is no longer inside it
+                        let Some(crop_box) =
+                            Self::clamp_capture_region(capture_region, desc.Width, desc.Height)
+                        else {
+                            counters_frame_pool.dropped();
+                            return Ok(());
+                        };
+
+                        let crop_width = crop_box.right - crop_box.left;
+                        let crop_height = crop_box.bottom - crop_box.top;
+
+                        // Recreate the crop texture if the clamped region size changed
+                        let mut crop_desc = D3D11_TEXTURE2D_DESC::default();
+                        if let Some((texture, _)) = &crop_texture {
+                            unsafe { texture.0.GetDesc(&mut crop_desc) };
+                        }
+
+                        if crop_texture.is_none()
+                            || crop_desc.Width != crop_width
+                            || crop_desc.Height != crop_height
+                        {
+                            let mut texture_desc = desc;
+                            texture_desc.Width = crop_width;
+                            texture_desc.Height = crop_height;
+                            texture_desc.Usage = D3D11_USAGE_DEFAULT;
+                            texture_desc.CPUAccessFlags = 0;
+                            texture_desc.MiscFlags = 0;
+
+                            let mut texture = None;
+                            unsafe {
+                                d3d_device_frame_pool.CreateTexture2D(
+                                    &texture_desc,
+                                    None,
+                                    Some(&mut texture),
+                                )?;
+                            };
+                            let texture = texture.unwrap();
+
+                            // The surface wraps the texture, so it is created once per texture
+                            let dxgi_surface = texture.cast::<IDXGISurface>()?;
+                            let surface = unsafe {
+                                CreateDirect3D11SurfaceFromDXGISurface(&dxgi_surface)?
+                                    .cast::<IDirect3DSurface>()?
+                            };
+
+                            crop_texture =
+                                Some((SendDirectX::new(texture), SendDirectX::new(surface)));
+                        }
+
+                        let (texture, surface) = crop_texture.as_ref().unwrap();
+                        let texture = texture.0.clone();
+                        let surface = surface.0.clone();
+
+                        // Copy the region of the frame on the GPU
+                        unsafe {
+                            context.CopySubresourceRegion(
+                                &texture,
+                                0,
+                                0,
+                                0,
+                                0,
+                                &frame_texture,
+                                0,
+                                Some(&crop_box),
+                            );
+                        };
+
+                        (surface, texture, Some(crop_box))
+                    }
+                    None => (frame_surface, frame_texture, None),
+                };
+
+                // Draw the cursor in software, a cursor that can't be drawn leaves the frame as is
+                if cursor_capture == CursorCaptureSettings::SoftwareOverlay
+                    && color_format == ColorFormat::Bgra8
+                {
+                    if let Some((shape, position)) = Self::cursor_overlay_position(
+                        item_type,
+                        overlay_monitor_rect,
+                        &mut cursor_overlay,
+                        crop_box,
+                    ) {
+                        let _ = cursor_overlay.draw(
+                            &d3d_device_frame_pool,
+                            &context,
+                            &frame_texture,
+                            shape,
+                            position.x,
+                            position.y,
+                        );
+                    }
+                }
+
+                // Make the alpha channel opaque, after the cursor so it is opaque as well
+                if let Some(opaque_filler) = &mut opaque_filler {
+                    if let Err(e) =
+                        opaque_filler
+                            .0
+                            .fill(&d3d_device_frame_pool, &context, &frame_texture)
+                    {
+                        *capture_error_frame_pool.lock() = Some(Error::DirectXError(e));
+
+                        halt_frame_pool.store(true, atomic::Ordering::Relaxed);
+                        counters_frame_pool.dropped();
+
+                        // Notify the struct that the capture session is closed
+                        let result = callback_frame_pool.lock().on_closed();
+                        if let Err(e) = result {
+                            *result_frame_pool.lock() = Some(e);
+                        }
+
+                        // To stop the message loop
+                        if let Some(thread_id) = thread_id {
+                            unsafe {
+                                PostThreadMessageW(
+                                    thread_id,
+                                    WM_QUIT,
+                                    WPARAM::default(),
+                                    LPARAM::default(),
+                                )?;
+                            };
+                        }
+
+                        return Ok(());
+                    }
+                }
+
                 // Set width & height
-                let texture_width = desc.Width;
-                let texture_height = desc.Height;
+                let (texture_width, texture_height) =
+                    crop_box.map_or((desc.Width, desc.Height), |crop_box| {
+                        (
+                            crop_box.right - crop_box.left,
+                            crop_box.bottom - crop_box.top,
+                        )
+                    });
+
+                // Get the regions that changed since the previous frame, a frame without
+                // reporting or with a zero sized region is treated as a full frame update
+                let full_frame = RectInt32 {
+                    X: 0,
+                    Y: 0,
+                    Width: i32::try_from(texture_width).unwrap(),
+                    Height: i32::try_from(texture_height).unwrap(),
+                };
+                let dirty_regions = if dirty_region == DirtyRegionSettings::Default {
+                    vec![full_frame]
+                } else {
+                    let mut dirty_regions = mem::take(&mut pending_dirty_regions);
+                    dirty_regions.extend(frame_dirty_regions);
+
+                    if dirty_regions
+                        .iter()
+                        .any(|region| region.Width == 0 || region.Height == 0)
+                    {
+                        vec![full_frame]
+                    } else if let Some(crop_box) = crop_box {
+                        Self::crop_dirty_regions(&dirty_regions, &crop_box)
+                    } else {
+                        dirty_regions
+                    }
+                };
+
+                // Drop the frame if nothing changed inside the captured region
+                if deliver_on_change_only && dirty_regions.is_empty() {
+                    counters_frame_pool.dropped();
+                    return Ok(());
+                }
 
                 // Create a frame
                 let mut frame = Frame::new(
@@ -269,15 +821,32 @@ impl GraphicsCaptureApi {
                     texture_height,
                     color_format,
                 );
+                frame.set_dirty_regions(dirty_regions);
+                frame.set_previous_timespan(previous_timespan.replace(timespan));
+                frame.set_staging_cache(&mut staging_cache);
+
+                // Measure the frame timestamp from the chosen epoch
+                let epoch = match timestamp_epoch {
+                    EpochMode::FirstFrame => *first_timespan.get_or_insert(timespan),
+                    EpochMode::SessionStart => TimeSpan {
+                        Duration: session_start_frame_pool.load(atomic::Ordering::Relaxed),
+                    },
+                    EpochMode::QpcZero => TimeSpan::default(),
+                };
+                frame.set_epoch(timestamp_epoch, epoch);
+                counters_frame_pool.set_epoch(epoch);
 
                 // Init internal capture control
                 let stop = Arc::new(AtomicBool::new(false));
                 let internal_capture_control = InternalCaptureControl::new(stop.clone());
 
                 // Send the frame to the callback struct
+                counters_frame_pool.delivered(timespan, frame.delivery_latency().ok());
+                *handler_started_frame_pool.lock() = Some(Instant::now());
                 let result = callback_frame_pool
                     .lock()
                     .on_frame_arrived(&mut frame, internal_capture_control);
+                *handler_started_frame_pool.lock() = None;
 
                 if stop.load(atomic::Ordering::Relaxed) || result.is_err() {
                     if let Err(e) = result {
@@ -287,14 +856,16 @@ impl GraphicsCaptureApi {
                     halt_frame_pool.store(true, atomic::Ordering::Relaxed);
 
                     // To stop the message loop
-                    unsafe {
-                        PostThreadMessageW(
-                            thread_id,
-                            WM_QUIT,
-                            WPARAM::default(),
-                            LPARAM::default(),
-                        )?;
-                    };
+                    if let Some(thread_id) = thread_id {
+                        unsafe {
+                            PostThreadMessageW(
+                                thread_id,
+                                WM_QUIT,
+                                WPARAM::default(),
+                                LPARAM::default(),
+                            )?;
+                        };
+                    }
                 }
 
                 Result::Ok(())
@@ -306,11 +877,13 @@ impl GraphicsCaptureApi {
                 match cursor_capture {
                     CursorCaptureSettings::Default => (),
                     CursorCaptureSettings::WithCursor => session.SetIsCursorCaptureEnabled(true)?,
-                    CursorCaptureSettings::WithoutCursor => {
+                    // Hide the OS cursor so the software cursor isn't drawn twice
+                    CursorCaptureSettings::WithoutCursor
+                    | CursorCaptureSettings::SoftwareOverlay => {
                         session.SetIsCursorCaptureEnabled(false)?
                     }
                 };
-            } else {
+            } else if cursor_capture != CursorCaptureSettings::SoftwareOverlay {
                 return Err(Error::CursorConfigUnsupported);
             }
         }
@@ -329,6 +902,58 @@ impl GraphicsCaptureApi {
             }
         }
 
+        if dirty_region != DirtyRegionSettings::Default {
+            if Self::is_dirty_region_settings_supported()? {
+                match dirty_region {
+                    DirtyRegionSettings::Default => (),
+                    DirtyRegionSettings::ReportOnly => {
+                        session.SetDirtyRegionMode(GraphicsCaptureDirtyRegionMode::ReportOnly)?;
+                    }
+                    DirtyRegionSettings::ReportAndRender => {
+                        session
+                            .SetDirtyRegionMode(GraphicsCaptureDirtyRegionMode::ReportAndRender)?;
+                    }
+                }
+            } else {
+                return Err(Error::DirtyRegionConfigUnsupported);
+            }
+        }
+
+        // Stop the capture if a single frame handler call takes too long
+        let watchdog = handler_timeout.map(|handler_timeout| {
+            let halt_watchdog = halt.clone();
+            let handler_started_watchdog = handler_started;
+            let handler_timed_out_watchdog = handler_timed_out.clone();
+            let interval = (handler_timeout / 10).max(Duration::from_millis(1));
+
+            thread::spawn(move || {
+                while !halt_watchdog.load(atomic::Ordering::Relaxed) {
+                    thread::sleep(interval);
+
+                    let timed_out = handler_started_watchdog
+                        .lock()
+                        .is_some_and(|started| started.elapsed() > handler_timeout);
+
+                    if timed_out {
+                        handler_timed_out_watchdog.store(true, atomic::Ordering::Relaxed);
+                        halt_watchdog.store(true, atomic::Ordering::Relaxed);
+
+                        // To stop the message loop once the handler returns
+                        if let Some(thread_id) = thread_id {
+                            unsafe {
+                                let _ = PostThreadMessageW(
+                                    thread_id,
+                                    WM_QUIT,
+                                    WPARAM::default(),
+                                    LPARAM::default(),
+                                );
+                            };
+                        }
+                    }
+                }
+            })
+        });
+
         Ok(Self {
             item,
             _d3d_device: d3d_device,
@@ -338,6 +963,12 @@ impl GraphicsCaptureApi {
             session: Some(session),
             halt,
             active: false,
+            counters,
+            session_start,
+            timestamp_epoch,
+            handler_timed_out,
+            capture_error,
+            watchdog,
             frame_arrived_event_token,
             capture_closed_event_token,
         })
@@ -355,6 +986,23 @@ impl GraphicsCaptureApi {
         }
         self.active = true;
 
+        // Convert the counter to 100 nanosecond units like the frame time
+        let mut counter = 0;
+        let mut frequency = 0;
+        unsafe {
+            QueryPerformanceCounter(&mut counter)?;
+            QueryPerformanceFrequency(&mut frequency)?;
+        };
+        let session_start = i128::from(counter) * 10_000_000 / i128::from(frequency);
+        let session_start = i64::try_from(session_start).unwrap_or(i64::MAX);
+        self.session_start
+            .store(session_start, atomic::Ordering::Relaxed);
+        if self.timestamp_epoch == EpochMode::SessionStart {
+            self.counters.set_epoch(TimeSpan {
+                Duration: session_start,
+            });
+        }
+
         self.session.as_ref().unwrap().StartCapture()?;
 
         Ok(())
@@ -363,6 +1011,13 @@ impl GraphicsCaptureApi {
     /// Stop the capture.
     #[inline]
     pub fn stop_capture(mut self) {
+        self.halt.store(true, atomic::Ordering::Relaxed);
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog
+                .join()
+                .expect("Failed to join handler timeout watchdog thread");
+        }
+
         if let Some(frame_pool) = self.frame_pool.take() {
             frame_pool
                 .RemoveFrameArrived(self.frame_arrived_event_token)
@@ -391,6 +1046,412 @@ impl GraphicsCaptureApi {
         self.halt.clone()
     }
 
+    /// Get the frame counters handle.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `Arc<CaptureCounters>` representing the frame counters handle.
+    #[must_use]
+    #[inline]
+    pub fn counters_handle(&self) -> Arc<CaptureCounters> {
+        self.counters.clone()
+    }
+
+    /// Get the frame statistics of the capture session.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `CaptureStats` of the capture session.
+    #[must_use]
+    #[inline]
+    pub fn stats(&self) -> CaptureStats {
+        self.counters.stats()
+    }
+
+    /// Clamp the capture region to the bounds of the capture item.
+    ///
+    /// # Arguments
+    ///
+    /// * `capture_region` - The region of the capture item to capture.
+    /// * `width` - The width of the capture item.
+    /// * `height` - The height of the capture item.
+    ///
+    /// # Returns
+    ///
+    /// Returns the clamped region as a `D3D11_BOX`, or `None` if the region is empty or fully outside of the item.
+    const fn clamp_capture_region(
+        capture_region: CaptureRegion,
+        width: u32,
+        height: u32,
+    ) -> Option<D3D11_BOX> {
+        let right = capture_region.x.saturating_add(capture_region.width);
+        let right = if right < width { right } else { width };
+        let bottom = capture_region.y.saturating_add(capture_region.height);
+        let bottom = if bottom < height { bottom } else { height };
+
+        if capture_region.x >= right || capture_region.y >= bottom {
+            return None;
+        }
+
+        Some(D3D11_BOX {
+            left: capture_region.x,
+            top: capture_region.y,
+            front: 0,
+            right,
+            bottom,
+            back: 1,
+        })
+    }
+
+    /// Get the client area of a window relative to its extended frame bounds, which is the area
+    /// the capture item covers.
+    ///
+    /// The extended frame bounds are always in physical pixels, while the client rectangle is in
+    /// the logical coordinates of the calling thread, so it is converted to physical pixels first.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The captured window.
+    ///
+    /// # Returns
+    ///
+    /// Returns the client area as a `CaptureRegion`, or `None` if the window bounds can't be retrieved.
+    fn client_area_region(window: Window) -> Option<CaptureRegion> {
+        let client_rect = window.client_rect().ok()?;
+
+        let mut top_left = POINT {
+            x: client_rect.left,
+            y: client_rect.top,
+        };
+        let mut bottom_right = POINT {
+            x: client_rect.right,
+            y: client_rect.bottom,
+        };
+        unsafe {
+            let hwnd = HWND(window.as_raw_hwnd());
+            if !LogicalToPhysicalPointForPerMonitorDPI(hwnd, &mut top_left).as_bool()
+                || !LogicalToPhysicalPointForPerMonitorDPI(hwnd, &mut bottom_right).as_bool()
+            {
+                return None;
+            }
+        };
+
+        let frame_bounds = Self::window_frame_bounds(window)?;
+
+        Some(Self::client_region_in_frame(
+            RECT {
+                left: top_left.x,
+                top: top_left.y,
+                right: bottom_right.x,
+                bottom: bottom_right.y,
+            },
+            frame_bounds,
+        ))
+    }
+
+    /// Get the client area relative to the extended frame bounds of the window.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_rect` - The client area in physical screen coordinates.
+    /// * `frame_bounds` - The extended frame bounds in physical screen coordinates.
+    ///
+    /// # Returns
+    ///
+    /// Returns the client area as a `CaptureRegion`, parts outside the frame bounds are cut off.
+    fn client_region_in_frame(client_rect: RECT, frame_bounds: RECT) -> CaptureRegion {
+        let left = client_rect.left.max(frame_bounds.left);
+        let top = client_rect.top.max(frame_bounds.top);
+
+        CaptureRegion::new(
+            u32::try_from(left - frame_bounds.left).unwrap_or(0),
+            u32::try_from(top - frame_bounds.top).unwrap_or(0),
+            u32::try_from(client_rect.right.min(frame_bounds.right) - left).unwrap_or(0),
+            u32::try_from(client_rect.bottom.min(frame_bounds.bottom) - top).unwrap_or(0),
+        )
+    }
+
+    /// Get the extended frame bounds of a window, the area a window capture item covers.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The captured window.
+    ///
+    /// # Returns
+    ///
+    /// Returns the bounds on the virtual desktop in physical pixels, or `None` if they can't be retrieved.
+    fn window_frame_bounds(window: Window) -> Option<RECT> {
+        let mut frame_bounds = RECT::default();
+        unsafe {
+            DwmGetWindowAttribute(
+                HWND(window.as_raw_hwnd()),
+                DWMWA_EXTENDED_FRAME_BOUNDS,
+                ptr::addr_of_mut!(frame_bounds).cast(),
+                u32::try_from(mem::size_of::<RECT>()).unwrap(),
+            )
+            .ok()?;
+        };
+
+        Some(frame_bounds)
+    }
+
+    /// Get the cursor and where to draw it on a frame of the capture item.
+    ///
+    /// Positions are read in physical pixels, so they match the frames regardless of the DPI
+    /// awareness of the process.
+    ///
+    /// # Arguments
+    ///
+    /// * `item_type` - What the capture item was created from.
+    /// * `monitor_rect` - The physical bounds of the captured monitor, `None` for other items.
+    /// * `cursor_overlay` - The overlay that caches the shape of the cursor.
+    /// * `crop_box` - The region the frame was cropped to, `None` if it is not cropped.
+    ///
+    /// # Returns
+    ///
+    /// Returns the shape of the cursor and the position of its top-left corner relative to the
+    /// frame, or `None` if the cursor is hidden, over another window or not over the frame.
+    fn cursor_overlay_position(
+        item_type: CaptureItemType,
+        monitor_rect: Option<RECT>,
+        cursor_overlay: &mut CursorOverlay,
+        crop_box: Option<D3D11_BOX>,
+    ) -> Option<(CursorShape, POINT)> {
+        let mut cursor_info = CURSORINFO {
+            cbSize: u32::try_from(mem::size_of::<CURSORINFO>()).unwrap(),
+            ..Default::default()
+        };
+        unsafe { GetCursorInfo(&mut cursor_info).ok()? };
+
+        if cursor_info.flags.0 & CURSOR_SHOWING.0 == 0 {
+            return None;
+        }
+
+        let mut point = POINT::default();
+        unsafe { GetPhysicalCursorPos(&mut point).ok()? };
+
+        // The bounds of the item on the virtual desktop
+        let item_rect = match item_type {
+            CaptureItemType::Monitor(_) => monitor_rect?,
+            CaptureItemType::Window(window) => {
+                let window_under_cursor =
+                    unsafe { GetAncestor(WindowFromPhysicalPoint(point), GA_ROOT) };
+                if window_under_cursor.0 != window.as_raw_hwnd() {
+                    return None;
+                }
+
+                Self::window_frame_bounds(window)?
+            }
+            CaptureItemType::Unknown => return None,
+        };
+
+        let shape = cursor_overlay.shape(cursor_info.hCursor).ok()?;
+        let position = Self::cursor_frame_position(
+            point,
+            shape.hotspot,
+            (shape.width, shape.height),
+            item_rect,
+            crop_box,
+        )?;
+
+        Some((shape, position))
+    }
+
+    /// Place a cursor on a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - The position of the cursor on the virtual desktop.
+    /// * `hotspot` - The hot spot of the cursor relative to its top-left corner.
+    /// * `cursor_size` - The width and height of the cursor.
+    /// * `item_rect` - The bounds of the capture item on the virtual desktop.
+    /// * `crop_box` - The region the frame was cropped to, `None` if it is not cropped.
+    ///
+    /// # Returns
+    ///
+    /// Returns the position of the top-left corner of the cursor relative to the frame, or
+    /// `None` if no part of the cursor is over the frame.
+    fn cursor_frame_position(
+        point: POINT,
+        hotspot: POINT,
+        cursor_size: (u32, u32),
+        item_rect: RECT,
+        crop_box: Option<D3D11_BOX>,
+    ) -> Option<POINT> {
+        let frame_rect = crop_box.map_or(item_rect, |crop_box| RECT {
+            left: item_rect.left + i32::try_from(crop_box.left).unwrap_or(0),
+            top: item_rect.top + i32::try_from(crop_box.top).unwrap_or(0),
+            right: item_rect.left + i32::try_from(crop_box.right).unwrap_or(0),
+            bottom: item_rect.top + i32::try_from(crop_box.bottom).unwrap_or(0),
+        });
+
+        let left = point.x - hotspot.x;
+        let top = point.y - hotspot.y;
+        let right = left + i32::try_from(cursor_size.0).unwrap_or(0);
+        let bottom = top + i32::try_from(cursor_size.1).unwrap_or(0);
+
+        if left >= frame_rect.right
+            || right <= frame_rect.left
+            || top >= frame_rect.bottom
+            || bottom <= frame_rect.top
+        {
+            return None;
+        }
+
+        Some(POINT {
+            x: left - frame_rect.left,
+            y: top - frame_rect.top,
+        })
+    }
+
+    /// Place a capture region inside another region.
+    ///
+    /// # Arguments
+    ///
+    /// * `outer` - The region the capture region is relative to.
+    /// * `capture_region` - The capture region, `None` to use the whole outer region.
+    ///
+    /// # Returns
+    ///
+    /// Returns the capture region relative to the capture item, limited to the outer region.
+    const fn offset_capture_region(
+        outer: CaptureRegion,
+        capture_region: Option<CaptureRegion>,
+    ) -> CaptureRegion {
+        let Some(capture_region) = capture_region else {
+            return outer;
+        };
+
+        let max_width = outer.width.saturating_sub(capture_region.x);
+        let max_height = outer.height.saturating_sub(capture_region.y);
+
+        CaptureRegion::new(
+            outer.x.saturating_add(capture_region.x),
+            outer.y.saturating_add(capture_region.y),
+            if capture_region.width < max_width {
+                capture_region.width
+            } else {
+                max_width
+            },
+            if capture_region.height < max_height {
+                capture_region.height
+            } else {
+                max_height
+            },
+        )
+    }
+
+    /// Decide if a frame should be discarded while the pipeline warms up.
+    ///
+    /// # Arguments
+    ///
+    /// * `warmup_remaining` - The number of frames still to discard, decremented when a frame is discarded.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the frame should be discarded.
+    const fn warmup_frame(warmup_remaining: &mut u32) -> bool {
+        if *warmup_remaining == 0 {
+            return false;
+        }
+
+        *warmup_remaining -= 1;
+        true
+    }
+
+    /// Decide if a frame should be delivered to keep up with the target frame rate.
+    ///
+    /// The delivery time advances by exactly one interval so the average cadence matches the
+    /// target frame rate, it is reset to the frame time when the source fell behind by more than
+    /// one interval so the handler is never starved after a pause.
+    ///
+    /// # Arguments
+    ///
+    /// * `last_delivered` - The delivery time of the previously delivered frame.
+    /// * `time` - The time of the frame in 100 nanosecond units.
+    /// * `frame_interval` - The minimum time between delivered frames in 100 nanosecond units.
+    ///
+    /// # Returns
+    ///
+    /// Returns the new delivery time if the frame should be delivered, or `None` if it should be dropped.
+    const fn throttle_frame(
+        last_delivered: Option<i64>,
+        time: i64,
+        frame_interval: i64,
+    ) -> Option<i64> {
+        let Some(last_delivered) = last_delivered else {
+            return Some(time);
+        };
+
+        let elapsed = time - last_delivered;
+        if elapsed < frame_interval {
+            None
+        } else if elapsed < frame_interval * 2 {
+            Some(last_delivered + frame_interval)
+        } else {
+            Some(time)
+        }
+    }
+
+    /// Translate the dirty regions of a frame to the cropped frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `dirty_regions` - The dirty regions of the whole frame.
+    /// * `crop_box` - The region the frame is cropped to.
+    ///
+    /// # Returns
+    ///
+    /// Returns the parts of the dirty regions that are inside the cropped frame, relative to its top-left corner.
+    fn crop_dirty_regions(dirty_regions: &[RectInt32], crop_box: &D3D11_BOX) -> Vec<RectInt32> {
+        let crop_left = i32::try_from(crop_box.left).unwrap();
+        let crop_top = i32::try_from(crop_box.top).unwrap();
+        let crop_right = i32::try_from(crop_box.right).unwrap();
+        let crop_bottom = i32::try_from(crop_box.bottom).unwrap();
+
+        dirty_regions
+            .iter()
+            .filter_map(|region| {
+                let left = region.X.max(crop_left);
+                let top = region.Y.max(crop_top);
+                let right = (region.X + region.Width).min(crop_right);
+                let bottom = (region.Y + region.Height).min(crop_bottom);
+
+                (left < right && top < bottom).then(|| RectInt32 {
+                    X: left - crop_left,
+                    Y: top - crop_top,
+                    Width: right - left,
+                    Height: bottom - top,
+                })
+            })
+            .collect()
+    }
+
+    /// Check if a frame handler call exceeded the handler timeout.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the capture was stopped by the handler timeout watchdog, `false` otherwise.
+    #[must_use]
+    #[inline]
+    pub fn is_handler_timed_out(&self) -> bool {
+        self.handler_timed_out.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Take the error that stopped the capture inside the frame arrived handler.
+    ///
+    /// The frame handler is notified with `GraphicsCaptureApiHandler::on_closed` before the
+    /// capture stops.
+    ///
+    /// # Returns
+    ///
+    /// The error that stopped the capture, or `None` if the capture wasn't stopped by an error.
+    #[must_use]
+    #[inline]
+    pub fn take_capture_error(&self) -> Option<Error> {
+        self.capture_error.lock().take()
+    }
+
     /// Check if the Windows Graphics Capture API is supported.
     ///
     /// # Returns
@@ -429,10 +1490,30 @@ impl GraphicsCaptureApi {
             &HSTRING::from("IsBorderRequired"),
         )? && Self::is_supported()?)
     }
+
+    /// Check if you can change the dirty region setting.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if dirty region reporting is supported, `false` otherwise.
+    #[inline]
+    pub fn is_dirty_region_settings_supported() -> Result<bool, Error> {
+        Ok(ApiInformation::IsPropertyPresent(
+            &HSTRING::from("Windows.Graphics.Capture.GraphicsCaptureSession"),
+            &HSTRING::from("DirtyRegionMode"),
+        )? && Self::is_supported()?)
+    }
 }
 
 impl Drop for GraphicsCaptureApi {
     fn drop(&mut self) {
+        self.halt.store(true, atomic::Ordering::Relaxed);
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog
+                .join()
+                .expect("Failed to join handler timeout watchdog thread");
+        }
+
         if let Some(frame_pool) = self.frame_pool.take() {
             frame_pool
                 .RemoveFrameArrived(self.frame_arrived_event_token)
@@ -450,3 +1531,377 @@ impl Drop for GraphicsCaptureApi {
             .expect("Failed to remove Capture Session Closed event handler");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_stats_latency() {
+        let counters = CaptureCounters::default();
+        assert_eq!(counters.stats(), CaptureStats::default());
+
+        counters.delivered(TimeSpan { Duration: 10 }, Some(Duration::from_millis(2)));
+        counters.delivered(TimeSpan { Duration: 20 }, Some(Duration::from_millis(6)));
+        counters.delivered(TimeSpan { Duration: 30 }, None);
+        counters.dropped();
+
+        let stats = counters.stats();
+        assert_eq!(stats.delivered, 3);
+        assert_eq!(stats.dropped, 1);
+        assert_eq!(stats.last_timestamp, Some(TimeSpan { Duration: 30 }));
+        assert_eq!(stats.min_latency, Some(Duration::from_millis(2)));
+        assert_eq!(stats.avg_latency, Some(Duration::from_millis(4)));
+        assert_eq!(stats.max_latency, Some(Duration::from_millis(6)));
+    }
+
+    #[test]
+    fn cursor_frame_position_of_item() {
+        let item_rect = RECT {
+            left: -1920,
+            top: 0,
+            right: 0,
+            bottom: 1080,
+        };
+
+        // The hot spot of a 32x32 cursor at (4, 2) is at (100, 200) of the item
+        assert_eq!(
+            GraphicsCaptureApi::cursor_frame_position(
+                POINT { x: -1820, y: 200 },
+                POINT { x: 4, y: 2 },
+                (32, 32),
+                item_rect,
+                None,
+            ),
+            Some(POINT { x: 96, y: 198 })
+        );
+    }
+
+    #[test]
+    fn cursor_frame_position_of_cropped_frame() {
+        let item_rect = RECT {
+            left: 100,
+            top: 50,
+            right: 900,
+            bottom: 650,
+        };
+        let crop_box = D3D11_BOX {
+            left: 200,
+            top: 100,
+            front: 0,
+            right: 400,
+            bottom: 300,
+            back: 1,
+        };
+
+        assert_eq!(
+            GraphicsCaptureApi::cursor_frame_position(
+                POINT { x: 310, y: 160 },
+                POINT { x: 0, y: 0 },
+                (32, 32),
+                item_rect,
+                Some(crop_box),
+            ),
+            Some(POINT { x: 10, y: 10 })
+        );
+
+        // A cursor partly over the left edge of the crop is still drawn
+        assert_eq!(
+            GraphicsCaptureApi::cursor_frame_position(
+                POINT { x: 290, y: 160 },
+                POINT { x: 0, y: 0 },
+                (32, 32),
+                item_rect,
+                Some(crop_box),
+            ),
+            Some(POINT { x: -10, y: 10 })
+        );
+    }
+
+    #[test]
+    fn cursor_frame_position_outside_frame() {
+        let item_rect = RECT {
+            left: 0,
+            top: 0,
+            right: 1920,
+            bottom: 1080,
+        };
+
+        // Right of the item
+        assert_eq!(
+            GraphicsCaptureApi::cursor_frame_position(
+                POINT { x: 1920, y: 500 },
+                POINT { x: 0, y: 0 },
+                (32, 32),
+                item_rect,
+                None,
+            ),
+            None
+        );
+
+        // The hot spot is outside, but the whole cursor ends at the left edge
+        assert_eq!(
+            GraphicsCaptureApi::cursor_frame_position(
+                POINT { x: -1, y: 500 },
+                POINT { x: 31, y: 0 },
+                (32, 32),
+                item_rect,
+                None,
+            ),
+            None
+        );
+
+        // Outside of the cropped region but inside the item
+        let crop_box = D3D11_BOX {
+            left: 0,
+            top: 0,
+            front: 0,
+            right: 100,
+            bottom: 100,
+            back: 1,
+        };
+        assert_eq!(
+            GraphicsCaptureApi::cursor_frame_position(
+                POINT { x: 500, y: 500 },
+                POINT { x: 0, y: 0 },
+                (32, 32),
+                item_rect,
+                Some(crop_box),
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn warmup_frame_delivers_fourth_frame() {
+        let mut warmup_remaining = 3;
+        let mut captured = 0;
+        let mut first_delivered = None;
+
+        for _ in 0..6 {
+            captured += 1;
+            if !GraphicsCaptureApi::warmup_frame(&mut warmup_remaining) && first_delivered.is_none()
+            {
+                first_delivered = Some(captured);
+            }
+        }
+
+        assert_eq!(first_delivered, Some(4));
+        assert_eq!(warmup_remaining, 0);
+    }
+
+    #[test]
+    fn warmup_frame_disabled() {
+        let mut warmup_remaining = 0;
+        assert!(!GraphicsCaptureApi::warmup_frame(&mut warmup_remaining));
+        assert_eq!(warmup_remaining, 0);
+    }
+
+    #[test]
+    fn capture_counters_epoch() {
+        let counters = CaptureCounters::default();
+        assert_eq!(counters.epoch(), None);
+
+        counters.set_epoch(TimeSpan::default());
+        assert_eq!(counters.epoch(), Some(TimeSpan::default()));
+
+        counters.set_epoch(TimeSpan { Duration: 1234 });
+        assert_eq!(counters.epoch(), Some(TimeSpan { Duration: 1234 }));
+    }
+
+    #[test]
+    fn throttle_frame_first_frame() {
+        assert_eq!(
+            GraphicsCaptureApi::throttle_frame(None, 1000, 100),
+            Some(1000)
+        );
+    }
+
+    #[test]
+    fn throttle_frame_too_soon() {
+        assert_eq!(
+            GraphicsCaptureApi::throttle_frame(Some(1000), 1099, 100),
+            None
+        );
+    }
+
+    #[test]
+    fn throttle_frame_keeps_cadence() {
+        // A frame slightly late advances the schedule by exactly one interval
+        assert_eq!(
+            GraphicsCaptureApi::throttle_frame(Some(1000), 1130, 100),
+            Some(1100)
+        );
+    }
+
+    #[test]
+    fn throttle_frame_resyncs_after_gap() {
+        assert_eq!(
+            GraphicsCaptureApi::throttle_frame(Some(1000), 1500, 100),
+            Some(1500)
+        );
+    }
+
+    #[test]
+    fn throttle_frame_rate() {
+        // 60 frames per second throttled to 30 delivers every second frame
+        let frame_interval = 10_000_000 / 30;
+        let mut last_delivered = None;
+        let mut delivered = 0;
+
+        for frame in 0..60 {
+            let time = frame * 10_000_000 / 60;
+            if let Some(time) =
+                GraphicsCaptureApi::throttle_frame(last_delivered, time, frame_interval)
+            {
+                last_delivered = Some(time);
+                delivered += 1;
+            }
+        }
+
+        assert_eq!(delivered, 30);
+    }
+
+    #[test]
+    fn clamp_capture_region_inside() {
+        let crop_box =
+            GraphicsCaptureApi::clamp_capture_region(CaptureRegion::new(10, 20, 30, 40), 100, 100)
+                .unwrap();
+
+        assert_eq!(
+            (crop_box.left, crop_box.top, crop_box.right, crop_box.bottom),
+            (10, 20, 40, 60)
+        );
+        assert_eq!((crop_box.front, crop_box.back), (0, 1));
+    }
+
+    #[test]
+    fn clamp_capture_region_partially_outside() {
+        let crop_box =
+            GraphicsCaptureApi::clamp_capture_region(CaptureRegion::new(80, 90, 50, 50), 100, 100)
+                .unwrap();
+
+        assert_eq!(
+            (crop_box.left, crop_box.top, crop_box.right, crop_box.bottom),
+            (80, 90, 100, 100)
+        );
+    }
+
+    #[test]
+    fn clamp_capture_region_outside() {
+        assert!(GraphicsCaptureApi::clamp_capture_region(
+            CaptureRegion::new(100, 0, 10, 10),
+            100,
+            100
+        )
+        .is_none());
+        assert!(GraphicsCaptureApi::clamp_capture_region(
+            CaptureRegion::new(0, 0, 0, 10),
+            100,
+            100
+        )
+        .is_none());
+        assert!(GraphicsCaptureApi::clamp_capture_region(
+            CaptureRegion::new(u32::MAX, u32::MAX, u32::MAX, u32::MAX),
+            100,
+            100
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn offset_capture_region_without_region() {
+        let outer = CaptureRegion::new(8, 31, 800, 600);
+
+        assert_eq!(
+            GraphicsCaptureApi::offset_capture_region(outer, None),
+            outer
+        );
+    }
+
+    #[test]
+    fn offset_capture_region_inside() {
+        let outer = CaptureRegion::new(8, 31, 800, 600);
+
+        assert_eq!(
+            GraphicsCaptureApi::offset_capture_region(
+                outer,
+                Some(CaptureRegion::new(10, 20, 100, 50))
+            ),
+            CaptureRegion::new(18, 51, 100, 50)
+        );
+    }
+
+    #[test]
+    fn offset_capture_region_clamped_to_outer() {
+        let outer = CaptureRegion::new(8, 31, 800, 600);
+
+        assert_eq!(
+            GraphicsCaptureApi::offset_capture_region(
+                outer,
+                Some(CaptureRegion::new(700, 550, 500, 500))
+            ),
+            CaptureRegion::new(708, 581, 100, 50)
+        );
+        assert_eq!(
+            GraphicsCaptureApi::offset_capture_region(
+                outer,
+                Some(CaptureRegion::new(900, 0, 10, 10))
+            ),
+            CaptureRegion::new(908, 31, 0, 10)
+        );
+    }
+
+    #[test]
+    fn client_region_in_frame_offsets_by_frame_bounds() {
+        let frame_bounds = RECT {
+            left: 100,
+            top: 50,
+            right: 900,
+            bottom: 650,
+        };
+        let client_rect = RECT {
+            left: 108,
+            top: 81,
+            right: 892,
+            bottom: 642,
+        };
+
+        assert_eq!(
+            GraphicsCaptureApi::client_region_in_frame(client_rect, frame_bounds),
+            CaptureRegion::new(8, 31, 784, 561)
+        );
+    }
+
+    #[test]
+    fn client_region_in_frame_clips_to_frame_bounds() {
+        let frame_bounds = RECT {
+            left: 0,
+            top: 0,
+            right: 400,
+            bottom: 300,
+        };
+        let client_rect = RECT {
+            left: -10,
+            top: 20,
+            right: 420,
+            bottom: 280,
+        };
+
+        assert_eq!(
+            GraphicsCaptureApi::client_region_in_frame(client_rect, frame_bounds),
+            CaptureRegion::new(0, 20, 400, 260)
+        );
+
+        let outside = RECT {
+            left: 500,
+            top: 20,
+            right: 600,
+            bottom: 280,
+        };
+        assert_eq!(
+            GraphicsCaptureApi::client_region_in_frame(outside, frame_bounds),
+            CaptureRegion::new(500, 20, 0, 260)
+        );
+    }
+}
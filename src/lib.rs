@@ -147,6 +147,8 @@
 #![warn(clippy::cargo)]
 #![allow(clippy::multiple_crate_versions)] // Should update as soon as possible
 
+/// Contains the system audio capture functionality for recording audio alongside the video.
+pub mod audio;
 /// Contains the main capture functionality, including the `WindowsCaptureHandler` trait and related types.
 pub mod capture;
 /// Internal module for Direct3D 11 related functionality.
@@ -163,3 +165,5 @@ pub mod monitor;
 pub mod settings;
 /// Contains the functionality for working with windows and capturing specific windows.
 pub mod window;
+
+pub use d3d11::{video_memory_info, Error as DirectXError, StagingTexture, VideoMemoryInfo};
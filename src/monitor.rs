@@ -1,7 +1,7 @@
 use std::{mem, num::ParseIntError, ptr, string::FromUtf16Error};
 
 use windows::{
-    core::{HSTRING, PCWSTR},
+    core::{Interface, HSTRING, PCWSTR},
     Graphics::Capture::GraphicsCaptureItem,
     Win32::{
         Devices::Display::{
@@ -13,12 +13,17 @@ use windows::{
             QDC_ONLY_ACTIVE_PATHS,
         },
         Foundation::{BOOL, LPARAM, POINT, RECT, TRUE},
+        Graphics::Dxgi::{
+            Common::{DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020, DXGI_COLOR_SPACE_TYPE},
+            CreateDXGIFactory1, IDXGIFactory1, IDXGIOutput6, DXGI_ERROR_NOT_FOUND,
+        },
         Graphics::Gdi::{
             EnumDisplayDevicesW, EnumDisplayMonitors, EnumDisplaySettingsW, GetMonitorInfoW,
             MonitorFromPoint, DEVMODEW, DISPLAY_DEVICEW, ENUM_CURRENT_SETTINGS, HDC, HMONITOR,
-            MONITORINFO, MONITORINFOEXW, MONITOR_DEFAULTTONULL,
+            MONITORINFO, MONITORINFOEXW, MONITOR_DEFAULTTONEAREST, MONITOR_DEFAULTTONULL,
         },
         System::WinRT::Graphics::Capture::IGraphicsCaptureItemInterop,
+        UI::HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI},
     },
 };
 
@@ -30,12 +35,16 @@ pub enum Error {
     NameNotFound,
     #[error("Monitor index is lower than one")]
     IndexIsLowerThanOne,
+    #[error("Monitor settings number is lower than one")]
+    SettingsNumberIsLowerThanOne,
     #[error("Failed to get monitor info")]
     FailedToGetMonitorInfo,
     #[error("Failed to get monitor settings")]
     FailedToGetMonitorSettings,
     #[error("Failed to get monitor name")]
     FailedToGetMonitorName,
+    #[error("Monitor reports the default refresh rate of the hardware instead of a real rate")]
+    UnknownRefreshRate,
     #[error("Failed to parse monitor index: {0}")]
     FailedToParseMonitorIndex(#[from] ParseIntError),
     #[error("Failed to convert windows string: {0}")]
@@ -44,6 +53,19 @@ pub enum Error {
     WindowsError(#[from] windows::core::Error),
 }
 
+/// Represents The HDR Metadata Of A Monitor
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct HdrMetadata {
+    /// The color space of the monitor output.
+    pub color_space: DXGI_COLOR_SPACE_TYPE,
+    /// The minimum luminance of the monitor in nits.
+    pub min_luminance: f32,
+    /// The maximum luminance of the monitor in nits, usually only reachable on a small area.
+    pub max_luminance: f32,
+    /// The maximum luminance of the monitor in nits that can be sustained on the whole screen.
+    pub max_full_frame_luminance: f32,
+}
+
 /// Represents A Monitor Device
 ///
 /// # Example
@@ -106,129 +128,177 @@ impl Monitor {
         Ok(monitor)
     }
 
-    /// Returns the index of the monitor.
+    /// Returns the monitor with the specified number shown in the Windows Display Settings.
+    ///
+    /// The settings number is a best-effort match, Windows doesn't expose the number it shows.
+    /// It follows the order of the active display paths reported by the display configuration,
+    /// which usually matches the Display Settings but doesn't always match the enumeration index
+    /// or the number in the `\\.\DISPLAYn` device name. Monitors that mirror another monitor
+    /// share its number.
+    ///
+    /// # Arguments
+    ///
+    /// * `number` - The number shown in the Display Settings. The number starts from 1.
     ///
     /// # Errors
     ///
-    /// Returns an `Error` if there is an error retrieving the monitor index.
+    /// Returns an `Error::SettingsNumberIsLowerThanOne` if the number is less than 1.
+    /// Returns an `Error::NotFound` if no monitor has the specified number.
     #[inline]
-    pub fn index(&self) -> Result<usize, Error> {
+    pub fn from_settings_number(number: u32) -> Result<Self, Error> {
+        if number < 1 {
+            return Err(Error::SettingsNumberIsLowerThanOne);
+        }
+
+        let device_names = Self::settings_device_names()?;
+        let device_name = device_names
+            .get(number as usize - 1)
+            .ok_or(Error::NotFound)?;
+
+        for monitor in Self::enumerate()? {
+            if &monitor.device_name()? == device_name {
+                return Ok(monitor);
+            }
+        }
+
+        Err(Error::NotFound)
+    }
+
+    /// Returns the number of the monitor shown in the Windows Display Settings.
+    ///
+    /// Like `Monitor::from_settings_number`, the number is a best-effort match.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::NotFound` if the monitor isn't part of an active display path.
+    #[inline]
+    pub fn settings_number(&self) -> Result<u32, Error> {
         let device_name = self.device_name()?;
-        Ok(device_name.replace("\\\\.\\DISPLAY", "").parse()?)
+
+        let position = Self::settings_device_names()?
+            .iter()
+            .position(|name| *name == device_name)
+            .ok_or(Error::NotFound)?;
+
+        Ok(u32::try_from(position).unwrap() + 1)
     }
 
-    /// Returns the name of the monitor.
+    /// Returns the monitor that contains the specified point, or the nearest monitor to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The x coordinate of the point in virtual screen coordinates.
+    /// * `y` - The y coordinate of the point in virtual screen coordinates.
     ///
     /// # Errors
     ///
-    /// Returns an `Error` if there is an error retrieving the monitor name.
+    /// Returns an `Error::NotFound` if no monitor could be found.
     #[inline]
-    pub fn name(&self) -> Result<String, Error> {
-        let mut monitor_info = MONITORINFOEXW {
-            monitorInfo: MONITORINFO {
-                cbSize: u32::try_from(mem::size_of::<MONITORINFOEXW>()).unwrap(),
-                rcMonitor: RECT::default(),
-                rcWork: RECT::default(),
-                dwFlags: 0,
-            },
-            szDevice: [0; 32],
-        };
-        if unsafe {
-            !GetMonitorInfoW(
-                HMONITOR(self.as_raw_hmonitor()),
-                std::ptr::addr_of_mut!(monitor_info).cast(),
-            )
-            .as_bool()
-        } {
-            return Err(Error::FailedToGetMonitorInfo);
+    pub fn from_point(x: i32, y: i32) -> Result<Self, Error> {
+        let point = POINT { x, y };
+        let monitor = unsafe { MonitorFromPoint(point, MONITOR_DEFAULTTONEAREST) };
+
+        if monitor.is_invalid() {
+            return Err(Error::NotFound);
         }
 
-        let mut number_of_paths = 0;
-        let mut number_of_modes = 0;
-        unsafe {
-            GetDisplayConfigBufferSizes(
-                QDC_ONLY_ACTIVE_PATHS,
-                &mut number_of_paths,
-                &mut number_of_modes,
-            )
-            .ok()?;
+        Ok(Self { monitor })
+    }
+
+    /// Checks if the monitor contains the specified point.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The x coordinate of the point in virtual screen coordinates.
+    /// * `y` - The y coordinate of the point in virtual screen coordinates.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the point is inside the monitor, `false` otherwise or if the monitor info can't be retrieved.
+    #[must_use]
+    #[inline]
+    pub fn contains_point(&self, x: i32, y: i32) -> bool {
+        let Ok(rect) = self.rect() else {
+            return false;
         };
 
-        let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); number_of_paths as usize];
-        let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); number_of_modes as usize];
-        unsafe {
-            QueryDisplayConfig(
-                QDC_ONLY_ACTIVE_PATHS,
-                &mut number_of_paths,
-                paths.as_mut_ptr(),
-                &mut number_of_modes,
-                modes.as_mut_ptr(),
-                None,
-            )
+        x >= rect.left && x < rect.right && y >= rect.top && y < rect.bottom
+    }
+
+    /// Returns the monitor with the highest refresh rate.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::NotFound` if no monitor reports a known refresh rate.
+    /// Returns an `Error` if there is an error retrieving the monitor refresh rates.
+    #[inline]
+    pub fn highest_refresh_rate() -> Result<Self, Error> {
+        let mut best = None;
+        for monitor in Self::enumerate()? {
+            let refresh_rate = match monitor.refresh_rate() {
+                Ok(refresh_rate) => refresh_rate,
+                Err(Error::UnknownRefreshRate) => continue,
+                Err(e) => return Err(e),
+            };
+            if best.is_none_or(|(_, best_refresh_rate)| refresh_rate > best_refresh_rate) {
+                best = Some((monitor, refresh_rate));
+            }
         }
-        .ok()?;
 
-        for path in paths {
-            let mut source = DISPLAYCONFIG_SOURCE_DEVICE_NAME {
-                header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
-                    r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME,
-                    size: u32::try_from(mem::size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>())
-                        .unwrap(),
-                    adapterId: path.sourceInfo.adapterId,
-                    id: path.sourceInfo.id,
-                },
-                viewGdiDeviceName: [0; 32],
+        best.map(|(monitor, _)| monitor).ok_or(Error::NotFound)
+    }
+
+    /// Returns the monitor with the lowest refresh rate.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::NotFound` if no monitor reports a known refresh rate.
+    /// Returns an `Error` if there is an error retrieving the monitor refresh rates.
+    #[inline]
+    pub fn lowest_refresh_rate() -> Result<Self, Error> {
+        let mut best = None;
+        for monitor in Self::enumerate()? {
+            let refresh_rate = match monitor.refresh_rate() {
+                Ok(refresh_rate) => refresh_rate,
+                Err(Error::UnknownRefreshRate) => continue,
+                Err(e) => return Err(e),
             };
+            if best.is_none_or(|(_, best_refresh_rate)| refresh_rate < best_refresh_rate) {
+                best = Some((monitor, refresh_rate));
+            }
+        }
 
-            let device_name = self.device_name()?;
-            let view_gdi_device_name = String::from_utf16(
-                &monitor_info
-                    .szDevice
-                    .as_slice()
-                    .iter()
-                    .take_while(|ch| **ch != 0x0000)
-                    .copied()
-                    .collect::<Vec<u16>>(),
-            )?;
+        best.map(|(monitor, _)| monitor).ok_or(Error::NotFound)
+    }
 
-            if unsafe { DisplayConfigGetDeviceInfo(&mut source.header) } == 0
-                && device_name == view_gdi_device_name
-            {
-                let mut target = DISPLAYCONFIG_TARGET_DEVICE_NAME {
-                    header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
-                        r#type: DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME,
-                        size: u32::try_from(mem::size_of::<DISPLAYCONFIG_TARGET_DEVICE_NAME>())
-                            .unwrap(),
-                        adapterId: path.sourceInfo.adapterId,
-                        id: path.targetInfo.id,
-                    },
-                    flags: DISPLAYCONFIG_TARGET_DEVICE_NAME_FLAGS::default(),
-                    outputTechnology: DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY::default(),
-                    edidManufactureId: 0,
-                    edidProductCodeId: 0,
-                    connectorInstance: 0,
-                    monitorFriendlyDeviceName: [0; 64],
-                    monitorDevicePath: [0; 128],
-                };
+    /// Returns the index of the monitor.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is an error retrieving the monitor index.
+    #[inline]
+    pub fn index(&self) -> Result<usize, Error> {
+        let device_name = self.device_name()?;
+        Ok(device_name.replace("\\\\.\\DISPLAY", "").parse()?)
+    }
 
-                if unsafe { DisplayConfigGetDeviceInfo(&mut target.header) } == 0 {
-                    let name = String::from_utf16(
-                        &target
-                            .monitorFriendlyDeviceName
-                            .as_slice()
-                            .iter()
-                            .take_while(|ch| **ch != 0x0000)
-                            .copied()
-                            .collect::<Vec<u16>>(),
-                    )?;
-                    return Ok(name);
-                }
+    /// Returns the name of the monitor.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is an error retrieving the monitor name.
+    #[inline]
+    pub fn name(&self) -> Result<String, Error> {
+        let device_name = self.device_name()?;
 
-                return Err(Error::FailedToGetMonitorInfo);
-            }
+        match Self::display_config_names()?
+            .into_iter()
+            .find(|(view_gdi_device_name, _)| *view_gdi_device_name == device_name)
+        {
+            Some((_, Some(name))) => Ok(name),
+            Some((_, None)) => Err(Error::FailedToGetMonitorInfo),
+            None => Err(Error::NameNotFound),
         }
-
-        Err(Error::NameNotFound)
     }
 
     /// Returns the device name of the monitor.
@@ -238,24 +308,7 @@ impl Monitor {
     /// Returns an `Error` if there is an error retrieving the monitor device name.
     #[inline]
     pub fn device_name(&self) -> Result<String, Error> {
-        let mut monitor_info = MONITORINFOEXW {
-            monitorInfo: MONITORINFO {
-                cbSize: u32::try_from(mem::size_of::<MONITORINFOEXW>()).unwrap(),
-                rcMonitor: RECT::default(),
-                rcWork: RECT::default(),
-                dwFlags: 0,
-            },
-            szDevice: [0; 32],
-        };
-        if unsafe {
-            !GetMonitorInfoW(
-                HMONITOR(self.as_raw_hmonitor()),
-                std::ptr::addr_of_mut!(monitor_info).cast(),
-            )
-            .as_bool()
-        } {
-            return Err(Error::FailedToGetMonitorInfo);
-        }
+        let monitor_info = self.monitor_info()?;
 
         let device_name = String::from_utf16(
             &monitor_info
@@ -277,24 +330,7 @@ impl Monitor {
     /// Returns an `Error` if there is an error retrieving the monitor device string.
     #[inline]
     pub fn device_string(&self) -> Result<String, Error> {
-        let mut monitor_info = MONITORINFOEXW {
-            monitorInfo: MONITORINFO {
-                cbSize: u32::try_from(mem::size_of::<MONITORINFOEXW>()).unwrap(),
-                rcMonitor: RECT::default(),
-                rcWork: RECT::default(),
-                dwFlags: 0,
-            },
-            szDevice: [0; 32],
-        };
-        if unsafe {
-            !GetMonitorInfoW(
-                HMONITOR(self.as_raw_hmonitor()),
-                std::ptr::addr_of_mut!(monitor_info).cast(),
-            )
-            .as_bool()
-        } {
-            return Err(Error::FailedToGetMonitorInfo);
-        }
+        let mut monitor_info = self.monitor_info()?;
 
         let mut display_device = DISPLAY_DEVICEW {
             cb: u32::try_from(mem::size_of::<DISPLAY_DEVICEW>()).unwrap(),
@@ -330,10 +366,150 @@ impl Monitor {
         Ok(device_string)
     }
 
-    /// Returns the refresh rate of the monitor in hertz.
+    /// Returns the position of the monitor's top-left corner in the virtual desktop.
     ///
     /// # Errors
     ///
+    /// Returns an `Error` if there is an error retrieving the monitor info.
+    #[inline]
+    pub fn position(&self) -> Result<(i32, i32), Error> {
+        let rect = self.rect()?;
+        Ok((rect.left, rect.top))
+    }
+
+    /// Returns the bounds of the monitor in the virtual desktop.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is an error retrieving the monitor info.
+    #[inline]
+    pub fn rect(&self) -> Result<RECT, Error> {
+        Ok(self.monitor_info()?.monitorInfo.rcMonitor)
+    }
+
+    /// Returns the bounds of the monitor in physical pixels of the virtual desktop.
+    ///
+    /// Unlike `rect` the bounds are not scaled for DPI unaware processes, so they match the
+    /// captured frames and `GetPhysicalCursorPos`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is an error retrieving the monitor settings.
+    #[inline]
+    pub(crate) fn physical_rect(&self) -> Result<RECT, Error> {
+        let mut device_mode = DEVMODEW {
+            dmSize: u16::try_from(mem::size_of::<DEVMODEW>()).unwrap(),
+            ..DEVMODEW::default()
+        };
+        let name = HSTRING::from(self.device_name()?);
+        if unsafe {
+            !EnumDisplaySettingsW(
+                PCWSTR(name.as_ptr()),
+                ENUM_CURRENT_SETTINGS,
+                &mut device_mode,
+            )
+            .as_bool()
+        } {
+            return Err(Error::FailedToGetMonitorSettings);
+        }
+
+        let position = unsafe { device_mode.Anonymous1.Anonymous2.dmPosition };
+        Ok(RECT {
+            left: position.x,
+            top: position.y,
+            right: position.x + i32::try_from(device_mode.dmPelsWidth).unwrap(),
+            bottom: position.y + i32::try_from(device_mode.dmPelsHeight).unwrap(),
+        })
+    }
+
+    /// Returns the work area of the monitor in the virtual desktop, which excludes the taskbar
+    /// and docked toolbars.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is an error retrieving the monitor info.
+    #[inline]
+    pub fn work_area(&self) -> Result<RECT, Error> {
+        Ok(self.monitor_info()?.monitorInfo.rcWork)
+    }
+
+    /// Returns the effective horizontal and vertical DPI of the monitor.
+    ///
+    /// This is the DPI of the scale factor set in the display settings, for example `144` at
+    /// 150% scaling. A DPI unaware process always gets `96`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is an error retrieving the monitor DPI.
+    #[inline]
+    pub fn dpi(&self) -> Result<(u32, u32), Error> {
+        let mut dpi_x = 0;
+        let mut dpi_y = 0;
+        unsafe { GetDpiForMonitor(self.monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y)? };
+
+        Ok((dpi_x, dpi_y))
+    }
+
+    /// Returns the HDR metadata of the monitor.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::NotFound` if no DXGI output belongs to the monitor.
+    /// Returns an `Error` if there is an error enumerating the DXGI outputs.
+    ///
+    /// # Returns
+    ///
+    /// The HDR metadata, or `None` if the monitor is not in HDR mode.
+    #[inline]
+    pub fn hdr_metadata(&self) -> Result<Option<HdrMetadata>, Error> {
+        let factory: IDXGIFactory1 = unsafe { CreateDXGIFactory1()? };
+
+        for adapter_index in 0.. {
+            let adapter = match unsafe { factory.EnumAdapters1(adapter_index) } {
+                Ok(adapter) => adapter,
+                Err(e) if e.code() == DXGI_ERROR_NOT_FOUND => break,
+                Err(e) => return Err(e.into()),
+            };
+
+            for output_index in 0.. {
+                let output = match unsafe { adapter.EnumOutputs(output_index) } {
+                    Ok(output) => output,
+                    Err(e) if e.code() == DXGI_ERROR_NOT_FOUND => break,
+                    Err(e) => return Err(e.into()),
+                };
+
+                let desc = unsafe { output.cast::<IDXGIOutput6>()?.GetDesc1()? };
+                if desc.Monitor != self.monitor {
+                    continue;
+                }
+
+                if desc.ColorSpace != DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020 {
+                    return Ok(None);
+                }
+
+                return Ok(Some(HdrMetadata {
+                    color_space: desc.ColorSpace,
+                    min_luminance: desc.MinLuminance,
+                    max_luminance: desc.MaxLuminance,
+                    max_full_frame_luminance: desc.MaxFullFrameLuminance,
+                }));
+            }
+        }
+
+        Err(Error::NotFound)
+    }
+
+    /// Returns the refresh rate of the current display mode of the monitor in hertz.
+    ///
+    /// Some drivers report a frequency of `0` or `1`, which means the default rate of the
+    /// hardware rather than a real rate. Those return `Error::UnknownRefreshRate` instead of a
+    /// nonsensical value, pace the capture with a fixed target frame rate in that case. Monitors
+    /// with a variable refresh rate, like G-Sync or FreeSync displays, report the maximum rate of
+    /// their current mode like any other monitor.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::UnknownRefreshRate` if the monitor reports the hardware default rate.
     /// Returns an `Error` if there is an error retrieving the monitor refresh rate.
     #[inline]
     pub fn refresh_rate(&self) -> Result<u32, Error> {
@@ -353,7 +529,7 @@ impl Monitor {
             return Err(Error::FailedToGetMonitorSettings);
         }
 
-        Ok(device_mode.dmDisplayFrequency)
+        known_refresh_rate(device_mode.dmDisplayFrequency)
     }
 
     /// Returns the width of the monitor in pixels.
@@ -430,6 +606,35 @@ impl Monitor {
         Ok(monitors)
     }
 
+    /// Returns a list of all monitors together with their names.
+    ///
+    /// The display configuration is queried once for all monitors, which is cheaper than calling
+    /// `name` on every monitor. Monitors without a friendly name fall back to their device name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is an error enumerating the monitors or querying the display
+    /// configuration.
+    #[inline]
+    pub fn enumerate_with_names() -> Result<Vec<(Self, String)>, Error> {
+        let names = Self::display_config_names()?;
+
+        Self::enumerate()?
+            .into_iter()
+            .map(|monitor| {
+                let device_name = monitor.device_name()?;
+                let name = names
+                    .iter()
+                    .find(|(view_gdi_device_name, _)| *view_gdi_device_name == device_name)
+                    .and_then(|(_, name)| name.clone())
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or(device_name);
+
+                Ok((monitor, name))
+            })
+            .collect()
+    }
+
     /// Creates a `Monitor` instance from a raw HMONITOR.
     ///
     /// # Arguments
@@ -450,6 +655,134 @@ impl Monitor {
         self.monitor.0
     }
 
+    // Get The GDI Device Name And Friendly Name Of Every Active Display Path
+    #[inline]
+    fn display_config_names() -> Result<Vec<(String, Option<String>)>, Error> {
+        let mut number_of_paths = 0;
+        let mut number_of_modes = 0;
+        unsafe {
+            GetDisplayConfigBufferSizes(
+                QDC_ONLY_ACTIVE_PATHS,
+                &mut number_of_paths,
+                &mut number_of_modes,
+            )
+            .ok()?;
+        };
+
+        let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); number_of_paths as usize];
+        let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); number_of_modes as usize];
+        unsafe {
+            QueryDisplayConfig(
+                QDC_ONLY_ACTIVE_PATHS,
+                &mut number_of_paths,
+                paths.as_mut_ptr(),
+                &mut number_of_modes,
+                modes.as_mut_ptr(),
+                None,
+            )
+        }
+        .ok()?;
+        paths.truncate(number_of_paths as usize);
+
+        let mut names = Vec::with_capacity(paths.len());
+        for path in paths {
+            let mut source = DISPLAYCONFIG_SOURCE_DEVICE_NAME {
+                header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+                    r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME,
+                    size: u32::try_from(mem::size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>())
+                        .unwrap(),
+                    adapterId: path.sourceInfo.adapterId,
+                    id: path.sourceInfo.id,
+                },
+                viewGdiDeviceName: [0; 32],
+            };
+
+            if unsafe { DisplayConfigGetDeviceInfo(&mut source.header) } != 0 {
+                continue;
+            }
+
+            let view_gdi_device_name = String::from_utf16(
+                &source
+                    .viewGdiDeviceName
+                    .as_slice()
+                    .iter()
+                    .take_while(|ch| **ch != 0x0000)
+                    .copied()
+                    .collect::<Vec<u16>>(),
+            )?;
+
+            let mut target = DISPLAYCONFIG_TARGET_DEVICE_NAME {
+                header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+                    r#type: DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME,
+                    size: u32::try_from(mem::size_of::<DISPLAYCONFIG_TARGET_DEVICE_NAME>())
+                        .unwrap(),
+                    adapterId: path.sourceInfo.adapterId,
+                    id: path.targetInfo.id,
+                },
+                flags: DISPLAYCONFIG_TARGET_DEVICE_NAME_FLAGS::default(),
+                outputTechnology: DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY::default(),
+                edidManufactureId: 0,
+                edidProductCodeId: 0,
+                connectorInstance: 0,
+                monitorFriendlyDeviceName: [0; 64],
+                monitorDevicePath: [0; 128],
+            };
+
+            let name = if unsafe { DisplayConfigGetDeviceInfo(&mut target.header) } == 0 {
+                Some(String::from_utf16(
+                    &target
+                        .monitorFriendlyDeviceName
+                        .as_slice()
+                        .iter()
+                        .take_while(|ch| **ch != 0x0000)
+                        .copied()
+                        .collect::<Vec<u16>>(),
+                )?)
+            } else {
+                None
+            };
+
+            names.push((view_gdi_device_name, name));
+        }
+
+        Ok(names)
+    }
+
+    // Get The Monitor Info Of The Monitor
+    #[inline]
+    fn monitor_info(&self) -> Result<MONITORINFOEXW, Error> {
+        let mut monitor_info = MONITORINFOEXW {
+            monitorInfo: MONITORINFO {
+                cbSize: u32::try_from(mem::size_of::<MONITORINFOEXW>()).unwrap(),
+                rcMonitor: RECT::default(),
+                rcWork: RECT::default(),
+                dwFlags: 0,
+            },
+            szDevice: [0; 32],
+        };
+        if unsafe {
+            !GetMonitorInfoW(
+                HMONITOR(self.as_raw_hmonitor()),
+                std::ptr::addr_of_mut!(monitor_info).cast(),
+            )
+            .as_bool()
+        } {
+            return Err(Error::FailedToGetMonitorInfo);
+        }
+
+        Ok(monitor_info)
+    }
+
+    // Get The GDI Device Names Of The Active Display Paths In Display Settings Order
+    #[inline]
+    fn settings_device_names() -> Result<Vec<String>, Error> {
+        let names = Self::display_config_names()?;
+
+        Ok(unique_device_names(
+            names.into_iter().map(|(device_name, _)| device_name),
+        ))
+    }
+
     // Callback Used For Enumerating All Monitors
     #[inline]
     unsafe extern "system" fn enum_monitors_callback(
@@ -466,6 +799,19 @@ impl Monitor {
     }
 }
 
+// Remove The Repeated Device Names Of Cloned Displays, They Have A Path Per Target But Share The
+// Same Source
+fn unique_device_names(device_names: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut unique = Vec::new();
+    for device_name in device_names {
+        if !unique.contains(&device_name) {
+            unique.push(device_name);
+        }
+    }
+
+    unique
+}
+
 // Implements TryFrom For Monitor To Convert It To GraphicsCaptureItem
 impl TryFrom<Monitor> for GraphicsCaptureItem {
     type Error = Error;
@@ -478,3 +824,90 @@ impl TryFrom<Monitor> for GraphicsCaptureItem {
         Ok(unsafe { interop.CreateForMonitor(monitor)? })
     }
 }
+
+// 0 And 1 Mean The Default Rate Of The Hardware
+const fn known_refresh_rate(display_frequency: u32) -> Result<u32, Error> {
+    if display_frequency <= 1 {
+        return Err(Error::UnknownRefreshRate);
+    }
+
+    Ok(display_frequency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_device_names_keeps_order() {
+        let device_names = [
+            "\\\\.\\DISPLAY2",
+            "\\\\.\\DISPLAY1",
+            "\\\\.\\DISPLAY2",
+            "\\\\.\\DISPLAY3",
+        ]
+        .map(String::from);
+
+        assert_eq!(
+            unique_device_names(device_names),
+            ["\\\\.\\DISPLAY2", "\\\\.\\DISPLAY1", "\\\\.\\DISPLAY3"]
+        );
+    }
+
+    #[test]
+    fn known_refresh_rate_rejects_hardware_default() {
+        assert!(matches!(
+            known_refresh_rate(0),
+            Err(Error::UnknownRefreshRate)
+        ));
+        assert!(matches!(
+            known_refresh_rate(1),
+            Err(Error::UnknownRefreshRate)
+        ));
+        assert!(matches!(known_refresh_rate(60), Ok(60)));
+        assert!(matches!(known_refresh_rate(144), Ok(144)));
+    }
+
+    #[test]
+    fn physical_rect_matches_size() {
+        for monitor in Monitor::enumerate().unwrap() {
+            let rect = monitor.physical_rect().unwrap();
+            assert_eq!(
+                u32::try_from(rect.right - rect.left).unwrap(),
+                monitor.width().unwrap()
+            );
+            assert_eq!(
+                u32::try_from(rect.bottom - rect.top).unwrap(),
+                monitor.height().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn refresh_rate_of_monitors() {
+        for monitor in Monitor::enumerate().unwrap() {
+            match monitor.refresh_rate() {
+                Ok(refresh_rate) => assert!(refresh_rate > 1),
+                Err(Error::UnknownRefreshRate) => (),
+                Err(error) => panic!("Unexpected refresh rate error: {error}"),
+            }
+        }
+    }
+
+    #[test]
+    fn settings_number_round_trip() {
+        for monitor in Monitor::enumerate().unwrap() {
+            let Ok(number) = monitor.settings_number() else {
+                continue;
+            };
+
+            let found = Monitor::from_settings_number(number).unwrap();
+            assert_eq!(found.device_name().unwrap(), monitor.device_name().unwrap());
+        }
+
+        assert!(matches!(
+            Monitor::from_settings_number(0),
+            Err(Error::SettingsNumberIsLowerThanOne)
+        ));
+    }
+}
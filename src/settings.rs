@@ -1,5 +1,9 @@
+use std::{any::Any, time::Duration};
+
 use windows::Graphics::Capture::GraphicsCaptureItem;
 
+use crate::{monitor::Monitor, window::Window};
+
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
 pub enum ColorFormat {
     Rgba16F = 10,
@@ -7,6 +11,52 @@ pub enum ColorFormat {
     Bgra8 = 87,
 }
 
+impl ColorFormat {
+    /// Get the recommended color format for capturing a monitor.
+    ///
+    /// A monitor in HDR mode is recommended `ColorFormat::Rgba16F`, which keeps the extended
+    /// range, while an SDR monitor is recommended `ColorFormat::Bgra8`, because a 16-bit float
+    /// capture of SDR content only doubles the memory and bandwidth. `ColorFormat::Bgra8` is
+    /// also what the video encoder takes. If the HDR state can't be queried `ColorFormat::Bgra8`
+    /// is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `monitor` - The monitor that will be captured.
+    ///
+    /// # Returns
+    ///
+    /// The recommended color format.
+    #[must_use]
+    #[inline]
+    pub fn recommended_for_monitor(monitor: &Monitor) -> Self {
+        match monitor.hdr_metadata() {
+            Ok(Some(_)) => Self::Rgba16F,
+            _ => Self::Bgra8,
+        }
+    }
+
+    /// Get the recommended color format for capturing a window.
+    ///
+    /// Uses the same mapping as `recommended_for_monitor` for the monitor the window is mostly
+    /// on, a window that isn't on any monitor is recommended `ColorFormat::Bgra8`.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The window that will be captured.
+    ///
+    /// # Returns
+    ///
+    /// The recommended color format.
+    #[must_use]
+    #[inline]
+    pub fn recommended_for_window(window: &Window) -> Self {
+        window.monitor().map_or(Self::Bgra8, |monitor| {
+            Self::recommended_for_monitor(&monitor)
+        })
+    }
+}
+
 impl Default for ColorFormat {
     #[must_use]
     #[inline]
@@ -16,10 +66,21 @@ impl Default for ColorFormat {
 }
 
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
+#[non_exhaustive]
 pub enum CursorCaptureSettings {
     Default,
     WithCursor,
     WithoutCursor,
+    /// Draw the cursor onto each frame in software, for OS builds that can't toggle cursor
+    /// capture.
+    ///
+    /// The cursor is placed relative to the captured monitor or window, so the capture item must
+    /// be created from one, otherwise starting the capture fails with
+    /// `graphics_capture_api::Error::CursorOverlayUnsupported`. The cursor position is read in
+    /// physical pixels like the frames, so the DPI awareness of the process doesn't matter. Only
+    /// `ColorFormat::Bgra8` frames get the cursor, and only the area under the cursor is copied
+    /// through GDI, frames the cursor isn't over are left untouched.
+    SoftwareOverlay,
 }
 
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
@@ -29,6 +90,140 @@ pub enum DrawBorderSettings {
     WithoutBorder,
 }
 
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum DirtyRegionSettings {
+    Default,
+    ReportOnly,
+    ReportAndRender,
+}
+
+/// Represents the point in time frame timestamps are measured from.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum EpochMode {
+    /// The first frame delivered to the frame handler, which gets a timestamp of zero.
+    FirstFrame,
+    /// The moment the capture session was started.
+    SessionStart,
+    /// The zero of the QPC timebase, the raw `SystemRelativeTime` of the frame. Use this to
+    /// correlate frames of multiple sessions or with other QPC based timestamps.
+    QpcZero,
+}
+
+impl Default for EpochMode {
+    #[inline]
+    fn default() -> Self {
+        Self::QpcZero
+    }
+}
+
+/// Represents the area of the capture item that is delivered.
+///
+/// New areas may be added in minor releases, so matches on it need a wildcard arm.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum CaptureAreaSettings {
+    /// The whole capture item, for windows this includes the drop shadow and borders.
+    Default,
+    /// Only the client area of the captured window, the capture item must be a `Window`.
+    ClientOnly,
+}
+
+/// Represents what a capture item was created from.
+///
+/// New item types may be added in minor releases, so matches on it need a wildcard arm.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum CaptureItemType {
+    /// The item captures a monitor.
+    Monitor(Monitor),
+    /// The item captures a window.
+    Window(Window),
+    /// The item was created directly, for example with the `GraphicsCapturePicker`, so its
+    /// source is unknown.
+    Unknown,
+}
+
+/// Used to convert a value into the `GraphicsCaptureItem` to capture, together with what the
+/// item was created from.
+///
+/// Settings that depend on the captured monitor or window, like
+/// `CaptureAreaSettings::ClientOnly`, read it from the item type instead of taking the monitor
+/// or window a second time. It is implemented for every item that converts into a
+/// `GraphicsCaptureItem`, items other than a `Monitor` or `Window` have the type
+/// `CaptureItemType::Unknown`.
+pub trait TryIntoCaptureItemWithType {
+    type Error;
+
+    /// Convert the value into a capture item and the type of the item.
+    ///
+    /// # Returns
+    ///
+    /// The capture item and what it was created from.
+    fn try_into_capture_item_with_type(
+        self,
+    ) -> Result<(GraphicsCaptureItem, CaptureItemType), Self::Error>;
+}
+
+// Resolves The Item Type Of Monitors And Windows, Any Other Item Doesn't Tell What It Was Created From
+impl<T: TryInto<GraphicsCaptureItem> + 'static> TryIntoCaptureItemWithType for T {
+    type Error = T::Error;
+
+    #[inline]
+    fn try_into_capture_item_with_type(
+        self,
+    ) -> Result<(GraphicsCaptureItem, CaptureItemType), Self::Error> {
+        let item: &dyn Any = &self;
+        let item_type = match (
+            item.downcast_ref::<Monitor>(),
+            item.downcast_ref::<Window>(),
+        ) {
+            (Some(monitor), _) => CaptureItemType::Monitor(*monitor),
+            (_, Some(window)) => CaptureItemType::Window(*window),
+            _ => CaptureItemType::Unknown,
+        };
+
+        Ok((self.try_into()?, item_type))
+    }
+}
+
+/// Represents a rectangle of the capture item to capture, relative to the item's top-left corner.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub struct CaptureRegion {
+    /// The x coordinate of the region.
+    pub x: u32,
+    /// The y coordinate of the region.
+    pub y: u32,
+    /// The width of the region.
+    pub width: u32,
+    /// The height of the region.
+    pub height: u32,
+}
+
+impl CaptureRegion {
+    /// Create a new capture region.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The x coordinate of the region.
+    /// * `y` - The y coordinate of the region.
+    /// * `width` - The width of the region.
+    /// * `height` - The height of the region.
+    ///
+    /// # Returns
+    ///
+    /// A new `CaptureRegion` instance.
+    #[must_use]
+    #[inline]
+    pub const fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
 #[derive(Eq, PartialEq, Clone, Debug)]
 /// Represents the settings for screen capturing.
 pub struct Settings<Flags, T: TryInto<GraphicsCaptureItem>> {
@@ -38,6 +233,24 @@ pub struct Settings<Flags, T: TryInto<GraphicsCaptureItem>> {
     pub(crate) cursor_capture: CursorCaptureSettings,
     /// Specifies whether to draw a border around the captured region.
     pub(crate) draw_border: DrawBorderSettings,
+    /// Specifies whether the OS should report the dirty regions of each frame.
+    pub(crate) dirty_region: DirtyRegionSettings,
+    /// Specifies whether frames without dirty regions are skipped.
+    pub(crate) deliver_on_change_only: bool,
+    /// The number of frames discarded after the capture started.
+    pub(crate) warmup_frames: u32,
+    /// The point in time frame timestamps are measured from.
+    pub(crate) timestamp_epoch: EpochMode,
+    /// The maximum time a single frame handler call may take before the capture is stopped.
+    pub(crate) handler_timeout: Option<Duration>,
+    /// The area of the capture item to capture.
+    pub(crate) capture_area: CaptureAreaSettings,
+    /// The region of the capture item to capture.
+    pub(crate) capture_region: Option<CaptureRegion>,
+    /// The maximum number of frames per second delivered to the frame handler.
+    pub(crate) target_frame_rate: Option<u32>,
+    /// Specifies whether the alpha channel of each frame is made opaque on the GPU.
+    pub(crate) force_opaque: bool,
     /// The color format for the captured graphics.
     pub(crate) color_format: ColorFormat,
     /// Additional flags for capturing graphics.
@@ -67,11 +280,239 @@ impl<Flags, T: TryInto<GraphicsCaptureItem>> Settings<Flags, T> {
             item,
             cursor_capture,
             draw_border,
+            dirty_region: DirtyRegionSettings::Default,
+            deliver_on_change_only: false,
+            warmup_frames: 0,
+            timestamp_epoch: EpochMode::QpcZero,
+            handler_timeout: None,
+            capture_area: CaptureAreaSettings::Default,
+            capture_region: None,
+            target_frame_rate: None,
+            force_opaque: false,
             color_format,
             flags,
         }
     }
 
+    /// Set the dirty region settings
+    ///
+    /// # Arguments
+    ///
+    /// * `dirty_region` - Whether the OS should report the changed regions of each frame.
+    ///
+    /// # Returns
+    ///
+    /// The settings with the dirty region settings applied
+    #[must_use]
+    #[inline]
+    pub const fn with_dirty_region(mut self, dirty_region: DirtyRegionSettings) -> Self {
+        self.dirty_region = dirty_region;
+        self
+    }
+
+    /// Set whether to deliver only frames that changed
+    ///
+    /// Frames whose dirty region list is empty are dropped before they reach the frame handler,
+    /// so an idle screen produces no handler calls. Dirty region reporting is enabled for this if
+    /// the dirty region settings are `DirtyRegionSettings::Default`. When the OS doesn't support
+    /// dirty regions every frame is delivered.
+    ///
+    /// Combined with `with_target_frame_rate`, the changes of a frame dropped by the throttle are
+    /// kept and the next frame the throttle lets through is delivered with them, even if that
+    /// frame has no dirty regions of its own.
+    ///
+    /// # Arguments
+    ///
+    /// * `deliver_on_change_only` - Whether to skip frames without changes.
+    ///
+    /// # Returns
+    ///
+    /// The settings with the delivery mode applied
+    #[must_use]
+    #[inline]
+    pub const fn with_deliver_on_change_only(mut self, deliver_on_change_only: bool) -> Self {
+        self.deliver_on_change_only = deliver_on_change_only;
+        self
+    }
+
+    /// Set the number of warm-up frames
+    ///
+    /// The first frames after the capture started are often blank or stale while the capture
+    /// pipeline warms up. This many frames are discarded before the first frame reaches the frame
+    /// handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `warmup_frames` - The number of frames to discard.
+    ///
+    /// # Returns
+    ///
+    /// The settings with the warm-up frames applied
+    #[must_use]
+    #[inline]
+    pub const fn with_warmup_frames(mut self, warmup_frames: u32) -> Self {
+        self.warmup_frames = warmup_frames;
+        self
+    }
+
+    /// Set the timestamp epoch
+    ///
+    /// Controls what `Frame::timespan` is measured from. With `EpochMode::FirstFrame` or
+    /// `EpochMode::SessionStart` the `VideoEncoder` starts its timeline at the epoch instead of
+    /// its first sample, so the video timeline matches `Frame::timespan`. Timespans passed to
+    /// `send_frame_buffer` or `send_audio_buffer` stay in the QPC timebase, the encoder subtracts
+    /// the same epoch from them. `CaptureControl::epoch_qpc` reports the epoch once it is known.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp_epoch` - The point in time frame timestamps are measured from.
+    ///
+    /// # Returns
+    ///
+    /// The settings with the timestamp epoch applied
+    #[must_use]
+    #[inline]
+    pub const fn with_timestamp_epoch(mut self, timestamp_epoch: EpochMode) -> Self {
+        self.timestamp_epoch = timestamp_epoch;
+        self
+    }
+
+    /// Set the handler timeout
+    ///
+    /// If a single `on_frame_arrived` call takes longer than the timeout, a watchdog thread stops
+    /// the capture and the capture returns `GraphicsCaptureApiError::HandlerTimedOut`. The hung
+    /// handler can't be interrupted, the capture thread exits once the handler returns.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler_timeout` - The maximum duration of a single frame handler call, `None` to disable the watchdog.
+    ///
+    /// # Returns
+    ///
+    /// The settings with the handler timeout applied
+    #[must_use]
+    #[inline]
+    pub const fn with_handler_timeout(mut self, handler_timeout: Option<Duration>) -> Self {
+        self.handler_timeout = handler_timeout;
+        self
+    }
+
+    /// Set the capture area
+    ///
+    /// With `CaptureAreaSettings::ClientOnly` each frame is cropped to the client area of the
+    /// captured window on the GPU, following the window when it is resized. The capture region is
+    /// then relative to the client area. Starting a capture of anything but a `Window` with
+    /// `CaptureAreaSettings::ClientOnly` fails with `Error::ClientAreaUnsupported`.
+    ///
+    /// # Arguments
+    ///
+    /// * `capture_area` - The area of the capture item to capture.
+    ///
+    /// # Returns
+    ///
+    /// The settings with the capture area applied
+    #[must_use]
+    #[inline]
+    pub const fn with_capture_area(mut self, capture_area: CaptureAreaSettings) -> Self {
+        self.capture_area = capture_area;
+        self
+    }
+
+    /// Set the capture region
+    ///
+    /// Each frame is cropped to the region on the GPU before it is passed to the frame handler,
+    /// the region is clamped to the bounds of the capture item. The OS always captures the whole
+    /// item, so the frame pool keeps full size frames and the crop is an extra GPU copy, it saves
+    /// the CPU copy and the processing of the pixels outside the region.
+    ///
+    /// # Arguments
+    ///
+    /// * `capture_region` - The region of the capture item to capture, `None` to capture the whole item.
+    ///
+    /// # Returns
+    ///
+    /// The settings with the capture region applied
+    #[must_use]
+    #[inline]
+    pub const fn with_capture_region(mut self, capture_region: Option<CaptureRegion>) -> Self {
+        self.capture_region = capture_region;
+        self
+    }
+
+    /// Set the target frame rate
+    ///
+    /// Frames that arrive sooner than `1 / target_frame_rate` after the previously delivered frame
+    /// are dropped before they are copied, unlike the minimum update interval of the OS this
+    /// applies to every source.
+    ///
+    /// # Arguments
+    ///
+    /// * `target_frame_rate` - The maximum number of frames per second, `None` or zero to deliver every frame.
+    ///
+    /// # Returns
+    ///
+    /// The settings with the target frame rate applied
+    #[must_use]
+    #[inline]
+    pub const fn with_target_frame_rate(mut self, target_frame_rate: Option<u32>) -> Self {
+        self.target_frame_rate = target_frame_rate;
+        self
+    }
+
+    /// Set whether to force the alpha channel to opaque
+    ///
+    /// Captured alpha values are undefined for most windows, which makes some players blend
+    /// recordings against garbage. With this enabled the video processor sets the alpha channel
+    /// of each frame to fully opaque on the GPU before the frame reaches the frame handler, at
+    /// the cost of a GPU pass and copy per frame. Only `ColorFormat::Bgra8` and
+    /// `ColorFormat::Rgba8` are supported, `FrameBuffer::force_opaque` does the same on the CPU.
+    /// If the GPU pass fails, the frame handler is notified with `on_closed` and the capture
+    /// returns `GraphicsCaptureApiError::GraphicsCaptureApiError`.
+    ///
+    /// # Arguments
+    ///
+    /// * `force_opaque` - Whether to make the alpha channel of each frame opaque.
+    ///
+    /// # Returns
+    ///
+    /// The settings with the alpha mode applied
+    #[must_use]
+    #[inline]
+    pub const fn with_force_opaque(mut self, force_opaque: bool) -> Self {
+        self.force_opaque = force_opaque;
+        self
+    }
+
+    /// Replace the flags of the settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `flags` - The new flags.
+    ///
+    /// # Returns
+    ///
+    /// The settings with the new flags
+    #[must_use]
+    #[inline]
+    pub(crate) fn with_flags<NewFlags>(self, flags: NewFlags) -> Settings<NewFlags, T> {
+        Settings {
+            item: self.item,
+            cursor_capture: self.cursor_capture,
+            draw_border: self.draw_border,
+            dirty_region: self.dirty_region,
+            deliver_on_change_only: self.deliver_on_change_only,
+            warmup_frames: self.warmup_frames,
+            timestamp_epoch: self.timestamp_epoch,
+            handler_timeout: self.handler_timeout,
+            capture_area: self.capture_area,
+            capture_region: self.capture_region,
+            target_frame_rate: self.target_frame_rate,
+            force_opaque: self.force_opaque,
+            color_format: self.color_format,
+            flags,
+        }
+    }
+
     /// Get the item
     ///
     /// # Returns
@@ -105,6 +546,105 @@ impl<Flags, T: TryInto<GraphicsCaptureItem>> Settings<Flags, T> {
         self.draw_border
     }
 
+    /// Get the dirty region settings
+    ///
+    /// # Returns
+    ///
+    /// The dirty region settings
+    #[must_use]
+    #[inline]
+    pub const fn dirty_region(&self) -> DirtyRegionSettings {
+        self.dirty_region
+    }
+
+    /// Get whether only frames that changed are delivered
+    ///
+    /// # Returns
+    ///
+    /// `true` if frames without dirty regions are skipped
+    #[must_use]
+    #[inline]
+    pub const fn deliver_on_change_only(&self) -> bool {
+        self.deliver_on_change_only
+    }
+
+    /// Get the number of warm-up frames
+    ///
+    /// # Returns
+    ///
+    /// The number of frames discarded after the capture started
+    #[must_use]
+    #[inline]
+    pub const fn warmup_frames(&self) -> u32 {
+        self.warmup_frames
+    }
+
+    /// Get the timestamp epoch
+    ///
+    /// # Returns
+    ///
+    /// The point in time frame timestamps are measured from
+    #[must_use]
+    #[inline]
+    pub const fn timestamp_epoch(&self) -> EpochMode {
+        self.timestamp_epoch
+    }
+
+    /// Get the handler timeout
+    ///
+    /// # Returns
+    ///
+    /// The handler timeout
+    #[must_use]
+    #[inline]
+    pub const fn handler_timeout(&self) -> Option<Duration> {
+        self.handler_timeout
+    }
+
+    /// Get the capture area settings
+    ///
+    /// # Returns
+    ///
+    /// The capture area settings
+    #[must_use]
+    #[inline]
+    pub const fn capture_area(&self) -> CaptureAreaSettings {
+        self.capture_area
+    }
+
+    /// Get the capture region
+    ///
+    /// # Returns
+    ///
+    /// The capture region
+    #[must_use]
+    #[inline]
+    pub const fn capture_region(&self) -> Option<CaptureRegion> {
+        self.capture_region
+    }
+
+    /// Get the target frame rate
+    ///
+    /// # Returns
+    ///
+    /// The target frame rate
+    #[must_use]
+    #[inline]
+    pub const fn target_frame_rate(&self) -> Option<u32> {
+        self.target_frame_rate
+    }
+
+    /// Get whether the alpha channel is forced to opaque
+    ///
+    /// # Returns
+    ///
+    /// `true` if the alpha channel of each frame is made opaque
+    #[must_use]
+    #[inline]
+    pub const fn force_opaque(&self) -> bool {
+        self.force_opaque
+    }
+
     /// Get the color format
     ///
     /// # Returns
@@ -127,3 +667,284 @@ impl<Flags, T: TryInto<GraphicsCaptureItem>> Settings<Flags, T> {
         &self.flags
     }
 }
+
+/// Builds `Settings` with named setters, everything but the item and the flags has a default.
+///
+/// # Example
+/// ```no_run
+/// use windows_capture::{
+///     monitor::Monitor,
+///     settings::{ColorFormat, CursorCaptureSettings, SettingsBuilder},
+/// };
+///
+/// let settings = SettingsBuilder::new(Monitor::primary().unwrap(), ())
+///     .cursor_capture(CursorCaptureSettings::WithoutCursor)
+///     .color_format(ColorFormat::Bgra8)
+///     .build();
+/// ```
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct SettingsBuilder<Flags, T: TryInto<GraphicsCaptureItem>> {
+    settings: Settings<Flags, T>,
+}
+
+impl<Flags, T: TryInto<GraphicsCaptureItem>> SettingsBuilder<Flags, T> {
+    /// Create a settings builder with the default settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The graphics capture item.
+    /// * `flags` - Additional flags for the capture settings that will be passed to user defined `new` function.
+    ///
+    /// # Returns
+    ///
+    /// A new `SettingsBuilder` instance.
+    #[must_use]
+    #[inline]
+    pub fn new(item: T, flags: Flags) -> Self {
+        Self {
+            settings: Settings::new(
+                item,
+                CursorCaptureSettings::Default,
+                DrawBorderSettings::Default,
+                ColorFormat::default(),
+                flags,
+            ),
+        }
+    }
+
+    /// Set whether to capture the cursor, by default `CursorCaptureSettings::Default`.
+    #[must_use]
+    #[inline]
+    pub const fn cursor_capture(mut self, cursor_capture: CursorCaptureSettings) -> Self {
+        self.settings.cursor_capture = cursor_capture;
+        self
+    }
+
+    /// Set whether to draw a border around the captured item, by default
+    /// `DrawBorderSettings::Default`.
+    #[must_use]
+    #[inline]
+    pub const fn draw_border(mut self, draw_border: DrawBorderSettings) -> Self {
+        self.settings.draw_border = draw_border;
+        self
+    }
+
+    /// Set the color format of the captured frames, by default `ColorFormat::Rgba8`.
+    #[must_use]
+    #[inline]
+    pub const fn color_format(mut self, color_format: ColorFormat) -> Self {
+        self.settings.color_format = color_format;
+        self
+    }
+
+    /// Set the dirty region settings, see `Settings::with_dirty_region`.
+    #[must_use]
+    #[inline]
+    pub const fn dirty_region(mut self, dirty_region: DirtyRegionSettings) -> Self {
+        self.settings.dirty_region = dirty_region;
+        self
+    }
+
+    /// Set whether to deliver only frames that changed, see `Settings::with_deliver_on_change_only`.
+    #[must_use]
+    #[inline]
+    pub const fn deliver_on_change_only(mut self, deliver_on_change_only: bool) -> Self {
+        self.settings.deliver_on_change_only = deliver_on_change_only;
+        self
+    }
+
+    /// Set the number of warm-up frames, see `Settings::with_warmup_frames`.
+    #[must_use]
+    #[inline]
+    pub const fn warmup_frames(mut self, warmup_frames: u32) -> Self {
+        self.settings.warmup_frames = warmup_frames;
+        self
+    }
+
+    /// Set the timestamp epoch, see `Settings::with_timestamp_epoch`.
+    #[must_use]
+    #[inline]
+    pub const fn timestamp_epoch(mut self, timestamp_epoch: EpochMode) -> Self {
+        self.settings.timestamp_epoch = timestamp_epoch;
+        self
+    }
+
+    /// Set the handler timeout, see `Settings::with_handler_timeout`.
+    #[must_use]
+    #[inline]
+    pub const fn handler_timeout(mut self, handler_timeout: Option<Duration>) -> Self {
+        self.settings.handler_timeout = handler_timeout;
+        self
+    }
+
+    /// Set the capture area, see `Settings::with_capture_area`.
+    #[must_use]
+    #[inline]
+    pub const fn capture_area(mut self, capture_area: CaptureAreaSettings) -> Self {
+        self.settings.capture_area = capture_area;
+        self
+    }
+
+    /// Set the capture region, see `Settings::with_capture_region`.
+    #[must_use]
+    #[inline]
+    pub const fn capture_region(mut self, capture_region: Option<CaptureRegion>) -> Self {
+        self.settings.capture_region = capture_region;
+        self
+    }
+
+    /// Set the target frame rate, see `Settings::with_target_frame_rate`.
+    #[must_use]
+    #[inline]
+    pub const fn target_frame_rate(mut self, target_frame_rate: Option<u32>) -> Self {
+        self.settings.target_frame_rate = target_frame_rate;
+        self
+    }
+
+    /// Set whether to force the alpha channel to opaque, see `Settings::with_force_opaque`.
+    #[must_use]
+    #[inline]
+    pub const fn force_opaque(mut self, force_opaque: bool) -> Self {
+        self.settings.force_opaque = force_opaque;
+        self
+    }
+
+    /// Build the settings.
+    ///
+    /// # Returns
+    ///
+    /// The settings with every option of the builder applied.
+    #[must_use]
+    #[inline]
+    pub fn build(self) -> Settings<Flags, T> {
+        self.settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Item That Never Converts, The Settings Tests Only Check What Is Stored
+    #[derive(Eq, PartialEq, Clone, Copy, Debug)]
+    struct TestItem(u32);
+
+    impl TryFrom<TestItem> for GraphicsCaptureItem {
+        type Error = ();
+
+        fn try_from(_: TestItem) -> Result<Self, Self::Error> {
+            Err(())
+        }
+    }
+
+    #[test]
+    fn builder_defaults_match_settings_new() {
+        let settings = SettingsBuilder::new(TestItem(1), 7_u8).build();
+
+        assert_eq!(
+            settings,
+            Settings::new(
+                TestItem(1),
+                CursorCaptureSettings::Default,
+                DrawBorderSettings::Default,
+                ColorFormat::default(),
+                7_u8,
+            )
+        );
+        assert_eq!(settings.item(), &TestItem(1));
+        assert_eq!(settings.dirty_region(), DirtyRegionSettings::Default);
+        assert!(!settings.deliver_on_change_only());
+        assert_eq!(settings.warmup_frames(), 0);
+        assert_eq!(settings.timestamp_epoch(), EpochMode::QpcZero);
+        assert_eq!(settings.handler_timeout(), None);
+        assert_eq!(settings.capture_area(), CaptureAreaSettings::Default);
+        assert_eq!(settings.capture_region(), None);
+        assert_eq!(settings.target_frame_rate(), None);
+        assert!(!settings.force_opaque());
+        assert_eq!(settings.flags(), &7);
+    }
+
+    #[test]
+    fn builder_round_trip() {
+        let capture_region = CaptureRegion::new(10, 20, 300, 200);
+        let settings = SettingsBuilder::new(TestItem(2), "flags")
+            .cursor_capture(CursorCaptureSettings::WithoutCursor)
+            .draw_border(DrawBorderSettings::WithoutBorder)
+            .color_format(ColorFormat::Bgra8)
+            .dirty_region(DirtyRegionSettings::ReportOnly)
+            .deliver_on_change_only(true)
+            .warmup_frames(3)
+            .timestamp_epoch(EpochMode::FirstFrame)
+            .handler_timeout(Some(Duration::from_millis(250)))
+            .capture_area(CaptureAreaSettings::ClientOnly)
+            .capture_region(Some(capture_region))
+            .target_frame_rate(Some(30))
+            .force_opaque(true)
+            .build();
+
+        assert_eq!(settings.item(), &TestItem(2));
+        assert_eq!(
+            settings.cursor_capture(),
+            CursorCaptureSettings::WithoutCursor
+        );
+        assert_eq!(settings.draw_border(), DrawBorderSettings::WithoutBorder);
+        assert_eq!(settings.color_format(), ColorFormat::Bgra8);
+        assert_eq!(settings.dirty_region(), DirtyRegionSettings::ReportOnly);
+        assert!(settings.deliver_on_change_only());
+        assert_eq!(settings.warmup_frames(), 3);
+        assert_eq!(settings.timestamp_epoch(), EpochMode::FirstFrame);
+        assert_eq!(settings.handler_timeout(), Some(Duration::from_millis(250)));
+        assert_eq!(settings.capture_area(), CaptureAreaSettings::ClientOnly);
+        assert_eq!(settings.capture_region(), Some(capture_region));
+        assert_eq!(settings.target_frame_rate(), Some(30));
+        assert!(settings.force_opaque());
+        assert_eq!(settings.flags(), &"flags");
+    }
+
+    #[test]
+    fn builder_matches_with_methods() {
+        let built = SettingsBuilder::new(TestItem(3), ())
+            .deliver_on_change_only(true)
+            .warmup_frames(5)
+            .timestamp_epoch(EpochMode::SessionStart)
+            .handler_timeout(Some(Duration::from_secs(1)))
+            .build();
+
+        let chained = Settings::new(
+            TestItem(3),
+            CursorCaptureSettings::Default,
+            DrawBorderSettings::Default,
+            ColorFormat::default(),
+            (),
+        )
+        .with_deliver_on_change_only(true)
+        .with_warmup_frames(5)
+        .with_timestamp_epoch(EpochMode::SessionStart)
+        .with_handler_timeout(Some(Duration::from_secs(1)));
+
+        assert_eq!(built, chained);
+    }
+
+    #[test]
+    fn epoch_mode_defaults_to_qpc_zero() {
+        assert_eq!(EpochMode::default(), EpochMode::QpcZero);
+    }
+
+    #[test]
+    fn item_type_of_monitor_and_window() {
+        let monitor = Monitor::primary().unwrap();
+        let (_, item_type) = monitor.try_into_capture_item_with_type().unwrap();
+        assert_eq!(item_type, CaptureItemType::Monitor(monitor));
+
+        let window = Window::foreground().unwrap();
+        let (_, item_type) = window.try_into_capture_item_with_type().unwrap();
+        assert_eq!(item_type, CaptureItemType::Window(window));
+    }
+
+    #[test]
+    fn item_type_of_other_items_is_unknown() {
+        let item = GraphicsCaptureItem::try_from(Monitor::primary().unwrap()).unwrap();
+        let (_, item_type) = item.try_into_capture_item_with_type().unwrap();
+        assert_eq!(item_type, CaptureItemType::Unknown);
+    }
+}
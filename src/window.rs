@@ -1,19 +1,35 @@
-use std::{ptr, string::FromUtf16Error};
+use std::{
+    ptr,
+    string::FromUtf16Error,
+    thread,
+    time::{Duration, Instant},
+};
 
 use windows::{
-    core::HSTRING,
+    core::{HSTRING, PWSTR},
     Graphics::Capture::GraphicsCaptureItem,
     Win32::{
-        Foundation::{BOOL, HWND, LPARAM, RECT, TRUE},
-        Graphics::Gdi::{MonitorFromWindow, MONITOR_DEFAULTTONULL},
+        Foundation::{CloseHandle, BOOL, FALSE, HWND, LPARAM, POINT, RECT, TRUE},
+        Graphics::{
+            Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED},
+            Gdi::{ClientToScreen, MonitorFromWindow, MONITOR_DEFAULTTONULL},
+        },
         System::{
-            Threading::GetCurrentProcessId, WinRT::Graphics::Capture::IGraphicsCaptureItemInterop,
+            Threading::{
+                GetCurrentProcessId, OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+                PROCESS_QUERY_LIMITED_INFORMATION,
+            },
+            WinRT::Graphics::Capture::IGraphicsCaptureItemInterop,
         },
-        UI::WindowsAndMessaging::{
-            EnumChildWindows, FindWindowW, GetClientRect, GetDesktopWindow, GetForegroundWindow,
-            GetWindowLongPtrW, GetWindowRect, GetWindowTextLengthW, GetWindowTextW,
-            GetWindowThreadProcessId, IsWindowVisible, GWL_EXSTYLE, GWL_STYLE, WS_CHILD,
-            WS_EX_TOOLWINDOW,
+        UI::{
+            HiDpi::GetDpiForWindow,
+            WindowsAndMessaging::{
+                EnumChildWindows, FindWindowExW, FindWindowW, GetClientRect, GetDesktopWindow,
+                GetForegroundWindow, GetWindowLongPtrW, GetWindowRect, GetWindowTextLengthW,
+                GetWindowTextW, GetWindowThreadProcessId, IsIconic, IsWindow, IsWindowVisible,
+                IsZoomed, ShowWindow, GWL_EXSTYLE, GWL_STYLE, SW_RESTORE, WS_CHILD,
+                WS_EX_TOOLWINDOW,
+            },
         },
     },
 };
@@ -26,6 +42,10 @@ pub enum Error {
     NoActiveWindow,
     #[error("Failed to find window with name: {0}")]
     NotFound(String),
+    #[error("Timed out waiting for the window to become capturable")]
+    CapturableTimeout,
+    #[error("Failed to restore the window, it is still minimized")]
+    RestoreFailed,
     #[error("Failed to convert windows string from UTF-16: {0}")]
     FailedToConvertWindowsString(#[from] FromUtf16Error),
     #[error("Windows API error: {0}")]
@@ -90,6 +110,46 @@ impl Window {
         Ok(Self { window })
     }
 
+    /// Returns the taskbar window of the primary monitor (`Shell_TrayWnd`).
+    ///
+    /// The taskbar is owned by Explorer, so it doesn't exist while Explorer is restarting and an
+    /// auto-hidden taskbar is captured as the thin strip that stays on screen. The taskbar isn't
+    /// returned by `Window::enumerate` because it is a tool window.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::NotFound` if the taskbar window is not found.
+    #[inline]
+    pub fn taskbar() -> Result<Self, Error> {
+        let class = HSTRING::from("Shell_TrayWnd");
+        let window = unsafe { FindWindowW(&class, None) }
+            .map_err(|_| Error::NotFound(String::from("Shell_TrayWnd")))?;
+
+        Ok(Self { window })
+    }
+
+    /// Returns the taskbar windows of the secondary monitors (`Shell_SecondaryTrayWnd`).
+    ///
+    /// Secondary taskbars only exist when the taskbar is shown on all displays, the same
+    /// limitations as `Window::taskbar` apply.
+    ///
+    /// # Returns
+    ///
+    /// The secondary taskbar windows, empty if there are none.
+    #[inline]
+    pub fn secondary_taskbars() -> Result<Vec<Self>, Error> {
+        let class = HSTRING::from("Shell_SecondaryTrayWnd");
+
+        let mut windows = Vec::new();
+        let mut after = HWND::default();
+        while let Ok(window) = unsafe { FindWindowExW(None, after, &class, None) } {
+            windows.push(Self { window });
+            after = window;
+        }
+
+        Ok(windows)
+    }
+
     /// Creates a `Window` instance from a window name substring.
     ///
     /// # Arguments
@@ -114,6 +174,40 @@ impl Window {
         target_window.map_or_else(|| Err(Error::NotFound(String::from(title))), Ok)
     }
 
+    /// Returns the window of the process with the specified executable name.
+    ///
+    /// The name is compared case-insensitively, for example `notepad.exe`. When several windows
+    /// match, the foreground window is preferred, otherwise the top-most window in z-order is
+    /// returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The executable name of the process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::NotFound` if no window of a process with the name is found.
+    #[inline]
+    pub fn from_process_name(name: &str) -> Result<Self, Error> {
+        let is_match = |window: &Self| {
+            window
+                .process_name()
+                .is_ok_and(|process_name| process_name.eq_ignore_ascii_case(name))
+        };
+
+        if let Ok(window) = Self::foreground() {
+            if is_match(&window) {
+                return Ok(window);
+            }
+        }
+
+        // Windows are enumerated in z-order, so the first match is the top-most one
+        Self::enumerate()?
+            .into_iter()
+            .find(is_match)
+            .ok_or_else(|| Error::NotFound(String::from(name)))
+    }
+
     /// Returns the title of the window.
     ///
     /// # Errors
@@ -143,6 +237,67 @@ impl Window {
         Ok(name)
     }
 
+    /// Returns the id of the process that created the window.
+    #[must_use]
+    #[inline]
+    pub fn process_id(&self) -> u32 {
+        let mut id = 0;
+        unsafe { GetWindowThreadProcessId(self.window, Some(&mut id)) };
+
+        id
+    }
+
+    /// Returns the executable name of the process that created the window, for example
+    /// `notepad.exe`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::WindowsError` if the process can't be opened or queried.
+    #[inline]
+    pub fn process_name(&self) -> Result<String, Error> {
+        let process =
+            unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, self.process_id())? };
+
+        let mut path = vec![0u16; 1024];
+        let mut len = u32::try_from(path.len()).unwrap();
+        let result = unsafe {
+            QueryFullProcessImageNameW(
+                process,
+                PROCESS_NAME_WIN32,
+                PWSTR(path.as_mut_ptr()),
+                &mut len,
+            )
+        };
+        unsafe { CloseHandle(process)? };
+        result?;
+
+        let path = String::from_utf16(&path[..len as usize])?;
+        let name = path.rsplit('\\').next().unwrap_or(&path);
+
+        Ok(String::from(name))
+    }
+
+    /// Returns the DPI of the window.
+    ///
+    /// The value depends on the DPI awareness of the window, a DPI unaware window always reports
+    /// `96`. Returns `0` if the window is invalid.
+    #[must_use]
+    #[inline]
+    pub fn dpi(&self) -> u32 {
+        unsafe { GetDpiForWindow(self.window) }
+    }
+
+    /// Returns the scale factor between the physical pixels of the window and logical
+    /// coordinates, for example `1.5` at 144 DPI.
+    ///
+    /// Captured frames are in physical pixels, divide by the scale factor to get logical
+    /// coordinates.
+    #[must_use]
+    #[inline]
+    pub fn scale_factor(&self) -> f32 {
+        self.dpi() as f32 / 96.0
+    }
+
     /// Returns the monitor that has the largest area of intersection with the window.
     ///
     /// Returns `None` if the window doesn't intersect with any monitor.
@@ -176,6 +331,41 @@ impl Window {
         }
     }
 
+    /// Returns the rectangle of the client area of the window in screen coordinates.
+    ///
+    /// Unlike `rect`, this excludes the borders, title bar and shadows of the window.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::WindowsError` if there is an error retrieving the client rectangle.
+    #[inline]
+    pub fn client_rect(&self) -> Result<RECT, Error> {
+        let mut rect = RECT::default();
+        unsafe { GetClientRect(self.window, &mut rect)? };
+
+        let mut top_left = POINT {
+            x: rect.left,
+            y: rect.top,
+        };
+        let mut bottom_right = POINT {
+            x: rect.right,
+            y: rect.bottom,
+        };
+        if unsafe {
+            !ClientToScreen(self.window, &mut top_left).as_bool()
+                || !ClientToScreen(self.window, &mut bottom_right).as_bool()
+        } {
+            return Err(Error::WindowsError(windows::core::Error::from_win32()));
+        }
+
+        Ok(RECT {
+            left: top_left.x,
+            top: top_left.y,
+            right: bottom_right.x,
+            bottom: bottom_right.y,
+        })
+    }
+
     /// Checks if the window is a valid window.
     ///
     /// # Returns
@@ -213,6 +403,106 @@ impl Window {
         true
     }
 
+    /// Checks if the window is minimized.
+    #[must_use]
+    #[inline]
+    pub fn is_minimized(&self) -> bool {
+        unsafe { IsIconic(self.window).as_bool() }
+    }
+
+    /// Checks if the window is maximized.
+    #[must_use]
+    #[inline]
+    pub fn is_maximized(&self) -> bool {
+        unsafe { IsZoomed(self.window).as_bool() }
+    }
+
+    /// Checks if the window is visible.
+    #[must_use]
+    #[inline]
+    pub fn is_visible(&self) -> bool {
+        unsafe { IsWindowVisible(self.window).as_bool() }
+    }
+
+    /// Restores the window if it is minimized or maximized.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::RestoreFailed` if the window is still minimized afterwards.
+    #[inline]
+    pub fn restore(&self) -> Result<(), Error> {
+        unsafe {
+            let _ = ShowWindow(self.window, SW_RESTORE);
+        };
+
+        if self.is_minimized() {
+            Err(Error::RestoreFailed)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks if the window is cloaked by the desktop window manager, for example when it is on
+    /// another virtual desktop or is a suspended UWP app.
+    #[must_use]
+    #[inline]
+    pub fn is_cloaked(&self) -> bool {
+        let mut cloaked = 0u32;
+        let result = unsafe {
+            DwmGetWindowAttribute(
+                self.window,
+                DWMWA_CLOAKED,
+                ptr::addr_of_mut!(cloaked).cast(),
+                u32::try_from(std::mem::size_of::<u32>()).unwrap(),
+            )
+        };
+
+        result.is_ok() && cloaked != 0
+    }
+
+    /// Waits until the window is a good capture target: an existing, visible window that is not
+    /// minimized, not cloaked and has a non-empty size. Starting a capture before that usually
+    /// yields black frames. Unlike `Window::is_valid`, tool windows and windows of the current
+    /// process are accepted, since they can be captured as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The maximum time to wait.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error::CapturableTimeout` if the window doesn't become capturable in time.
+    #[inline]
+    pub fn wait_until_capturable(&self, timeout: Duration) -> Result<(), Error> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+        let start = Instant::now();
+        loop {
+            if self.is_capturable() {
+                return Ok(());
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(Error::CapturableTimeout);
+            }
+
+            thread::sleep(POLL_INTERVAL.min(timeout - elapsed));
+        }
+    }
+
+    // Check If The Window Can Be Captured Without Yielding Black Frames
+    #[inline]
+    fn is_capturable(&self) -> bool {
+        let has_size = self
+            .rect()
+            .is_ok_and(|rect| rect.right > rect.left && rect.bottom > rect.top);
+
+        let is_window = unsafe { IsWindow(self.window).as_bool() };
+
+        is_window && self.is_visible() && !self.is_minimized() && !self.is_cloaked() && has_size
+    }
+
     /// Returns a list of all windows.
     ///
     /// # Errors
@@ -234,6 +524,49 @@ impl Window {
         Ok(windows)
     }
 
+    /// Returns a list of all windows of the process with the specified ID.
+    ///
+    /// The windows are in z-order, the top-most window comes first.
+    ///
+    /// # Arguments
+    ///
+    /// * `pid` - The ID of the process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is an error enumerating the windows.
+    #[inline]
+    pub fn enumerate_for_process(pid: u32) -> Result<Vec<Self>, Error> {
+        Ok(Self::enumerate()?
+            .into_iter()
+            .filter(|window| window.process_id() == pid)
+            .collect())
+    }
+
+    /// Returns a list of all windows of the processes with the specified executable name.
+    ///
+    /// The name is compared case-insensitively, for example `notepad.exe`. The windows are in
+    /// z-order, the top-most window comes first.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The executable name of the process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is an error enumerating the windows.
+    #[inline]
+    pub fn enumerate_for_process_name(name: &str) -> Result<Vec<Self>, Error> {
+        Ok(Self::enumerate()?
+            .into_iter()
+            .filter(|window| {
+                window
+                    .process_name()
+                    .is_ok_and(|process_name| process_name.eq_ignore_ascii_case(name))
+            })
+            .collect())
+    }
+
     /// Creates a `Window` instance from a raw HWND.
     ///
     /// # Arguments
@@ -277,3 +610,114 @@ impl TryFrom<Window> for GraphicsCaptureItem {
         Ok(unsafe { interop.CreateForWindow(window)? })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use windows::{
+        core::w,
+        Win32::UI::WindowsAndMessaging::{
+            CreateWindowExW, DestroyWindow, GetClassNameW, SW_MINIMIZE, WINDOW_EX_STYLE,
+            WS_OVERLAPPEDWINDOW, WS_VISIBLE,
+        },
+    };
+
+    use super::*;
+
+    // Create A Visible Top Level Window Owned By The Test Process
+    fn test_window() -> Window {
+        let window = unsafe {
+            CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("STATIC"),
+                w!("windows-capture test window"),
+                WS_OVERLAPPEDWINDOW | WS_VISIBLE,
+                0,
+                0,
+                200,
+                100,
+                None,
+                None,
+                None,
+                None,
+            )
+        }
+        .unwrap();
+
+        Window { window }
+    }
+
+    fn class_name(window: &Window) -> String {
+        let mut name = [0u16; 256];
+        let len = unsafe { GetClassNameW(window.window, &mut name) };
+
+        String::from_utf16_lossy(&name[..len as usize])
+    }
+
+    #[test]
+    fn taskbar_class() {
+        // Explorer may not be running, for example on a server core or while it restarts
+        match Window::taskbar() {
+            Ok(taskbar) => {
+                assert_eq!(class_name(&taskbar), "Shell_TrayWnd");
+                assert!(!Window::enumerate().unwrap().contains(&taskbar));
+            }
+            Err(Error::NotFound(class)) => assert_eq!(class, "Shell_TrayWnd"),
+            Err(error) => panic!("Unexpected taskbar error: {error}"),
+        }
+    }
+
+    #[test]
+    fn secondary_taskbars_class() {
+        let taskbars = Window::secondary_taskbars().unwrap();
+
+        assert!(taskbars
+            .iter()
+            .all(|taskbar| class_name(taskbar) == "Shell_SecondaryTrayWnd"));
+    }
+
+    #[test]
+    fn wait_until_capturable_accepts_own_window() {
+        let window = test_window();
+
+        assert!(!window.is_valid());
+        window
+            .wait_until_capturable(Duration::from_secs(5))
+            .unwrap();
+
+        unsafe { DestroyWindow(window.window).unwrap() };
+    }
+
+    #[test]
+    fn wait_until_capturable_times_out() {
+        let window = test_window();
+
+        unsafe {
+            let _ = ShowWindow(window.window, SW_MINIMIZE);
+        };
+        assert!(matches!(
+            window.wait_until_capturable(Duration::from_millis(50)),
+            Err(Error::CapturableTimeout)
+        ));
+
+        unsafe { DestroyWindow(window.window).unwrap() };
+        assert!(matches!(
+            window.wait_until_capturable(Duration::from_millis(50)),
+            Err(Error::CapturableTimeout)
+        ));
+    }
+
+    #[test]
+    fn restore_minimized_window() {
+        let window = test_window();
+
+        unsafe {
+            let _ = ShowWindow(window.window, SW_MINIMIZE);
+        };
+        assert!(window.is_minimized());
+
+        window.restore().unwrap();
+        assert!(!window.is_minimized());
+
+        unsafe { DestroyWindow(window.window).unwrap() };
+    }
+}
@@ -3,7 +3,10 @@
 #![allow(clippy::redundant_pub_crate)]
 #![allow(clippy::multiple_crate_versions)] // Should update as soon as possible
 
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use ::windows_capture::{
     capture::{
@@ -11,36 +14,121 @@ use ::windows_capture::{
         GraphicsCaptureApiHandler,
     },
     frame::{self, Frame},
-    graphics_capture_api::InternalCaptureControl,
+    graphics_capture_api::{CaptureStats, InternalCaptureControl},
     monitor::Monitor,
     settings::{ColorFormat, CursorCaptureSettings, DrawBorderSettings, Settings},
     window::Window,
 };
-use pyo3::{exceptions::PyException, prelude::*, types::PyList};
+use pyo3::{
+    exceptions::PyException,
+    prelude::*,
+    types::{PyDict, PyList},
+};
 
 /// Fastest Windows Screen Capture Library For Python 🔥.
 #[pymodule]
 fn windows_capture(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<NativeWindowsCapture>()?;
     m.add_class::<NativeCaptureControl>()?;
+    m.add_function(wrap_pyfunction!(list_monitors, m)?)?;
+    m.add_function(wrap_pyfunction!(list_windows, m)?)?;
     Ok(())
 }
 
+/// List The Monitors That Can Be Captured, The Index Can Be Passed As `monitor_index`
+///
+/// `refresh_rate` Is `None` For Monitors That Only Report The Default Rate Of The Hardware
+#[pyfunction]
+#[inline]
+pub fn list_monitors<'py>(py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    let monitors = Monitor::enumerate()
+        .map_err(|e| PyException::new_err(format!("Failed To Enumerate Monitors -> {e}")))?;
+    let primary = Monitor::primary()
+        .map_err(|e| PyException::new_err(format!("Failed To Get Primary Monitor -> {e}")))?;
+
+    let mut list = Vec::with_capacity(monitors.len());
+    for (index, monitor) in monitors.into_iter().enumerate() {
+        let map_err = |e: ::windows_capture::monitor::Error| {
+            PyException::new_err(format!("Failed To Get Monitor Info -> {e}"))
+        };
+
+        let refresh_rate = match monitor.refresh_rate() {
+            Ok(refresh_rate) => Some(refresh_rate),
+            Err(::windows_capture::monitor::Error::UnknownRefreshRate) => None,
+            Err(e) => return Err(map_err(e)),
+        };
+
+        let dict = PyDict::new_bound(py);
+        dict.set_item("index", index + 1)?;
+        dict.set_item("name", monitor.name().unwrap_or_default())?;
+        dict.set_item("width", monitor.width().map_err(map_err)?)?;
+        dict.set_item("height", monitor.height().map_err(map_err)?)?;
+        dict.set_item("refresh_rate", refresh_rate)?;
+        dict.set_item("primary", monitor == primary)?;
+        list.push(dict);
+    }
+
+    Ok(list)
+}
+
+/// List The Windows That Can Be Captured, The Title Can Be Passed As `window_name`
+///
+/// Windows That Close Or Fail To Report Their Title Or Size While Being Listed Are Skipped
+#[pyfunction]
+#[inline]
+pub fn list_windows<'py>(py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    let windows = Window::enumerate()
+        .map_err(|e| PyException::new_err(format!("Failed To Enumerate Windows -> {e}")))?;
+
+    let mut list = Vec::with_capacity(windows.len());
+    for window in windows {
+        // Windows Can Close While Being Listed, Skip The Ones That Can't Be Queried Anymore
+        let (Ok(rect), Ok(title)) = (window.rect(), window.title()) else {
+            continue;
+        };
+
+        let dict = PyDict::new_bound(py);
+        dict.set_item("title", title)?;
+        dict.set_item("process_name", window.process_name().unwrap_or_default())?;
+        dict.set_item("width", rect.right - rect.left)?;
+        dict.set_item("height", rect.bottom - rect.top)?;
+        list.push(dict);
+    }
+
+    Ok(list)
+}
+
 /// Internal Struct Used To Handle Free Threaded Start
 #[pyclass]
 pub struct NativeCaptureControl {
     capture_control:
         Option<CaptureControl<InnerNativeWindowsCapture, InnerNativeWindowsCaptureError>>,
+    start: Instant,
+    // Stats Of The Last `stats` Call, Kept After The Capture Thread Is Joined
+    last_stats: CaptureStats,
+    last_elapsed: Duration,
 }
 
 impl NativeCaptureControl {
     #[must_use]
     #[inline]
-    const fn new(
+    fn new(
         capture_control: CaptureControl<InnerNativeWindowsCapture, InnerNativeWindowsCaptureError>,
     ) -> Self {
         Self {
             capture_control: Some(capture_control),
+            start: Instant::now(),
+            last_stats: CaptureStats::default(),
+            last_elapsed: Duration::ZERO,
+        }
+    }
+
+    // Refresh The Stats While The Capture Thread Is Still Owned
+    #[inline]
+    fn update_stats(&mut self) {
+        if let Some(capture_control) = &self.capture_control {
+            self.last_stats = capture_control.stats();
+            self.last_elapsed = self.start.elapsed();
         }
     }
 }
@@ -55,8 +143,34 @@ impl NativeCaptureControl {
             .map_or(true, CaptureControl::is_finished)
     }
 
+    /// Get The Frame Statistics Of The Capture Session
+    ///
+    /// Returns A Dict With `frames_delivered`, `frames_dropped`, `fps` And `elapsed_seconds`, After
+    /// `wait` Or `stop` The Statistics From When They Were Called Are Returned
+    #[inline]
+    pub fn stats<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.update_stats();
+
+        let elapsed_seconds = self.last_elapsed.as_secs_f64();
+        let fps = if elapsed_seconds > 0.0 {
+            self.last_stats.delivered as f64 / elapsed_seconds
+        } else {
+            0.0
+        };
+
+        let dict = PyDict::new_bound(py);
+        dict.set_item("frames_delivered", self.last_stats.delivered)?;
+        dict.set_item("frames_dropped", self.last_stats.dropped)?;
+        dict.set_item("fps", fps)?;
+        dict.set_item("elapsed_seconds", elapsed_seconds)?;
+
+        Ok(dict)
+    }
+
     #[inline]
     pub fn wait(&mut self, py: Python) -> PyResult<()> {
+        self.update_stats();
+
         // But Honestly WTF Is This? You Know How Much Time It Took Me To Debug This?
         // Just Why? Who Decided This BS Threading Shit?
         py.allow_threads(|| {
@@ -90,6 +204,8 @@ impl NativeCaptureControl {
 
     #[inline]
     pub fn stop(&mut self, py: Python) -> PyResult<()> {
+        self.update_stats();
+
         // But Honestly WTF Is This? You Know How Much Time It Took Me To Debug This?
         // Just Why? Who TF Decided This BS Threading Shit?
         py.allow_threads(|| {
@@ -0,0 +1,25 @@
+import time
+
+from windows_capture import CaptureControl, Frame, InternalCaptureControl, WindowsCapture
+
+
+def test_stats_counts_delivered_frames() -> None:
+    capture = WindowsCapture()
+
+    @capture.event
+    def on_frame_arrived(frame: Frame, capture_control: InternalCaptureControl) -> None:
+        pass
+
+    @capture.event
+    def on_closed() -> None:
+        pass
+
+    capture_control: CaptureControl = capture.start_free_threaded()
+    time.sleep(1)
+    stats = capture_control.stats()
+    capture_control.stop()
+
+    assert stats["frames_delivered"] > 0
+    assert stats["frames_dropped"] >= 0
+    assert stats["elapsed_seconds"] > 0
+    assert stats["fps"] > 0
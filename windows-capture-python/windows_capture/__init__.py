@@ -1,6 +1,11 @@
 """Fastest Windows Screen Capture Library For Python 🔥."""
 
-from .windows_capture import NativeWindowsCapture, NativeCaptureControl
+from .windows_capture import (
+    NativeWindowsCapture,
+    NativeCaptureControl,
+    list_monitors,
+    list_windows,
+)
 import ctypes
 import numpy
 import cv2
@@ -103,6 +108,8 @@ class CaptureControl:
         Waits Until The Capturing Thread Stops
     stop():
         Gracefully Stop The Capture Thread
+    stats():
+        Gets The Frame Statistics Of The Capture Session
     """
 
     def __init__(self, native_capture_control: NativeCaptureControl) -> None:
@@ -121,6 +128,14 @@ class CaptureControl:
         """Gracefully Stop The Capture Thread"""
         self.native_capture_control.stop()
 
+    def stats(self) -> dict:
+        """
+        Gets The Frame Statistics Of The Capture Session
+
+        Returns A Dict With frames_delivered, frames_dropped, fps And elapsed_seconds
+        """
+        return self.native_capture_control.stats()
+
 
 class WindowsCapture:
     """